@@ -0,0 +1,3 @@
+ALTER TABLE transactions ADD COLUMN memo TEXT;
+ALTER TABLE transactions ADD COLUMN status TEXT NOT NULL DEFAULT 'uncleared'
+    CHECK(status IN ('uncleared', 'cleared', 'reconciled'));
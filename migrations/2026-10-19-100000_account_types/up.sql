@@ -0,0 +1,3 @@
+ALTER TABLE accounts ADD COLUMN account_type TEXT NOT NULL DEFAULT 'checking'
+    CHECK(account_type IN ('checking', 'savings', 'cash', 'credit_card', 'loan', 'investment'));
+ALTER TABLE accounts ADD COLUMN on_budget BOOLEAN NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+ALTER TABLE transactions ADD COLUMN transfer_account_id INTEGER REFERENCES accounts(id);
@@ -0,0 +1,15 @@
+CREATE TABLE users (
+    id INTEGER NOT NULL,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT)
+);
+
+CREATE TABLE tokens (
+    id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,33 @@
+CREATE TABLE tags_old (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    PRIMARY KEY(id AUTOINCREMENT)
+);
+INSERT INTO tags_old (id, name) SELECT id, name FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_old RENAME TO tags;
+
+CREATE TABLE buckets_old (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    PRIMARY KEY(id AUTOINCREMENT)
+);
+INSERT INTO buckets_old (id, name) SELECT id, name FROM buckets;
+DROP TABLE buckets;
+ALTER TABLE buckets_old RENAME TO buckets;
+
+CREATE TABLE accounts_old (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    account_type TEXT NOT NULL DEFAULT 'checking'
+        CHECK(account_type IN ('checking', 'savings', 'cash', 'credit_card', 'loan', 'investment')),
+    on_budget BOOLEAN NOT NULL DEFAULT 1,
+    PRIMARY KEY(id AUTOINCREMENT)
+);
+INSERT INTO accounts_old (id, name, account_type, on_budget)
+    SELECT id, name, account_type, on_budget FROM accounts;
+DROP TABLE accounts;
+ALTER TABLE accounts_old RENAME TO accounts;
+
+DROP TABLE budget_members;
+DROP TABLE budgets;
@@ -0,0 +1,62 @@
+CREATE TABLE budgets (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT)
+);
+
+CREATE TABLE budget_members (
+    budget_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL CHECK(role IN ('owner', 'editor', 'viewer')),
+    PRIMARY KEY(budget_id, user_id),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Existing data was shared by every user, it becomes their common budget,
+-- taken over by the first user to register when there are none yet
+INSERT INTO budgets (id, name) SELECT 1, 'Budget'
+    WHERE EXISTS (SELECT 1 FROM users) OR EXISTS (SELECT 1 FROM accounts)
+        OR EXISTS (SELECT 1 FROM buckets) OR EXISTS (SELECT 1 FROM tags);
+INSERT INTO budget_members (budget_id, user_id, role) SELECT 1, id, 'owner' FROM users;
+
+-- Names are now unique per budget, SQLite needs the tables to be rebuilt
+CREATE TABLE accounts_new (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    account_type TEXT NOT NULL DEFAULT 'checking'
+        CHECK(account_type IN ('checking', 'savings', 'cash', 'credit_card', 'loan', 'investment')),
+    on_budget BOOLEAN NOT NULL DEFAULT 1,
+    budget_id INTEGER NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    UNIQUE(budget_id, name),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id)
+);
+INSERT INTO accounts_new (id, name, account_type, on_budget, budget_id)
+    SELECT id, name, account_type, on_budget, 1 FROM accounts;
+DROP TABLE accounts;
+ALTER TABLE accounts_new RENAME TO accounts;
+
+CREATE TABLE buckets_new (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    budget_id INTEGER NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    UNIQUE(budget_id, name),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id)
+);
+INSERT INTO buckets_new (id, name, budget_id) SELECT id, name, 1 FROM buckets;
+DROP TABLE buckets;
+ALTER TABLE buckets_new RENAME TO buckets;
+
+CREATE TABLE tags_new (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    budget_id INTEGER NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    UNIQUE(budget_id, name),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id)
+);
+INSERT INTO tags_new (id, name, budget_id) SELECT id, name, 1 FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_new RENAME TO tags;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN admin BOOLEAN NOT NULL DEFAULT 0;
+
+-- The first user registered freely, they administrate the instance
+UPDATE users SET admin = 1 WHERE id = (SELECT MIN(id) FROM users);
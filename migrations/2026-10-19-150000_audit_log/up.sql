@@ -0,0 +1,17 @@
+CREATE TABLE audit_log (
+    id INTEGER NOT NULL,
+    budget_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    entity TEXT NOT NULL CHECK(entity IN ('account', 'bucket', 'transaction', 'fill')),
+    entity_id INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('create', 'update', 'delete')),
+    -- JSON of the row before and after the change, missing on create and delete
+    before TEXT,
+    after TEXT,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+CREATE INDEX audit_log_entity ON audit_log (budget_id, entity, entity_id);
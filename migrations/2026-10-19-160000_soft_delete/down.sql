@@ -0,0 +1,4 @@
+DELETE FROM transactions WHERE deleted_at IS NOT NULL;
+DELETE FROM fills WHERE deleted_at IS NOT NULL;
+ALTER TABLE transactions DROP COLUMN deleted_at;
+ALTER TABLE fills DROP COLUMN deleted_at;
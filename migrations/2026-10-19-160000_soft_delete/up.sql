@@ -0,0 +1,3 @@
+-- Deleted transactions and fills stay in the trash until restored or purged
+ALTER TABLE transactions ADD COLUMN deleted_at DATETIME;
+ALTER TABLE fills ADD COLUMN deleted_at DATETIME;
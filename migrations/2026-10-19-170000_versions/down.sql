@@ -0,0 +1,4 @@
+ALTER TABLE accounts DROP COLUMN version;
+ALTER TABLE transactions DROP COLUMN version;
+ALTER TABLE buckets DROP COLUMN version;
+ALTER TABLE fills DROP COLUMN version;
@@ -0,0 +1,5 @@
+-- Incremented on every update, compared against `If-Match` for optimistic concurrency
+ALTER TABLE accounts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE transactions ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE buckets ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE fills ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+DROP INDEX transactions_import_id;
+ALTER TABLE transactions DROP COLUMN import_id;
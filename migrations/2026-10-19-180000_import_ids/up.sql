@@ -0,0 +1,3 @@
+-- Reference of the bank for imported transactions, imported once per account
+ALTER TABLE transactions ADD COLUMN import_id TEXT;
+CREATE UNIQUE INDEX transactions_import_id ON transactions (account_id, import_id);
@@ -0,0 +1,2 @@
+-- Starting entry of an account, part of its balance but not income
+ALTER TABLE transactions ADD COLUMN opening BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+DROP TABLE exchange_rates;
+ALTER TABLE budgets DROP COLUMN currency;
+ALTER TABLE accounts DROP COLUMN currency;
@@ -0,0 +1,16 @@
+-- Currency of the amounts of an account, as an ISO 4217 code
+ALTER TABLE accounts ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+-- Currency the amounts of every account are converted to
+ALTER TABLE budgets ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+
+-- Value of one unit of `currency` in the currency of the budget, from `date` on
+CREATE TABLE exchange_rates (
+    id INTEGER NOT NULL,
+    currency TEXT NOT NULL,
+    date DATETIME NOT NULL,
+    rate REAL NOT NULL CHECK(rate > 0),
+    budget_id INTEGER NOT NULL,
+    PRIMARY KEY(id AUTOINCREMENT),
+    UNIQUE(budget_id, currency, date),
+    FOREIGN KEY(budget_id) REFERENCES budgets(id) ON DELETE CASCADE
+);
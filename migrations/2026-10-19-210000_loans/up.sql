@@ -0,0 +1,12 @@
+-- Terms of a loan account, repaid by monthly payments from `start_date` on
+CREATE TABLE loans (
+    account_id INTEGER NOT NULL,
+    principal REAL NOT NULL CHECK(principal > 0),
+    -- Yearly rate in percent
+    interest_rate REAL NOT NULL CHECK(interest_rate >= 0),
+    -- Number of monthly payments
+    term INTEGER NOT NULL CHECK(term > 0),
+    start_date DATETIME NOT NULL,
+    PRIMARY KEY(account_id),
+    FOREIGN KEY(account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,33 @@
+-- Id shared by both legs of a transfer, the one of its outgoing leg
+ALTER TABLE transactions ADD COLUMN transfer_id INTEGER;
+CREATE INDEX transactions_transfer_id ON transactions(transfer_id);
+
+UPDATE transactions SET transfer_id = id
+    WHERE transfer_account_id IS NOT NULL AND amount < 0;
+
+-- Received legs are paired with the sent leg of the same accounts, date and
+-- name, identical transfers of a day in the order they were made
+UPDATE transactions SET transfer_id = (
+    SELECT sent.id
+    FROM (
+        SELECT id, account_id, transfer_account_id, date, name, ROW_NUMBER() OVER (
+            PARTITION BY account_id, transfer_account_id, date, name ORDER BY id
+        ) AS rank
+        FROM transactions
+        WHERE transfer_account_id IS NOT NULL AND amount < 0
+    ) AS sent
+    INNER JOIN (
+        SELECT id, account_id, transfer_account_id, date, name, ROW_NUMBER() OVER (
+            PARTITION BY account_id, transfer_account_id, date, name ORDER BY id
+        ) AS rank
+        FROM transactions
+        WHERE transfer_account_id IS NOT NULL AND amount >= 0
+    ) AS received
+        ON received.account_id = sent.transfer_account_id
+        AND received.transfer_account_id = sent.account_id
+        AND received.date = sent.date
+        AND received.name = sent.name
+        AND received.rank = sent.rank
+    WHERE received.id = transactions.id
+)
+WHERE transfer_account_id IS NOT NULL AND amount >= 0;
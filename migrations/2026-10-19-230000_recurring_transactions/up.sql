@@ -0,0 +1,16 @@
+-- Transaction planned every `frequency` from `next_date` on, expanded by the
+-- forecast
+CREATE TABLE recurring_transactions (
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    amount REAL NOT NULL,
+    -- Date of the next occurrence
+    next_date DATETIME NOT NULL,
+    -- `weekly`, `monthly` or `yearly`
+    frequency TEXT NOT NULL,
+    account_id INTEGER NOT NULL,
+    bucket_id INTEGER,
+    PRIMARY KEY(id AUTOINCREMENT),
+    FOREIGN KEY(account_id) REFERENCES accounts(id),
+    FOREIGN KEY(bucket_id) REFERENCES buckets(id)
+);
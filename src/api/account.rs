@@ -1,106 +1,311 @@
 use crate::models;
 use crate::schema;
 
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::Local;
+use diesel::expression::dsl::sum;
+use diesel::result::DatabaseErrorKind;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
 use rocket::fairing::AdHoc;
-use rocket::response::status::{Conflict, Created, NotFound};
-use rocket::serde::json::Json;
-use rocket::{delete, get, post, put, routes};
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::json::{Json, Value};
+use rocket::serde::Serialize;
+use rocket::{delete, get, patch, post, put, routes};
 
+use super::audit;
+use super::auth::Admin;
+use super::budget::{budget_accounts, Editor, Member};
+use super::etag::{IfMatch, Tagged};
+use super::exchange_rate::Rates;
+use super::patch::merge;
+use super::transaction::insert_opening;
 use crate::DbConnection;
-use models::{Account, AccountForm};
+use models::{Account, AccountForm, Currency};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Balance {
+    account_id: i32,
+    // In the currency of the account
+    balance: f32,
+    currency: Currency,
+    // In the currency of the budget, at the latest rate
+    base_balance: f32,
+}
 
 #[get("/")]
-async fn list(db: DbConnection) -> Json<Vec<Account>> {
-    db.run(|conn| schema::accounts::table.load::<Account>(conn))
-        .await
-        .map(Json)
-        .unwrap()
+async fn list(db: DbConnection, member: Member) -> Json<Vec<Account>> {
+    db.run(move |conn| {
+        schema::accounts::table
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .load::<Account>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
 }
 
 #[get("/<account_id>")]
-async fn read(db: DbConnection, account_id: i32) -> Result<Json<Account>, NotFound<&'static str>> {
+async fn read(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+) -> Result<Tagged<Account>, NotFound<&'static str>> {
     db.run(move |conn| {
         schema::accounts::table
             .filter(schema::accounts::id.eq(account_id))
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
             .first::<Account>(conn)
     })
     .await
     .map_err(|_| NotFound("Account not found."))
+    .map(Tagged)
+}
+
+#[get("/<account_id>/balance")]
+async fn balance(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+) -> Result<Json<Balance>, Custom<String>> {
+    db.run(move |conn| {
+        let currency = match budget_accounts(member.budget_id)
+            .filter(schema::accounts::id.eq(account_id))
+            .select(schema::accounts::currency)
+            .first::<Currency>(conn)
+            .optional()?
+        {
+            Some(currency) => currency,
+            None => {
+                return Ok(Err(Custom(
+                    Status::NotFound,
+                    String::from("Account not found."),
+                )))
+            }
+        };
+        let balance = schema::transactions::table
+            .filter(schema::transactions::account_id.eq(account_id))
+            .filter(schema::transactions::deleted_at.is_null())
+            .select(sum(schema::transactions::amount))
+            .first::<Option<f32>>(conn)?
+            .unwrap_or_default();
+        let rates = Rates::load(conn, member.budget_id)?;
+        Ok(rates
+            .convert(balance, &currency, Local::now().naive_local())
+            .map(|base_balance| Balance {
+                account_id,
+                balance,
+                base_balance,
+                currency,
+            }))
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
     .map(Json)
 }
 
 #[post("/", data = "<account_form>")]
 async fn create(
     db: DbConnection,
+    editor: Editor,
     account_form: Json<AccountForm>,
 ) -> Result<Created<Json<Account>>, Conflict<&'static str>> {
-    db.run(move |conn| {
-        diesel::insert_into(schema::accounts::table)
-            .values(&*account_form)
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| Conflict(Some("Account already exists.")))?;
-    Ok(Created::new("/").body(get_last_account(&db).await.map(Json).unwrap()))
+    let mut account_form = account_form.into_inner();
+    db.run(move |conn| conn.transaction(|| insert_account(conn, &editor, &mut account_form)))
+        .await
+        .map_err(|_: diesel::result::Error| Conflict(Some("Account already exists.")))
+        .map(|account| Created::new("/").body(Json(account)))
 }
 
 #[delete("/<account_id>")]
-async fn delete(db: DbConnection, account_id: i32) -> Result<(), Conflict<String>> {
+async fn delete(db: DbConnection, editor: Editor, account_id: i32) -> Result<(), Conflict<String>> {
     db.run(move |conn| {
-        diesel::delete(schema::accounts::table)
-            .filter(schema::accounts::id.eq(account_id))
-            .execute(conn)
+        conn.transaction(|| {
+            let query = schema::accounts::table
+                .filter(schema::accounts::id.eq(account_id))
+                .filter(schema::accounts::budget_id.eq(editor.budget_id));
+            let account = query.first::<Account>(conn).optional()?;
+            diesel::delete(query).execute(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                account.as_ref(),
+                None,
+            )
+        })
     })
     .await
-    .map_err(|e| Conflict(Some(e.to_string())))?;
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))?;
     Ok(())
 }
 
 #[put("/<account_id>", data = "<account_form>")]
 async fn update(
     db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
     account_form: Json<AccountForm>,
     account_id: i32,
-) -> Json<Account> {
+) -> Result<Tagged<Account>, Custom<String>> {
+    let account_form = account_form.into_inner();
+    update_account(db, editor, if_match, account_id, move |_| Ok(account_form)).await
+}
+
+// Changes only the fields present in the JSON object
+#[patch("/<account_id>", data = "<patch>")]
+async fn patch(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    patch: Json<Value>,
+    account_id: i32,
+) -> Result<Tagged<Account>, Custom<String>> {
+    update_account(db, editor, if_match, account_id, move |before| {
+        merge(before, &patch)
+    })
+    .await
+}
+
+// Updates an account with the form built from its current state
+async fn update_account<F>(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    account_id: i32,
+    account_form: F,
+) -> Result<Tagged<Account>, Custom<String>>
+where
+    F: FnOnce(&Account) -> Result<AccountForm, Custom<String>> + Send + 'static,
+{
     db.run(move |conn| {
-        diesel::update(schema::accounts::table)
-            .filter(schema::accounts::id.eq(account_id))
-            .set(&*account_form)
-            .execute(conn)
-            .unwrap();
-        schema::accounts::table
-            .filter(schema::accounts::id.eq(account_id))
-            .first::<Account>(conn)
+        conn.transaction(|| {
+            let query = schema::accounts::table
+                .filter(schema::accounts::id.eq(account_id))
+                .filter(schema::accounts::budget_id.eq(editor.budget_id));
+            let before = match query.first::<Account>(conn).optional()? {
+                Some(before) => before,
+                None => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Account not found."),
+                    )))
+                }
+            };
+            let account_form = match if_match.check(&before).and_then(|_| account_form(&before)) {
+                Ok(account_form) => account_form,
+                Err(e) => return Ok(Err(e)),
+            };
+            // Amounts are stored in the currency of the account, changing it
+            // would change what they are worth
+            let currency_changed = account_form
+                .currency
+                .as_ref()
+                .is_some_and(|currency| *currency != before.currency);
+            if currency_changed {
+                let transactions = schema::transactions::table
+                    .filter(schema::transactions::account_id.eq(account_id))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if transactions > 0 {
+                    return Ok(Err(Custom(
+                        Status::Conflict,
+                        String::from("Cannot change the currency of an account with transactions."),
+                    )));
+                }
+            }
+            diesel::update(query)
+                .set((
+                    account_form.columns(),
+                    schema::accounts::version.eq(schema::accounts::version + 1),
+                ))
+                .execute(conn)?;
+            let after = query.first::<Account>(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                Some(&before),
+                Some(&after),
+            )?;
+            Ok(Ok(after))
+        })
     })
     .await
-    .map(Json)
-    .unwrap()
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Custom(Status::Conflict, String::from("Account already exists."))
+        }
+        _ => Custom(Status::InternalServerError, e.to_string()),
+    })?
+    .map(Tagged)
 }
 
+// Deletes every account of the budget, restricted to administrators
 #[delete("/")]
-async fn destroy(db: DbConnection) {
-    db.run(|conn| diesel::delete(schema::accounts::table).execute(conn))
-        .await
-        .unwrap();
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let query =
+                schema::accounts::table.filter(schema::accounts::budget_id.eq(editor.budget_id));
+            let accounts = query.load::<Account>(conn)?;
+            diesel::delete(query).execute(conn)?;
+            for account in &accounts {
+                audit::record(conn, editor.budget_id, editor.user_id, Some(account), None)?;
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+// Inserts an account in the currency of its budget unless given, along with
+// its opening balance
+pub(crate) fn insert_account(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    account_form: &mut AccountForm,
+) -> QueryResult<Account> {
+    if account_form.currency.is_none() {
+        account_form.currency = Some(
+            schema::budgets::table
+                .filter(schema::budgets::id.eq(editor.budget_id))
+                .select(schema::budgets::currency)
+                .first::<Currency>(conn)?,
+        );
+    }
+    diesel::insert_into(schema::accounts::table)
+        .values((
+            account_form.columns(),
+            schema::accounts::budget_id.eq(editor.budget_id),
+        ))
+        .execute(conn)?;
+    let account = get_last_account(conn, editor.budget_id)?;
+    audit::record(conn, editor.budget_id, editor.user_id, None, Some(&account))?;
+    if let Some(opening) = account_form.opening(account.id) {
+        insert_opening(conn, editor, &opening)?;
+    }
+    Ok(account)
 }
 
 // While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
 // Currently replacing this function manually
-async fn get_last_account(db: &DbConnection) -> Result<Account, diesel::result::Error> {
-    db.run(|conn| {
-        schema::accounts::table
-            .order(schema::accounts::id.desc())
-            .first::<Account>(conn)
-    })
-    .await
+fn get_last_account(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Account> {
+    schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::accounts::id.desc())
+        .first::<Account>(conn)
 }
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Account CRUD", |rocket| async {
         rocket.mount(
             "/account",
-            routes![read, create, list, delete, update, destroy],
+            routes![read, create, list, delete, update, patch, destroy, balance],
         )
     })
 }
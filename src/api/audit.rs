@@ -0,0 +1,277 @@
+use crate::models;
+use crate::schema;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::serde::json::{self, Json, Value};
+use rocket::serde::Serialize;
+use rocket::{get, routes};
+
+use super::budget::Member;
+use super::date::Date;
+use crate::DbConnection;
+use models::{Account, AuditAction, AuditEntity, Bucket, Fill, NewAuditEntry, Transaction};
+
+// Rows whose changes are recorded in the audit log
+pub(crate) trait Audited: Serialize {
+    const ENTITY: AuditEntity;
+
+    fn id(&self) -> i32;
+}
+
+impl Audited for Account {
+    const ENTITY: AuditEntity = AuditEntity::Account;
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Audited for Bucket {
+    const ENTITY: AuditEntity = AuditEntity::Bucket;
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Audited for Transaction {
+    const ENTITY: AuditEntity = AuditEntity::Transaction;
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Audited for Fill {
+    const ENTITY: AuditEntity = AuditEntity::Fill;
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+#[derive(Default)]
+struct AuditFilter {
+    entity: Option<AuditEntity>,
+    entity_id: Option<i32>,
+    action: Option<AuditAction>,
+    user_id: Option<i32>,
+    // Days included
+    from: Option<Date>,
+    to: Option<Date>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AuditEntry {
+    id: i32,
+    entity: AuditEntity,
+    entity_id: i32,
+    action: AuditAction,
+    before: Option<Value>,
+    after: Option<Value>,
+    user_id: i32,
+    username: String,
+    created_at: NaiveDateTime,
+}
+
+type AuditRow = (
+    i32,
+    AuditEntity,
+    i32,
+    AuditAction,
+    Option<String>,
+    Option<String>,
+    i32,
+    String,
+    NaiveDateTime,
+);
+
+#[get("/?<entity>&<entity_id>&<action>&<user_id>&<from>&<to>")]
+#[allow(clippy::too_many_arguments)]
+async fn list(
+    db: DbConnection,
+    member: Member,
+    entity: Option<AuditEntity>,
+    entity_id: Option<i32>,
+    action: Option<AuditAction>,
+    user_id: Option<i32>,
+    from: Option<Date>,
+    to: Option<Date>,
+) -> Json<Vec<AuditEntry>> {
+    let filter = AuditFilter {
+        entity,
+        entity_id,
+        action,
+        user_id,
+        from,
+        to,
+    };
+    db.run(move |conn| load_entries(conn, member.budget_id, filter))
+        .await
+        .map(Json)
+        .unwrap()
+}
+
+#[get("/account/<id>/history")]
+async fn account_history(db: DbConnection, member: Member, id: i32) -> Json<Vec<AuditEntry>> {
+    history(db, member, AuditEntity::Account, id).await
+}
+
+#[get("/bucket/<id>/history")]
+async fn bucket_history(db: DbConnection, member: Member, id: i32) -> Json<Vec<AuditEntry>> {
+    history(db, member, AuditEntity::Bucket, id).await
+}
+
+#[get("/transaction/<id>/history")]
+async fn transaction_history(db: DbConnection, member: Member, id: i32) -> Json<Vec<AuditEntry>> {
+    history(db, member, AuditEntity::Transaction, id).await
+}
+
+#[get("/fill/<id>/history")]
+async fn fill_history(db: DbConnection, member: Member, id: i32) -> Json<Vec<AuditEntry>> {
+    history(db, member, AuditEntity::Fill, id).await
+}
+
+// Changes of a single row, still available once it is deleted
+async fn history(
+    db: DbConnection,
+    member: Member,
+    entity: AuditEntity,
+    entity_id: i32,
+) -> Json<Vec<AuditEntry>> {
+    let filter = AuditFilter {
+        entity: Some(entity),
+        entity_id: Some(entity_id),
+        ..Default::default()
+    };
+    db.run(move |conn| load_entries(conn, member.budget_id, filter))
+        .await
+        .map(Json)
+        .unwrap()
+}
+
+// Records a change, without `before` for a creation and without `after` for a deletion
+pub(crate) fn record<T: Audited>(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    user_id: i32,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> QueryResult<()> {
+    let (entity_id, action) = match (before, after) {
+        (None, Some(after)) => (after.id(), AuditAction::Create),
+        (Some(before), Some(_)) => (before.id(), AuditAction::Update),
+        (Some(before), None) => (before.id(), AuditAction::Delete),
+        (None, None) => return Ok(()),
+    };
+    diesel::insert_into(schema::audit_log::table)
+        .values(&NewAuditEntry {
+            budget_id,
+            user_id,
+            entity: T::ENTITY,
+            entity_id,
+            action,
+            before: before.map(to_json),
+            after: after.map(to_json),
+            created_at: Local::now().naive_local(),
+        })
+        .execute(conn)
+        .map(|_| ())
+}
+
+// Budget and user of the latest change of a row, if it has any
+pub(crate) fn last_change<T: Audited>(
+    conn: &SqliteConnection,
+    row: &T,
+) -> QueryResult<Option<(i32, i32)>> {
+    schema::audit_log::table
+        .filter(schema::audit_log::entity.eq(T::ENTITY))
+        .filter(schema::audit_log::entity_id.eq(row.id()))
+        .order(schema::audit_log::id.desc())
+        .select((schema::audit_log::budget_id, schema::audit_log::user_id))
+        .first::<(i32, i32)>(conn)
+        .optional()
+}
+
+fn to_json<T: Serialize>(row: &T) -> String {
+    json::to_string(row).expect("rows serialize to JSON")
+}
+
+fn load_entries(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    filter: AuditFilter,
+) -> QueryResult<Vec<AuditEntry>> {
+    let mut query = schema::audit_log::table
+        .inner_join(schema::users::table)
+        .filter(schema::audit_log::budget_id.eq(budget_id))
+        .order(schema::audit_log::id.asc())
+        .select((
+            schema::audit_log::id,
+            schema::audit_log::entity,
+            schema::audit_log::entity_id,
+            schema::audit_log::action,
+            schema::audit_log::before,
+            schema::audit_log::after,
+            schema::users::id,
+            schema::users::username,
+            schema::audit_log::created_at,
+        ))
+        .into_boxed();
+    if let Some(entity) = filter.entity {
+        query = query.filter(schema::audit_log::entity.eq(entity));
+    }
+    if let Some(entity_id) = filter.entity_id {
+        query = query.filter(schema::audit_log::entity_id.eq(entity_id));
+    }
+    if let Some(action) = filter.action {
+        query = query.filter(schema::audit_log::action.eq(action));
+    }
+    if let Some(user_id) = filter.user_id {
+        query = query.filter(schema::audit_log::user_id.eq(user_id));
+    }
+    if let Some(from) = filter.from {
+        query = query.filter(schema::audit_log::created_at.ge(from.start()));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(schema::audit_log::created_at.lt(to.end()));
+    }
+    Ok(query
+        .load::<AuditRow>(conn)?
+        .into_iter()
+        .map(
+            |(id, entity, entity_id, action, before, after, user_id, username, created_at)| {
+                AuditEntry {
+                    id,
+                    entity,
+                    entity_id,
+                    action,
+                    before: before.and_then(|before| json::from_str(&before).ok()),
+                    after: after.and_then(|after| json::from_str(&after).ok()),
+                    user_id,
+                    username,
+                    created_at,
+                }
+            },
+        )
+        .collect())
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Audit log", |rocket| async {
+        rocket.mount("/audit", routes![list]).mount(
+            "/",
+            routes![
+                account_history,
+                bucket_history,
+                transaction_history,
+                fill_history
+            ],
+        )
+    })
+}
@@ -0,0 +1,264 @@
+use crate::models;
+use crate::schema;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Local;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::{Created, Custom, Unauthorized};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::{delete, get, post, routes};
+use sha2::{Digest, Sha256};
+
+use super::budget::{adopt_budgets, create_budget};
+use crate::DbConnection;
+use models::{BudgetForm, NewToken, NewUser, User};
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Token {
+    token: String,
+}
+
+// Signed in user with the admin role
+pub struct Admin(pub User);
+
+// Bearer token of the request, as stored in the database
+struct TokenHash(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TokenHash {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            Some(token) => Outcome::Success(TokenHash(hash_token(token))),
+            None => Outcome::Failure((Status::Unauthorized, "Missing bearer token.")),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for User {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let TokenHash(token_hash) = match request.guard::<TokenHash>().await {
+            Outcome::Success(token_hash) => token_hash,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(()) => return Outcome::Forward(()),
+        };
+        let db = match request.guard::<DbConnection>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Failure((Status::ServiceUnavailable, "Database unavailable.")),
+        };
+        let user = db
+            .run(move |conn| {
+                schema::users::table
+                    .inner_join(schema::tokens::table)
+                    .filter(schema::tokens::token_hash.eq(token_hash))
+                    .select((
+                        schema::users::id,
+                        schema::users::username,
+                        schema::users::admin,
+                    ))
+                    .first::<User>(conn)
+                    .optional()
+            })
+            .await;
+        match user {
+            Ok(Some(user)) => Outcome::Success(user),
+            _ => Outcome::Failure((Status::Unauthorized, "Invalid bearer token.")),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<User>().await {
+            Outcome::Success(user) if user.admin => Outcome::Success(Admin(user)),
+            Outcome::Success(_) => Outcome::Failure((Status::Forbidden, "Admin role required.")),
+            Outcome::Failure(failure) => Outcome::Failure(failure),
+            Outcome::Forward(()) => Outcome::Forward(()),
+        }
+    }
+}
+
+// The first user registers freely, the next ones are added by an administrator,
+// every user starts with a budget of their own unless the first one takes over
+// the budgets of an instance upgraded before users existed
+#[post("/register", data = "<credentials>")]
+async fn register(
+    db: DbConnection,
+    user: Option<User>,
+    credentials: Json<Credentials>,
+) -> Result<Created<Json<User>>, Custom<String>> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(credentials.password.as_bytes(), &salt)
+        .map_err(|e| Custom(Status::InternalServerError, e.to_string()))?
+        .to_string();
+    let username = credentials.into_inner().username;
+    db.run(move |conn| {
+        conn.transaction(|| {
+            // Counted within the transaction so that only one first user is an administrator
+            let user_count = schema::users::table.count().get_result::<i64>(conn)?;
+            match user {
+                _ if user_count == 0 => {}
+                None => {
+                    return Ok(Err(Custom(
+                        Status::Unauthorized,
+                        String::from("Only an administrator can register new users."),
+                    )))
+                }
+                Some(user) if !user.admin => {
+                    return Ok(Err(Custom(
+                        Status::Forbidden,
+                        String::from("Admin role required."),
+                    )))
+                }
+                Some(_) => {}
+            }
+            // The first user administrates the instance
+            let new_user = NewUser {
+                username,
+                password_hash,
+                admin: user_count == 0,
+            };
+            diesel::insert_into(schema::users::table)
+                .values(&new_user)
+                .execute(conn)?;
+            let user = schema::users::table
+                .order(schema::users::id.desc())
+                .select((
+                    schema::users::id,
+                    schema::users::username,
+                    schema::users::admin,
+                ))
+                .first::<User>(conn)?;
+            if !new_user.admin || adopt_budgets(conn, user.id)? == 0 {
+                create_budget(conn, user.id, &BudgetForm::new(user.username.clone()))?;
+            }
+            Ok(Ok(user))
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))?
+    .map(|user| Created::new("/").body(Json(user)))
+}
+
+#[post("/login", data = "<credentials>")]
+async fn login(
+    db: DbConnection,
+    credentials: Json<Credentials>,
+) -> Result<Json<Token>, Unauthorized<&'static str>> {
+    let username = credentials.username.clone();
+    let user = db
+        .run(move |conn| {
+            schema::users::table
+                .filter(schema::users::username.eq(username))
+                .select((schema::users::id, schema::users::password_hash))
+                .first::<(i32, String)>(conn)
+                .optional()
+        })
+        .await
+        .unwrap();
+    let user_id = match user {
+        Some((user_id, password_hash))
+            if verify_password(&credentials.password, &password_hash) =>
+        {
+            user_id
+        }
+        _ => return Err(Unauthorized(Some("Invalid username or password."))),
+    };
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let new_token = NewToken {
+        user_id,
+        token_hash: hash_token(&token),
+        created_at: Local::now().naive_local(),
+    };
+    db.run(move |conn| {
+        diesel::insert_into(schema::tokens::table)
+            .values(&new_token)
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+    Ok(Json(Token { token }))
+}
+
+#[get("/me")]
+async fn me(user: User) -> Json<User> {
+    Json(user)
+}
+
+// Revokes the token used for this request
+#[delete("/token")]
+async fn logout(db: DbConnection, _user: User, token_hash: TokenHash) {
+    db.run(move |conn| {
+        diesel::delete(schema::tokens::table)
+            .filter(schema::tokens::token_hash.eq(token_hash.0))
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+// Revokes every token of the user, signing out all devices
+#[delete("/tokens")]
+async fn logout_everywhere(db: DbConnection, user: User) {
+    db.run(move |conn| {
+        diesel::delete(schema::tokens::table)
+            .filter(schema::tokens::user_id.eq(user.id))
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// Tokens are only stored hashed, a database leak does not leak sessions
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Authentication", |rocket| async {
+        rocket.mount(
+            "/auth",
+            routes![register, login, me, logout, logout_everywhere],
+        )
+    })
+}
@@ -0,0 +1,72 @@
+use crate::schema;
+
+use diesel::{Connection, QueryDsl, RunQueryDsl};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{self, Json};
+use rocket::{get, post, routes};
+
+use super::auth::Admin;
+use crate::backup::{InstanceBackup, SCHEMA_VERSION};
+use crate::DbConnection;
+
+// Snapshot of the whole instance, password hashes included
+#[get("/backup")]
+async fn backup(db: DbConnection, _admin: Admin) -> Json<InstanceBackup> {
+    db.run(|conn| InstanceBackup::read(conn))
+        .await
+        .map(Json)
+        .unwrap()
+}
+
+// Loads a backup into an instance without any user, like registering the
+// first one; the size is limited by the `backup` limit, 256 MiB by default
+#[post("/restore", data = "<data>")]
+async fn restore(db: DbConnection, limits: &Limits, data: Data<'_>) -> Result<(), Custom<String>> {
+    let limit = limits.get("backup").unwrap_or_else(|| 256.mebibytes());
+    let body = data
+        .open(limit)
+        .into_string()
+        .await
+        .map_err(|e| Custom(Status::BadRequest, e.to_string()))?;
+    if !body.is_complete() {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            String::from("Backup exceeds the size limit."),
+        ));
+    }
+    let backup = json::from_str::<InstanceBackup>(&body)
+        .map_err(|e| Custom(Status::UnprocessableEntity, e.to_string()))?;
+    if backup.schema_version != SCHEMA_VERSION {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            format!(
+                "Backup of schema version {} cannot be restored into schema version {}.",
+                backup.schema_version, SCHEMA_VERSION
+            ),
+        ));
+    }
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let user_count = schema::users::table.count().get_result::<i64>(conn)?;
+            if user_count > 0 {
+                return Ok(Err(Custom(
+                    Status::Conflict,
+                    String::from("Only an empty instance can be restored."),
+                )));
+            }
+            backup.restore(conn)?;
+            Ok(Ok(()))
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::UnprocessableEntity, e.to_string()))?
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Backup", |rocket| async {
+        rocket.mount("/", routes![backup, restore])
+    })
+}
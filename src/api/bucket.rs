@@ -1,102 +1,214 @@
 use crate::models;
 use crate::schema;
 
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::result::DatabaseErrorKind;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
 use rocket::fairing::AdHoc;
-use rocket::response::status::{Conflict, Created, NotFound};
-use rocket::serde::json::Json;
-use rocket::{delete, get, post, put, routes};
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::json::{Json, Value};
+use rocket::{delete, get, patch, post, put, routes};
 
+use super::audit;
+use super::auth::Admin;
+use super::budget::{Editor, Member};
+use super::etag::{IfMatch, Tagged};
+use super::patch::merge;
 use crate::DbConnection;
 use models::{Bucket, BucketForm};
 
 #[get("/")]
-async fn list(db: DbConnection) -> Json<Vec<Bucket>> {
-    db.run(|conn| schema::buckets::table.load::<Bucket>(conn))
-        .await
-        .map(Json)
-        .unwrap()
+async fn list(db: DbConnection, member: Member) -> Json<Vec<Bucket>> {
+    db.run(move |conn| {
+        schema::buckets::table
+            .filter(schema::buckets::budget_id.eq(member.budget_id))
+            .load::<Bucket>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
 }
 
 #[get("/<id>")]
-async fn read(db: DbConnection, id: i32) -> Result<Json<Bucket>, NotFound<String>> {
+async fn read(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Result<Tagged<Bucket>, NotFound<String>> {
     db.run(move |conn| {
         schema::buckets::table
             .filter(schema::buckets::id.eq(id))
+            .filter(schema::buckets::budget_id.eq(member.budget_id))
             .first::<Bucket>(conn)
     })
     .await
     .map_err(|e| NotFound(e.to_string()))
-    .map(Json)
+    .map(Tagged)
 }
 
 #[post("/", data = "<form>")]
 async fn create(
     db: DbConnection,
+    editor: Editor,
     form: Json<BucketForm>,
 ) -> Result<Created<Json<Bucket>>, Conflict<String>> {
     db.run(move |conn| {
-        diesel::insert_into(schema::buckets::table)
-            .values(&*form)
-            .execute(conn)
+        conn.transaction(|| {
+            diesel::insert_into(schema::buckets::table)
+                .values((&*form, schema::buckets::budget_id.eq(editor.budget_id)))
+                .execute(conn)?;
+            let bucket = get_last_bucket(conn, editor.budget_id)?;
+            audit::record(conn, editor.budget_id, editor.user_id, None, Some(&bucket))?;
+            Ok(bucket)
+        })
     })
     .await
-    .map_err(|e| Conflict(Some(e.to_string())))?;
-    Ok(Created::new("/").body(get_last_bucket(&db).await.map(Json).unwrap()))
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))
+    .map(|bucket| Created::new("/").body(Json(bucket)))
 }
 
 #[delete("/<id>")]
-async fn delete(db: DbConnection, id: i32) -> Result<(), Conflict<String>> {
+async fn delete(db: DbConnection, editor: Editor, id: i32) -> Result<(), Conflict<String>> {
     db.run(move |conn| {
-        diesel::delete(schema::buckets::table)
-            .filter(schema::buckets::id.eq(id))
-            .execute(conn)
+        conn.transaction(|| {
+            let query = schema::buckets::table
+                .filter(schema::buckets::id.eq(id))
+                .filter(schema::buckets::budget_id.eq(editor.budget_id));
+            let bucket = query.first::<Bucket>(conn).optional()?;
+            diesel::delete(query).execute(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                bucket.as_ref(),
+                None,
+            )
+        })
     })
     .await
-    .map_err(|e| Conflict(Some(e.to_string())))?;
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))?;
     Ok(())
 }
 
 #[put("/<id>", data = "<form>")]
-async fn update(db: DbConnection, form: Json<BucketForm>, id: i32) -> Json<Bucket> {
+async fn update(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    form: Json<BucketForm>,
+    id: i32,
+) -> Result<Tagged<Bucket>, Custom<String>> {
+    let form = form.into_inner();
+    update_bucket(db, editor, if_match, id, move |_| Ok(form)).await
+}
+
+// Changes only the fields present in the JSON object
+#[patch("/<id>", data = "<patch>")]
+async fn patch(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    patch: Json<Value>,
+    id: i32,
+) -> Result<Tagged<Bucket>, Custom<String>> {
+    update_bucket(db, editor, if_match, id, move |before| {
+        merge(before, &patch)
+    })
+    .await
+}
+
+// Updates a bucket with the form built from its current state
+async fn update_bucket<F>(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    id: i32,
+    form: F,
+) -> Result<Tagged<Bucket>, Custom<String>>
+where
+    F: FnOnce(&Bucket) -> Result<BucketForm, Custom<String>> + Send + 'static,
+{
     db.run(move |conn| {
-        diesel::update(schema::buckets::table)
-            .filter(schema::buckets::id.eq(id))
-            .set(&*form)
-            .execute(conn)
-            .unwrap();
-        schema::buckets::table
-            .filter(schema::buckets::id.eq(id))
-            .first::<Bucket>(conn)
+        conn.transaction(|| {
+            let query = schema::buckets::table
+                .filter(schema::buckets::id.eq(id))
+                .filter(schema::buckets::budget_id.eq(editor.budget_id));
+            let before = match query.first::<Bucket>(conn).optional()? {
+                Some(before) => before,
+                None => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Bucket not found."),
+                    )))
+                }
+            };
+            let form = match if_match.check(&before).and_then(|_| form(&before)) {
+                Ok(form) => form,
+                Err(e) => return Ok(Err(e)),
+            };
+            diesel::update(query)
+                .set((
+                    &form,
+                    schema::buckets::version.eq(schema::buckets::version + 1),
+                ))
+                .execute(conn)?;
+            let after = query.first::<Bucket>(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                Some(&before),
+                Some(&after),
+            )?;
+            Ok(Ok(after))
+        })
     })
     .await
-    .map(Json)
-    .unwrap()
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Custom(Status::Conflict, String::from("Bucket already exists."))
+        }
+        _ => Custom(Status::InternalServerError, e.to_string()),
+    })?
+    .map(Tagged)
 }
 
+// Deletes every bucket of the budget, restricted to administrators
 #[delete("/")]
-async fn destroy(db: DbConnection) {
-    db.run(|conn| diesel::delete(schema::buckets::table).execute(conn))
-        .await
-        .unwrap();
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let query =
+                schema::buckets::table.filter(schema::buckets::budget_id.eq(editor.budget_id));
+            let buckets = query.load::<Bucket>(conn)?;
+            diesel::delete(query).execute(conn)?;
+            for bucket in &buckets {
+                audit::record(conn, editor.budget_id, editor.user_id, Some(bucket), None)?;
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 }
 
 // While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
 // Currently replacing this function manually
-async fn get_last_bucket(db: &DbConnection) -> Result<Bucket, diesel::result::Error> {
-    db.run(|conn| {
-        schema::buckets::table
-            .order(schema::buckets::id.desc())
-            .first::<Bucket>(conn)
-    })
-    .await
+fn get_last_bucket(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Bucket> {
+    schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .order(schema::buckets::id.desc())
+        .first::<Bucket>(conn)
 }
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Bucket CRUD", |rocket| async {
         rocket.mount(
             "/bucket",
-            routes![read, create, list, delete, update, destroy],
+            routes![read, create, list, delete, update, patch, destroy],
         )
     })
 }
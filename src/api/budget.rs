@@ -0,0 +1,576 @@
+use crate::models;
+use crate::schema;
+
+use diesel::dsl::{Eq, Filter, Select};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::{Created, Custom};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::{delete, get, post, put, routes};
+
+use super::audit;
+use super::auth::Admin;
+use crate::backup::BudgetBackup;
+use crate::DbConnection;
+use models::{Budget, BudgetForm, BudgetMember, Role, User};
+
+// Header selecting the budget of a request, the first budget of the user is used without it
+pub const BUDGET_HEADER: &str = "X-Budget-Id";
+
+// Signed in user and their role in the budget of the request
+pub struct Member {
+    pub user_id: i32,
+    pub budget_id: i32,
+    pub role: Role,
+}
+
+// Member allowed to change the budget, viewers are refused
+pub struct Editor {
+    pub user_id: i32,
+    pub budget_id: i32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Membership {
+    budget_id: i32,
+    name: String,
+    role: Role,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct MemberRole {
+    user_id: i32,
+    username: String,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RoleForm {
+    role: Role,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ResetForm {
+    // Name of the budget, typed again to confirm the reset
+    confirm: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Reset {
+    budget_id: i32,
+    // Path of the backup written before the reset
+    backup: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Member {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match request.guard::<User>().await {
+            Outcome::Success(user) => user,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(()) => return Outcome::Forward(()),
+        };
+        let budget_id = match request
+            .headers()
+            .get_one(BUDGET_HEADER)
+            .map(str::parse::<i32>)
+        {
+            Some(Ok(budget_id)) => Some(budget_id),
+            Some(Err(_)) => return Outcome::Failure((Status::BadRequest, "Invalid budget id.")),
+            None => None,
+        };
+        let db = match request.guard::<DbConnection>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Failure((Status::ServiceUnavailable, "Database unavailable.")),
+        };
+        let membership = db
+            .run(move |conn| {
+                let mut query = schema::budget_members::table
+                    .filter(schema::budget_members::user_id.eq(user.id))
+                    .order(schema::budget_members::budget_id.asc())
+                    .select((
+                        schema::budget_members::budget_id,
+                        schema::budget_members::role,
+                    ))
+                    .into_boxed();
+                if let Some(budget_id) = budget_id {
+                    query = query.filter(schema::budget_members::budget_id.eq(budget_id));
+                }
+                query.first::<(i32, Role)>(conn).optional()
+            })
+            .await;
+        match membership {
+            Ok(Some((budget_id, role))) => Outcome::Success(Member {
+                user_id: user.id,
+                budget_id,
+                role,
+            }),
+            _ => Outcome::Failure((Status::Forbidden, "Not a member of this budget.")),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<Member>().await {
+            Outcome::Success(Member {
+                role: Role::Viewer, ..
+            }) => Outcome::Failure((Status::Forbidden, "Viewers cannot change the budget.")),
+            Outcome::Success(member) => Outcome::Success(Editor {
+                user_id: member.user_id,
+                budget_id: member.budget_id,
+            }),
+            Outcome::Failure(failure) => Outcome::Failure(failure),
+            Outcome::Forward(()) => Outcome::Forward(()),
+        }
+    }
+}
+
+#[get("/")]
+async fn list(db: DbConnection, user: User) -> Json<Vec<Membership>> {
+    db.run(move |conn| {
+        schema::budgets::table
+            .inner_join(schema::budget_members::table)
+            .filter(schema::budget_members::user_id.eq(user.id))
+            .order(schema::budgets::id.asc())
+            .select((
+                schema::budgets::id,
+                schema::budgets::name,
+                schema::budget_members::role,
+            ))
+            .load::<(i32, String, Role)>(conn)
+    })
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|(budget_id, name, role)| Membership {
+                budget_id,
+                name,
+                role,
+            })
+            .collect()
+    })
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<id>")]
+async fn read(db: DbConnection, user: User, id: i32) -> Result<Json<Budget>, Custom<&'static str>> {
+    db.run(move |conn| {
+        get_role(conn, id, user.id)?;
+        schema::budgets::table
+            .filter(schema::budgets::id.eq(id))
+            .first::<Budget>(conn)
+            .map_err(|_| not_found())
+    })
+    .await
+    .map(Json)
+}
+
+// The creator of a budget is its owner
+#[post("/", data = "<form>")]
+async fn create(db: DbConnection, user: User, form: Json<BudgetForm>) -> Created<Json<Budget>> {
+    db.run(move |conn| create_budget(conn, user.id, &form))
+        .await
+        .map(|budget| Created::new("/").body(Json(budget)))
+        .unwrap()
+}
+
+#[put("/<id>", data = "<form>")]
+async fn update(
+    db: DbConnection,
+    user: User,
+    form: Json<BudgetForm>,
+    id: i32,
+) -> Result<Json<Budget>, Custom<&'static str>> {
+    db.run(move |conn| {
+        check_owner(conn, id, user.id)?;
+        conn.transaction(|| {
+            let query = schema::budgets::table.filter(schema::budgets::id.eq(id));
+            let before = query.first::<Budget>(conn)?;
+            // Rates are quoted against the currency of the budget, and amounts of
+            // accounts in it are taken as is
+            let currency_changed = form
+                .currency
+                .as_ref()
+                .is_some_and(|currency| *currency != before.currency);
+            if currency_changed {
+                let foreign_accounts = schema::accounts::table
+                    .filter(schema::accounts::budget_id.eq(id))
+                    .filter(schema::accounts::currency.ne(&before.currency))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                let rates = schema::exchange_rates::table
+                    .filter(schema::exchange_rates::budget_id.eq(id))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if foreign_accounts > 0 || rates > 0 {
+                    return Ok(Err(Custom(
+                        Status::Conflict,
+                        "Cannot change the currency of a budget using other currencies.",
+                    )));
+                }
+            }
+            diesel::update(query).set(&*form).execute(conn)?;
+            query.first::<Budget>(conn).map(Ok)
+        })
+        .map_err(|_: diesel::result::Error| not_found())?
+    })
+    .await
+    .map(Json)
+}
+
+// Only empty budgets can be deleted
+#[delete("/<id>")]
+async fn delete(db: DbConnection, user: User, id: i32) -> Result<(), Custom<&'static str>> {
+    db.run(move |conn| {
+        check_owner(conn, id, user.id)?;
+        diesel::delete(schema::budgets::table)
+            .filter(schema::budgets::id.eq(id))
+            .execute(conn)
+            .map_err(|_| Custom(Status::Conflict, "Budget is not empty."))
+    })
+    .await?;
+    Ok(())
+}
+
+#[get("/<id>/members")]
+async fn list_members(
+    db: DbConnection,
+    user: User,
+    id: i32,
+) -> Result<Json<Vec<MemberRole>>, Custom<&'static str>> {
+    db.run(move |conn| {
+        get_role(conn, id, user.id)?;
+        schema::budget_members::table
+            .inner_join(schema::users::table)
+            .filter(schema::budget_members::budget_id.eq(id))
+            .order(schema::users::id.asc())
+            .select((
+                schema::users::id,
+                schema::users::username,
+                schema::budget_members::role,
+            ))
+            .load::<(i32, String, Role)>(conn)
+            .map_err(|_| not_found())
+    })
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|(user_id, username, role)| MemberRole {
+                user_id,
+                username,
+                role,
+            })
+            .collect()
+    })
+    .map(Json)
+}
+
+// Adds a user to the budget, or changes the role of a member
+#[put("/<id>/members/<username>", data = "<form>")]
+async fn set_member(
+    db: DbConnection,
+    user: User,
+    id: i32,
+    username: String,
+    form: Json<RoleForm>,
+) -> Result<(), Custom<&'static str>> {
+    db.run(move |conn| {
+        check_owner(conn, id, user.id)?;
+        let user_id = find_user(conn, &username)?;
+        if form.role != Role::Owner {
+            check_other_owner(conn, id, user_id)?;
+        }
+        diesel::replace_into(schema::budget_members::table)
+            .values(&BudgetMember {
+                budget_id: id,
+                user_id,
+                role: form.role,
+            })
+            .execute(conn)
+            .unwrap();
+        Ok(())
+    })
+    .await
+}
+
+// Owners remove members, any member can leave
+#[delete("/<id>/members/<username>")]
+async fn remove_member(
+    db: DbConnection,
+    user: User,
+    id: i32,
+    username: String,
+) -> Result<(), Custom<&'static str>> {
+    db.run(move |conn| {
+        let user_id = find_user(conn, &username)?;
+        if user_id == user.id {
+            get_role(conn, id, user.id)?;
+        } else {
+            check_owner(conn, id, user.id)?;
+        }
+        check_other_owner(conn, id, user_id)?;
+        diesel::delete(schema::budget_members::table)
+            .filter(schema::budget_members::budget_id.eq(id))
+            .filter(schema::budget_members::user_id.eq(user_id))
+            .execute(conn)
+            .unwrap();
+        Ok(())
+    })
+    .await
+}
+
+// Deletes every account, bucket, tag, transaction and fill of a budget,
+// once a backup of them is written
+#[post("/<id>/reset", data = "<form>")]
+async fn reset(
+    db: DbConnection,
+    admin: Admin,
+    id: i32,
+    form: Json<ResetForm>,
+) -> Result<Json<Reset>, Custom<String>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let backup = match BudgetBackup::read(conn, id) {
+                Ok(backup) => backup,
+                Err(diesel::result::Error::NotFound) => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Budget not found."),
+                    )))
+                }
+                Err(e) => return Err(e),
+            };
+            if form.confirm != backup.budget.name() {
+                return Ok(Err(Custom(
+                    Status::Conflict,
+                    String::from("Confirm with the name of the budget."),
+                )));
+            }
+            let path = match backup.write(id) {
+                Ok(path) => path,
+                Err(e) => return Ok(Err(Custom(Status::InternalServerError, e.to_string()))),
+            };
+            diesel::delete(schema::transactions::table)
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(id)))
+                .execute(conn)?;
+            diesel::delete(schema::recurring_transactions::table)
+                .filter(schema::recurring_transactions::account_id.eq_any(budget_accounts(id)))
+                .execute(conn)?;
+            diesel::delete(schema::fills::table)
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(id)))
+                .execute(conn)?;
+            diesel::delete(schema::tags::table)
+                .filter(schema::tags::budget_id.eq(id))
+                .execute(conn)?;
+            diesel::delete(schema::buckets::table)
+                .filter(schema::buckets::budget_id.eq(id))
+                .execute(conn)?;
+            diesel::delete(schema::accounts::table)
+                .filter(schema::accounts::budget_id.eq(id))
+                .execute(conn)?;
+            for transaction in &backup.transactions {
+                audit::record(conn, id, admin.0.id, Some(transaction), None)?;
+            }
+            for fill in &backup.fills {
+                audit::record(conn, id, admin.0.id, Some(fill), None)?;
+            }
+            for bucket in &backup.buckets {
+                audit::record(conn, id, admin.0.id, Some(bucket), None)?;
+            }
+            for account in &backup.accounts {
+                audit::record(conn, id, admin.0.id, Some(account), None)?;
+            }
+            Ok(Ok(Reset {
+                budget_id: id,
+                backup: path.display().to_string(),
+            }))
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::result::Error::DatabaseError(..) => Custom(Status::Conflict, e.to_string()),
+        _ => Custom(Status::InternalServerError, e.to_string()),
+    })?
+    .map(Json)
+}
+
+// Budgets without members, like the one an instance upgraded before users
+// existed migrates its data to, are taken over by the first user
+pub(crate) fn adopt_budgets(conn: &SqliteConnection, user_id: i32) -> QueryResult<usize> {
+    let budget_ids = schema::budgets::table
+        .filter(
+            schema::budgets::id
+                .ne_all(schema::budget_members::table.select(schema::budget_members::budget_id)),
+        )
+        .select(schema::budgets::id)
+        .load::<i32>(conn)?;
+    for &budget_id in &budget_ids {
+        diesel::insert_into(schema::budget_members::table)
+            .values(&BudgetMember {
+                budget_id,
+                user_id,
+                role: Role::Owner,
+            })
+            .execute(conn)?;
+    }
+    Ok(budget_ids.len())
+}
+
+pub(crate) fn create_budget(
+    conn: &SqliteConnection,
+    user_id: i32,
+    form: &BudgetForm,
+) -> QueryResult<Budget> {
+    conn.transaction(|| {
+        diesel::insert_into(schema::budgets::table)
+            .values(form)
+            .execute(conn)?;
+        let budget_id = schema::budgets::table
+            .order(schema::budgets::id.desc())
+            .select(schema::budgets::id)
+            .first::<i32>(conn)?;
+        diesel::insert_into(schema::budget_members::table)
+            .values(&BudgetMember {
+                budget_id,
+                user_id,
+                role: Role::Owner,
+            })
+            .execute(conn)?;
+        schema::budgets::table
+            .filter(schema::budgets::id.eq(budget_id))
+            .first::<Budget>(conn)
+    })
+}
+
+// Ids of the accounts of a budget, to scope queries with `eq_any`
+pub(crate) fn budget_accounts(
+    budget_id: i32,
+) -> Select<
+    Filter<schema::accounts::table, Eq<schema::accounts::budget_id, i32>>,
+    schema::accounts::id,
+> {
+    schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .select(schema::accounts::id)
+}
+
+// Ids of the buckets of a budget, to scope queries with `eq_any`
+pub(crate) fn budget_buckets(
+    budget_id: i32,
+) -> Select<Filter<schema::buckets::table, Eq<schema::buckets::budget_id, i32>>, schema::buckets::id>
+{
+    schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .select(schema::buckets::id)
+}
+
+// Fails with `NotFound` when the account belongs to another budget
+pub(crate) fn check_account(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    account_id: i32,
+) -> QueryResult<()> {
+    budget_accounts(budget_id)
+        .filter(schema::accounts::id.eq(account_id))
+        .first::<i32>(conn)
+        .map(|_| ())
+}
+
+// Fails with `NotFound` when the bucket belongs to another budget
+pub(crate) fn check_bucket(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    bucket_id: i32,
+) -> QueryResult<()> {
+    budget_buckets(budget_id)
+        .filter(schema::buckets::id.eq(bucket_id))
+        .first::<i32>(conn)
+        .map(|_| ())
+}
+
+fn get_role(conn: &SqliteConnection, id: i32, user_id: i32) -> Result<Role, Custom<&'static str>> {
+    schema::budget_members::table
+        .filter(schema::budget_members::budget_id.eq(id))
+        .filter(schema::budget_members::user_id.eq(user_id))
+        .select(schema::budget_members::role)
+        .first::<Role>(conn)
+        .map_err(|_| not_found())
+}
+
+fn check_owner(conn: &SqliteConnection, id: i32, user_id: i32) -> Result<(), Custom<&'static str>> {
+    match get_role(conn, id, user_id)? {
+        Role::Owner => Ok(()),
+        _ => Err(Custom(
+            Status::Forbidden,
+            "Only owners can manage the budget.",
+        )),
+    }
+}
+
+// A budget always keeps an owner
+fn check_other_owner(
+    conn: &SqliteConnection,
+    id: i32,
+    user_id: i32,
+) -> Result<(), Custom<&'static str>> {
+    let other_owners = schema::budget_members::table
+        .filter(schema::budget_members::budget_id.eq(id))
+        .filter(schema::budget_members::user_id.ne(user_id))
+        .filter(schema::budget_members::role.eq(Role::Owner))
+        .count()
+        .get_result::<i64>(conn)
+        .unwrap();
+    match other_owners {
+        0 => Err(Custom(Status::Conflict, "A budget needs an owner.")),
+        _ => Ok(()),
+    }
+}
+
+fn find_user(conn: &SqliteConnection, username: &str) -> Result<i32, Custom<&'static str>> {
+    schema::users::table
+        .filter(schema::users::username.eq(username))
+        .select(schema::users::id)
+        .first::<i32>(conn)
+        .map_err(|_| Custom(Status::NotFound, "User not found."))
+}
+
+// Budgets of other users are not disclosed
+fn not_found() -> Custom<&'static str> {
+    Custom(Status::NotFound, "Budget not found.")
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Budget management", |rocket| async {
+        rocket.mount(
+            "/budget",
+            routes![
+                list,
+                read,
+                create,
+                update,
+                delete,
+                list_members,
+                set_member,
+                remove_member,
+                reset
+            ],
+        )
+    })
+}
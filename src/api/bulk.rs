@@ -0,0 +1,164 @@
+use crate::models;
+
+use diesel::{Connection, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{Json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{delete, patch, post, routes};
+
+use super::budget::Editor;
+use super::patch::merge;
+use super::transaction::{
+    check_not_reconciled, check_status, check_transfer, find_transaction, insert_transaction,
+    replace_transaction, trash_transaction,
+};
+use crate::DbConnection;
+use models::{Transaction, TransactionForm};
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BulkUpdate {
+    ids: Vec<i32>,
+    // Fields set on every transaction, as for `PATCH /transaction/<id>`
+    patch: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BulkDelete {
+    ids: Vec<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Bulk {
+    // Nothing is saved unless every item succeeds
+    committed: bool,
+    results: Vec<BulkResult>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct BulkResult {
+    // Position of the item in the request
+    index: usize,
+    transaction: Option<Transaction>,
+    error: Option<String>,
+}
+
+// Failure of a single item, rolling back the whole batch
+struct ItemError(String);
+
+impl From<diesel::result::Error> for ItemError {
+    fn from(e: diesel::result::Error) -> Self {
+        ItemError(e.to_string())
+    }
+}
+
+#[post("/bulk", data = "<forms>")]
+async fn create(
+    db: DbConnection,
+    editor: Editor,
+    forms: Json<Vec<TransactionForm>>,
+) -> Custom<Json<Bulk>> {
+    db.run(move |conn| {
+        run(conn, forms.iter(), |form| {
+            check_status(form)
+                .and_then(|_| check_transfer(None, form))
+                .map_err(ItemError)?;
+            insert_transaction(conn, &editor, form)
+                .map_err(|_| ItemError(String::from("Account or bucket not found.")))
+        })
+    })
+    .await
+}
+
+#[patch("/bulk", data = "<update>")]
+async fn update(db: DbConnection, editor: Editor, update: Json<BulkUpdate>) -> Custom<Json<Bulk>> {
+    db.run(move |conn| {
+        run(conn, update.ids.iter(), |&id| {
+            let before = find_editable(conn, &editor, id)?;
+            let form = merge::<_, TransactionForm>(&before, &update.patch)
+                .map_err(|Custom(_, e)| ItemError(e))?;
+            check_status(&form)
+                .and_then(|_| check_transfer(Some(&before), &form))
+                .map_err(ItemError)?;
+            replace_transaction(conn, &editor, &before, &form)
+                .map_err(|_| ItemError(String::from("Account or bucket not found.")))
+        })
+    })
+    .await
+}
+
+// Moves the transactions to the trash
+#[delete("/bulk", data = "<delete>")]
+async fn delete(db: DbConnection, editor: Editor, delete: Json<BulkDelete>) -> Custom<Json<Bulk>> {
+    db.run(move |conn| {
+        run(conn, delete.ids.iter(), |&id| {
+            let transaction = find_editable(conn, &editor, id)?;
+            trash_transaction(conn, &editor, &transaction)?;
+            Ok(transaction)
+        })
+    })
+    .await
+}
+
+// Reconciled transactions are locked against edits
+fn find_editable(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    id: i32,
+) -> Result<Transaction, ItemError> {
+    let transaction = find_transaction(conn, editor.budget_id, id)
+        .map_err(|_| ItemError(String::from("Transaction not found.")))?;
+    check_not_reconciled(conn, &transaction)?.map_err(|e| ItemError(String::from(e)))?;
+    Ok(transaction)
+}
+
+// Applies every item in its own savepoint within one database transaction,
+// which is rolled back as a whole as soon as an item fails
+fn run<I, F>(conn: &SqliteConnection, items: I, mut apply: F) -> Custom<Json<Bulk>>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Result<Transaction, ItemError>,
+{
+    let mut results = Vec::new();
+    let committed = conn
+        .transaction(|| {
+            for (index, item) in items.into_iter().enumerate() {
+                let result = conn.transaction(|| apply(item));
+                results.push(match result {
+                    Ok(transaction) => BulkResult {
+                        index,
+                        transaction: Some(transaction),
+                        error: None,
+                    },
+                    Err(ItemError(e)) => BulkResult {
+                        index,
+                        transaction: None,
+                        error: Some(e),
+                    },
+                });
+            }
+            if results.iter().any(|result| result.error.is_some()) {
+                Err(diesel::result::Error::RollbackTransaction)
+            } else {
+                Ok(())
+            }
+        })
+        .is_ok();
+    let status = if committed {
+        Status::Ok
+    } else {
+        Status::Conflict
+    };
+    Custom(status, Json(Bulk { committed, results }))
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Transaction bulk operations", |rocket| async {
+        rocket.mount("/transaction", routes![create, update, delete])
+    })
+}
@@ -0,0 +1,87 @@
+use crate::models;
+use crate::schema;
+
+use diesel::expression::dsl::sum;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::response::status::NotFound;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{get, routes};
+
+use super::budget::Member;
+use crate::DbConnection;
+use models::AccountType;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Reserve {
+    account_id: i32,
+    name: String,
+    reserved: f32,
+}
+
+#[get("/reserve")]
+async fn list(db: DbConnection, member: Member) -> Json<Vec<Reserve>> {
+    db.run(move |conn| {
+        schema::accounts::table
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .filter(schema::accounts::account_type.eq(AccountType::CreditCard))
+            .select((schema::accounts::id, schema::accounts::name))
+            .load::<(i32, String)>(conn)?
+            .into_iter()
+            .map(|(account_id, name)| get_reserve(conn, account_id, name))
+            .collect::<QueryResult<Vec<Reserve>>>()
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<account_id>/reserve")]
+async fn read(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+) -> Result<Json<Reserve>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        let name = schema::accounts::table
+            .filter(schema::accounts::id.eq(account_id))
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .filter(schema::accounts::account_type.eq(AccountType::CreditCard))
+            .select(schema::accounts::name)
+            .first::<String>(conn)?;
+        get_reserve(conn, account_id, name)
+    })
+    .await
+    .map_err(|_| NotFound("Credit card not found."))
+    .map(Json)
+}
+
+// Spending from a bucket on the card sets the money aside for repayment,
+// payments received from other accounts release it
+fn get_reserve(conn: &SqliteConnection, account_id: i32, name: String) -> QueryResult<Reserve> {
+    let covered = schema::transactions::table
+        .filter(schema::transactions::account_id.eq(account_id))
+        .filter(
+            schema::transactions::bucket_id
+                .is_not_null()
+                .or(schema::transactions::transfer_account_id.is_not_null()),
+        )
+        .filter(schema::transactions::deleted_at.is_null())
+        .select(sum(schema::transactions::amount))
+        .first::<Option<f32>>(conn)?
+        .unwrap_or_default();
+    Ok(Reserve {
+        account_id,
+        name,
+        reserved: -covered,
+    })
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Credit card reserve", |rocket| async {
+        rocket.mount("/card", routes![list, read])
+    })
+}
@@ -0,0 +1,85 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rocket::form::{self, FromFormField, ValueField};
+
+// Calendar date read from a query string, formatted as `YYYY-MM-DD`
+pub struct Date(pub NaiveDate);
+
+impl Date {
+    // First instant of the day
+    pub fn start(&self) -> NaiveDateTime {
+        self.0.and_hms(0, 0, 0)
+    }
+
+    // First instant of the following day, so that ranges include the day itself
+    pub fn end(&self) -> NaiveDateTime {
+        self.0.succ().and_hms(0, 0, 0)
+    }
+}
+
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for Date {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        NaiveDate::parse_from_str(field.value, "%Y-%m-%d")
+            .map(Date)
+            .map_err(|_| form::Error::validation("expected a date formatted as YYYY-MM-DD").into())
+    }
+}
+
+// Calendar month read from a query string, formatted as `YYYY-MM`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Month(pub NaiveDate);
+
+impl Month {
+    // Month of a date
+    pub fn of(date: NaiveDate) -> Month {
+        Month(NaiveDate::from_ymd(date.year(), date.month(), 1))
+    }
+
+    // First instant of the month
+    pub fn start(&self) -> NaiveDateTime {
+        self.0.and_hms(0, 0, 0)
+    }
+
+    // First instant of the following month, so that ranges include the month itself
+    pub fn end(&self) -> NaiveDateTime {
+        self.next().start()
+    }
+
+    // Following month
+    pub fn next(&self) -> Month {
+        match self.0.month() {
+            12 => Month(NaiveDate::from_ymd(self.0.year() + 1, 1, 1)),
+            month => Month(NaiveDate::from_ymd(self.0.year(), month + 1, 1)),
+        }
+    }
+
+    // Preceding month
+    pub fn previous(&self) -> Month {
+        Month::of(self.0.pred())
+    }
+
+    // Every month from `self` to `to`, both included
+    pub fn until(self, to: Month) -> Vec<Month> {
+        let mut months = Vec::new();
+        let mut month = self;
+        while month <= to {
+            months.push(month);
+            month = month.next();
+        }
+        months
+    }
+
+    // Formatted as `YYYY-MM`, as SQLite's `strftime('%Y-%m', ..)` does
+    pub fn key(&self) -> String {
+        self.0.format("%Y-%m").to_string()
+    }
+}
+
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for Month {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        NaiveDate::parse_from_str(&format!("{}-01", field.value), "%Y-%m-%d")
+            .map(Month)
+            .map_err(|_| form::Error::validation("expected a month formatted as YYYY-MM").into())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::models;
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, status::Custom, Responder, Response};
+use rocket::serde::{json::Json, Serialize};
+
+use models::{Account, Bucket, Fill, Transaction};
+
+// Rows updated with optimistic concurrency, their `version` is incremented on
+// every update and exchanged as ETag
+pub(crate) trait Versioned {
+    fn version(&self) -> i32;
+}
+
+impl Versioned for Account {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl Versioned for Bucket {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl Versioned for Transaction {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+impl Versioned for Fill {
+    fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+// JSON body with the version of the row as ETag
+pub(crate) struct Tagged<T>(pub T);
+
+impl<'r, T: Serialize + Versioned> Responder<'r, 'static> for Tagged<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let etag = format!("\"{}\"", self.0.version());
+        Response::build_from(Json(self.0).respond_to(request)?)
+            .raw_header("ETag", etag)
+            .ok()
+    }
+}
+
+// Version the client last read, required to update a row, `None` for `*`
+// which matches any version
+pub(crate) struct IfMatch(pub Option<i32>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one("If-Match").map(str::trim) {
+            Some("*") => Outcome::Success(IfMatch(None)),
+            Some(etag) => match etag.trim_start_matches("W/").trim_matches('"').parse() {
+                Ok(version) => Outcome::Success(IfMatch(Some(version))),
+                Err(_) => Outcome::Failure((Status::BadRequest, "Invalid If-Match header.")),
+            },
+            None => Outcome::Failure((Status::PreconditionRequired, "Missing If-Match header.")),
+        }
+    }
+}
+
+impl IfMatch {
+    // Refuses the update when the row changed since the client read it
+    pub(crate) fn check<T: Versioned>(&self, row: &T) -> Result<(), Custom<String>> {
+        match self.0 {
+            Some(version) if version != row.version() => Err(Custom(
+                Status::PreconditionFailed,
+                String::from("Row was modified since it was read."),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
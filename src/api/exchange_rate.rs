@@ -0,0 +1,292 @@
+use crate::models;
+use crate::schema;
+
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::{Created, Custom, NotFound};
+use rocket::serde::{json::Json, Deserialize};
+use rocket::{delete, get, post, routes};
+
+use super::auth::Admin;
+use super::budget::{Editor, Member};
+use crate::DbConnection;
+use models::{Currency, ExchangeRate, ExchangeRateForm};
+
+// Amount of a transaction in the currency of its budget, at the latest rate of
+// the account currency on its date, else the first one after it, as is for
+// accounts in the currency of the budget; `check_rates` makes sure the other
+// currencies have a rate first
+pub(crate) fn base_amount() -> String {
+    let rates = "SELECT exchange_rate.rate FROM accounts AS account \
+        INNER JOIN budgets AS budget ON budget.id = account.budget_id \
+        INNER JOIN exchange_rates AS exchange_rate \
+            ON exchange_rate.budget_id = account.budget_id \
+            AND exchange_rate.currency = account.currency \
+        WHERE account.id = transactions.account_id AND account.currency <> budget.currency";
+    format!(
+        "transactions.amount * COALESCE(\
+            ({} AND exchange_rate.date <= transactions.date \
+                ORDER BY exchange_rate.date DESC LIMIT 1), \
+            ({} ORDER BY exchange_rate.date ASC LIMIT 1), \
+            1.0)",
+        rates, rates
+    )
+}
+
+// Exchange rates of a budget, to convert balances at a given date
+pub(crate) struct Rates {
+    base: Currency,
+    // Rates of every currency, oldest first
+    rates: HashMap<Currency, Vec<(NaiveDateTime, f32)>>,
+}
+
+impl Rates {
+    pub(crate) fn load(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Self> {
+        let base = schema::budgets::table
+            .filter(schema::budgets::id.eq(budget_id))
+            .select(schema::budgets::currency)
+            .first::<Currency>(conn)?;
+        let mut rates: HashMap<Currency, Vec<(NaiveDateTime, f32)>> = HashMap::new();
+        for (currency, date, rate) in schema::exchange_rates::table
+            .filter(schema::exchange_rates::budget_id.eq(budget_id))
+            .order(schema::exchange_rates::date.asc())
+            .select((
+                schema::exchange_rates::currency,
+                schema::exchange_rates::date,
+                schema::exchange_rates::rate,
+            ))
+            .load::<(Currency, NaiveDateTime, f32)>(conn)?
+        {
+            rates.entry(currency).or_default().push((date, rate));
+        }
+        Ok(Self { base, rates })
+    }
+
+    // Rate picked like `base_amount` does for a transaction of that date
+    pub(crate) fn rate(
+        &self,
+        currency: &Currency,
+        date: NaiveDateTime,
+    ) -> Result<f32, Custom<String>> {
+        if *currency == self.base {
+            return Ok(1.0);
+        }
+        match self.rates.get(currency) {
+            Some(rates) => {
+                let after = rates.partition_point(|(rate_date, _)| *rate_date <= date);
+                Ok(rates[after.saturating_sub(1)].1)
+            }
+            None => Err(Custom(
+                Status::Conflict,
+                format!("No exchange rate for {}.", currency),
+            )),
+        }
+    }
+
+    pub(crate) fn convert(
+        &self,
+        amount: f32,
+        currency: &Currency,
+        date: NaiveDateTime,
+    ) -> Result<f32, Custom<String>> {
+        self.rate(currency, date).map(|rate| amount * rate)
+    }
+}
+
+// Amounts of every account of the budget can be converted to its currency,
+// checked before summing them with `base_amount`
+pub(crate) fn check_rates(
+    conn: &SqliteConnection,
+    budget_id: i32,
+) -> QueryResult<Result<(), Custom<String>>> {
+    let rates = Rates::load(conn, budget_id)?;
+    let currencies = schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .select(schema::accounts::currency)
+        .distinct()
+        .load::<Currency>(conn)?;
+    let now = Local::now().naive_local();
+    Ok(currencies
+        .iter()
+        .try_for_each(|currency| rates.rate(currency, now).map(|_| ())))
+}
+
+// Line of a CSV file of rates
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RateRecord {
+    date: String,
+    currency: Currency,
+    rate: f32,
+}
+
+#[get("/?<currency>")]
+async fn list(
+    db: DbConnection,
+    member: Member,
+    currency: Option<String>,
+) -> Json<Vec<ExchangeRate>> {
+    db.run(move |conn| {
+        let mut query = schema::exchange_rates::table
+            .filter(schema::exchange_rates::budget_id.eq(member.budget_id))
+            .order((
+                schema::exchange_rates::currency.asc(),
+                schema::exchange_rates::date.asc(),
+            ))
+            .into_boxed();
+        if let Some(currency) = currency {
+            query = query.filter(schema::exchange_rates::currency.eq(currency));
+        }
+        query.load::<ExchangeRate>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+// Sets the rate of a currency from a date on, replacing the one of that date
+#[post("/", data = "<form>")]
+async fn create(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<ExchangeRateForm>,
+) -> Result<Created<Json<ExchangeRate>>, Custom<String>> {
+    let form = form.into_inner();
+    db.run(move |conn| conn.transaction(|| save_rates(conn, editor.budget_id, vec![form])))
+        .await
+        .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))?
+        .map(|mut rates| Created::new("/").body(Json(rates.remove(0))))
+}
+
+// Sets the rates of a CSV file with a `date,currency,rate` header and dates
+// like `2022-07-01`; the size is limited by the `exchange_rates` limit, 1 MiB
+// by default
+#[post("/import", data = "<data>")]
+async fn import(
+    db: DbConnection,
+    editor: Editor,
+    limits: &Limits,
+    data: Data<'_>,
+) -> Result<Json<Vec<ExchangeRate>>, Custom<String>> {
+    let limit = limits
+        .get("exchange_rates")
+        .unwrap_or_else(|| 1.mebibytes());
+    let file = data
+        .open(limit)
+        .into_string()
+        .await
+        .map_err(|e| Custom(Status::BadRequest, e.to_string()))?;
+    if !file.is_complete() {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            String::from("File exceeds the size limit."),
+        ));
+    }
+    let forms = parse_rates(&file).map_err(|e| Custom(Status::UnprocessableEntity, e))?;
+    db.run(move |conn| conn.transaction(|| save_rates(conn, editor.budget_id, forms)))
+        .await
+        .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))?
+        .map(Json)
+}
+
+#[delete("/<id>")]
+async fn delete(db: DbConnection, editor: Editor, id: i32) -> Result<(), NotFound<&'static str>> {
+    let deleted = db
+        .run(move |conn| {
+            diesel::delete(schema::exchange_rates::table)
+                .filter(schema::exchange_rates::id.eq(id))
+                .filter(schema::exchange_rates::budget_id.eq(editor.budget_id))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+    match deleted {
+        0 => Err(NotFound("Exchange rate not found.")),
+        _ => Ok(()),
+    }
+}
+
+// Deletes every rate of the budget, restricted to administrators
+#[delete("/")]
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        diesel::delete(schema::exchange_rates::table)
+            .filter(schema::exchange_rates::budget_id.eq(editor.budget_id))
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+fn parse_rates(file: &str) -> Result<Vec<ExchangeRateForm>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file.as_bytes());
+    let mut forms = Vec::new();
+    for (index, record) in reader.deserialize::<RateRecord>().enumerate() {
+        // The header is the first line
+        let line = index + 2;
+        let record = record.map_err(|e| format!("Invalid rate on line {}: {}", line, e))?;
+        let date = NaiveDate::parse_from_str(&record.date, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date on line {}.", line))?;
+        forms.push(ExchangeRateForm {
+            currency: record.currency,
+            date: date.and_hms(0, 0, 0),
+            rate: record.rate,
+        });
+    }
+    Ok(forms)
+}
+
+// Replaces the rates of the same currency and date, amounts in the currency
+// of the budget need none
+fn save_rates(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    forms: Vec<ExchangeRateForm>,
+) -> QueryResult<Result<Vec<ExchangeRate>, Custom<String>>> {
+    let base = schema::budgets::table
+        .filter(schema::budgets::id.eq(budget_id))
+        .select(schema::budgets::currency)
+        .first::<Currency>(conn)?;
+    let mut rates = Vec::new();
+    for form in forms {
+        if form.currency == base {
+            return Ok(Err(Custom(
+                Status::UnprocessableEntity,
+                format!("{} is the currency of the budget.", base),
+            )));
+        }
+        if !(form.rate.is_finite() && form.rate > 0.0) {
+            return Ok(Err(Custom(
+                Status::UnprocessableEntity,
+                String::from("Rates must be positive."),
+            )));
+        }
+        diesel::replace_into(schema::exchange_rates::table)
+            .values((&form, schema::exchange_rates::budget_id.eq(budget_id)))
+            .execute(conn)?;
+        rates.push(
+            schema::exchange_rates::table
+                .filter(schema::exchange_rates::budget_id.eq(budget_id))
+                .filter(schema::exchange_rates::currency.eq(&form.currency))
+                .filter(schema::exchange_rates::date.eq(form.date))
+                .first::<ExchangeRate>(conn)?,
+        );
+    }
+    Ok(Ok(rates))
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Exchange rates", |rocket| async {
+        rocket.mount(
+            "/exchange-rate",
+            routes![list, create, import, delete, destroy],
+        )
+    })
+}
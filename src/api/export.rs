@@ -0,0 +1,279 @@
+use crate::models;
+use crate::schema;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::http::{ContentType, Status};
+use rocket::request::FromParam;
+use rocket::response::status::Custom;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{get, routes};
+
+use super::budget::{budget_accounts, budget_buckets, Member};
+use super::date::Date;
+use crate::DbConnection;
+use models::{
+    Account, Bucket, Budget, ExchangeRate, Fill, Loan, RecurringTransaction, Transaction,
+};
+
+// Version of the JSON export, bumped whenever its shape changes
+const EXPORT_VERSION: u32 = 4;
+
+// CSV headers, in the order the fields of each model are serialized
+const ACCOUNT_HEADERS: [&str; 7] = [
+    "id",
+    "name",
+    "account_type",
+    "on_budget",
+    "budget_id",
+    "version",
+    "currency",
+];
+const BUCKET_HEADERS: [&str; 4] = ["id", "name", "budget_id", "version"];
+const TRANSACTION_HEADERS: [&str; 14] = [
+    "id",
+    "name",
+    "amount",
+    "date",
+    "account_id",
+    "bucket_id",
+    "memo",
+    "status",
+    "transfer_account_id",
+    "deleted_at",
+    "version",
+    "import_id",
+    "opening",
+    "transfer_id",
+];
+const FILL_HEADERS: [&str; 6] = ["id", "amount", "date", "bucket_id", "deleted_at", "version"];
+const EXCHANGE_RATE_HEADERS: [&str; 5] = ["id", "currency", "date", "rate", "budget_id"];
+const LOAN_HEADERS: [&str; 5] = [
+    "account_id",
+    "principal",
+    "interest_rate",
+    "term",
+    "start_date",
+];
+const RECURRING_TRANSACTION_HEADERS: [&str; 7] = [
+    "id",
+    "name",
+    "amount",
+    "next_date",
+    "frequency",
+    "account_id",
+    "bucket_id",
+];
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Export {
+    version: u32,
+    exported_at: NaiveDateTime,
+    budget: Budget,
+    exchange_rates: Vec<ExchangeRate>,
+    accounts: Vec<Account>,
+    loans: Vec<Loan>,
+    buckets: Vec<Bucket>,
+    transactions: Vec<Transaction>,
+    fills: Vec<Fill>,
+    recurring_transactions: Vec<RecurringTransaction>,
+}
+
+// Entity exported as a CSV file, named like `accounts.csv`
+#[derive(Clone, Copy)]
+enum Entity {
+    Accounts,
+    Buckets,
+    Transactions,
+    Fills,
+    ExchangeRates,
+    Loans,
+    RecurringTransactions,
+}
+
+impl<'a> FromParam<'a> for Entity {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "accounts.csv" => Ok(Entity::Accounts),
+            "buckets.csv" => Ok(Entity::Buckets),
+            "transactions.csv" => Ok(Entity::Transactions),
+            "fills.csv" => Ok(Entity::Fills),
+            "exchange_rates.csv" => Ok(Entity::ExchangeRates),
+            "loans.csv" => Ok(Entity::Loans),
+            "recurring_transactions.csv" => Ok(Entity::RecurringTransactions),
+            _ => Err(param),
+        }
+    }
+}
+
+// Whole budget as one JSON document, transactions and fills are limited to
+// the days from `from` to `to` included
+#[get("/?<from>&<to>")]
+async fn export_json(
+    db: DbConnection,
+    member: Member,
+    from: Option<Date>,
+    to: Option<Date>,
+) -> Json<Export> {
+    db.run(move |conn| -> QueryResult<Export> {
+        Ok(Export {
+            version: EXPORT_VERSION,
+            exported_at: Local::now().naive_local(),
+            budget: schema::budgets::table
+                .filter(schema::budgets::id.eq(member.budget_id))
+                .first::<Budget>(conn)?,
+            exchange_rates: load_exchange_rates(conn, member.budget_id)?,
+            accounts: load_accounts(conn, member.budget_id)?,
+            loans: load_loans(conn, member.budget_id)?,
+            buckets: load_buckets(conn, member.budget_id)?,
+            transactions: load_transactions(conn, member.budget_id, &from, &to)?,
+            fills: load_fills(conn, member.budget_id, &from, &to)?,
+            recurring_transactions: load_recurring_transactions(conn, member.budget_id)?,
+        })
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<entity>?<from>&<to>")]
+async fn export_csv(
+    db: DbConnection,
+    member: Member,
+    entity: Entity,
+    from: Option<Date>,
+    to: Option<Date>,
+) -> Result<(ContentType, String), Custom<String>> {
+    db.run(move |conn| -> QueryResult<Result<String, String>> {
+        let budget_id = member.budget_id;
+        Ok(match entity {
+            Entity::Accounts => to_csv(&ACCOUNT_HEADERS, load_accounts(conn, budget_id)?),
+            Entity::Buckets => to_csv(&BUCKET_HEADERS, load_buckets(conn, budget_id)?),
+            Entity::Transactions => to_csv(
+                &TRANSACTION_HEADERS,
+                load_transactions(conn, budget_id, &from, &to)?,
+            ),
+            Entity::Fills => to_csv(&FILL_HEADERS, load_fills(conn, budget_id, &from, &to)?),
+            Entity::ExchangeRates => to_csv(
+                &EXCHANGE_RATE_HEADERS,
+                load_exchange_rates(conn, budget_id)?,
+            ),
+            Entity::Loans => to_csv(&LOAN_HEADERS, load_loans(conn, budget_id)?),
+            Entity::RecurringTransactions => to_csv(
+                &RECURRING_TRANSACTION_HEADERS,
+                load_recurring_transactions(conn, budget_id)?,
+            ),
+        })
+    })
+    .await
+    .unwrap()
+    .map(|body| (ContentType::CSV, body))
+    .map_err(|e| Custom(Status::InternalServerError, e))
+}
+
+fn load_accounts(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Vec<Account>> {
+    schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::accounts::id.asc())
+        .load::<Account>(conn)
+}
+
+fn load_loans(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Vec<Loan>> {
+    schema::loans::table
+        .filter(schema::loans::account_id.eq_any(budget_accounts(budget_id)))
+        .order(schema::loans::account_id.asc())
+        .load::<Loan>(conn)
+}
+
+fn load_buckets(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Vec<Bucket>> {
+    schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .order(schema::buckets::id.asc())
+        .load::<Bucket>(conn)
+}
+
+fn load_transactions(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: &Option<Date>,
+    to: &Option<Date>,
+) -> QueryResult<Vec<Transaction>> {
+    let mut query = schema::transactions::table
+        .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .filter(schema::transactions::deleted_at.is_null())
+        .order((
+            schema::transactions::date.asc(),
+            schema::transactions::id.asc(),
+        ))
+        .into_boxed();
+    if let Some(from) = from {
+        query = query.filter(schema::transactions::date.ge(from.start()));
+    }
+    if let Some(to) = to {
+        query = query.filter(schema::transactions::date.lt(to.end()));
+    }
+    query.load::<Transaction>(conn)
+}
+
+fn load_fills(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: &Option<Date>,
+    to: &Option<Date>,
+) -> QueryResult<Vec<Fill>> {
+    let mut query = schema::fills::table
+        .filter(schema::fills::bucket_id.eq_any(budget_buckets(budget_id)))
+        .filter(schema::fills::deleted_at.is_null())
+        .order((schema::fills::date.asc(), schema::fills::id.asc()))
+        .into_boxed();
+    if let Some(from) = from {
+        query = query.filter(schema::fills::date.ge(from.start()));
+    }
+    if let Some(to) = to {
+        query = query.filter(schema::fills::date.lt(to.end()));
+    }
+    query.load::<Fill>(conn)
+}
+
+fn load_exchange_rates(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Vec<ExchangeRate>> {
+    schema::exchange_rates::table
+        .filter(schema::exchange_rates::budget_id.eq(budget_id))
+        .order((
+            schema::exchange_rates::currency.asc(),
+            schema::exchange_rates::date.asc(),
+        ))
+        .load::<ExchangeRate>(conn)
+}
+
+fn load_recurring_transactions(
+    conn: &SqliteConnection,
+    budget_id: i32,
+) -> QueryResult<Vec<RecurringTransaction>> {
+    schema::recurring_transactions::table
+        .filter(schema::recurring_transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .order(schema::recurring_transactions::id.asc())
+        .load::<RecurringTransaction>(conn)
+}
+
+// One line per row after the header, written even without rows
+fn to_csv<T: Serialize>(headers: &[&str], rows: Vec<T>) -> Result<String, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(headers).map_err(|e| e.to_string())?;
+    for row in rows {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Export", |rocket| async {
+        rocket.mount("/export", routes![export_json, export_csv])
+    })
+}
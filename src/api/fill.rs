@@ -1,91 +1,212 @@
 use crate::models;
 use crate::schema;
 
-use chrono::NaiveDate;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::{Local, NaiveDate};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
 use rocket::fairing::AdHoc;
-use rocket::response::status::{Conflict, Created, NotFound};
-use rocket::serde::json::Json;
-use rocket::{delete, get, post, put, routes};
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::json::{Json, Value};
+use rocket::{delete, get, patch, post, put, routes};
 
+use super::audit;
+use super::auth::Admin;
+use super::budget::{budget_buckets, check_bucket, Editor, Member};
+use super::etag::{IfMatch, Tagged};
+use super::patch::merge;
 use crate::DbConnection;
 use models::{Fill, FillForm};
 
 #[get("/")]
-async fn list(db: DbConnection) -> Json<Vec<Fill>> {
-    db.run(|conn| schema::fills::table.load::<Fill>(conn))
-        .await
-        .map(Json)
-        .unwrap()
+async fn list(db: DbConnection, member: Member) -> Json<Vec<Fill>> {
+    db.run(move |conn| {
+        schema::fills::table
+            .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+            .filter(schema::fills::deleted_at.is_null())
+            .load::<Fill>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
 }
 
 #[get("/<id>")]
-async fn read(db: DbConnection, id: i32) -> Result<Json<Fill>, NotFound<&'static str>> {
+async fn read(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Result<Tagged<Fill>, NotFound<&'static str>> {
     db.run(move |conn| {
         schema::fills::table
             .filter(schema::fills::id.eq(id))
+            .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+            .filter(schema::fills::deleted_at.is_null())
             .first::<Fill>(conn)
     })
     .await
     .map_err(|_| NotFound("Fill not found."))
-    .map(Json)
+    .map(Tagged)
 }
 
 #[post("/", data = "<form>")]
 async fn create(
     db: DbConnection,
+    editor: Editor,
     form: Json<FillForm>,
 ) -> Result<Created<Json<Fill>>, Conflict<String>> {
     db.run(move |conn| {
-        diesel::insert_into(schema::fills::table)
-            .values(&*form)
-            .execute(conn)
+        conn.transaction(|| {
+            check_bucket(conn, editor.budget_id, form.bucket_id)?;
+            diesel::insert_into(schema::fills::table)
+                .values(&*form)
+                .execute(conn)?;
+            let fill = get_last_fill(conn, editor.budget_id)?;
+            audit::record(conn, editor.budget_id, editor.user_id, None, Some(&fill))?;
+            Ok(fill)
+        })
     })
     .await
-    .map_err(|e| Conflict(Some(e.to_string())))?;
-    Ok(Created::new("/").body(get_last_fill(&db).await.map(Json).unwrap()))
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))
+    .map(|fill| Created::new("/").body(Json(fill)))
 }
 
+// Moves the fill to the trash, from where it can be restored until purged
 #[delete("/<id>")]
-async fn delete(db: DbConnection, id: i32) {
+async fn delete(db: DbConnection, editor: Editor, id: i32) {
     db.run(move |conn| {
-        diesel::delete(schema::fills::table)
-            .filter(schema::fills::id.eq(id))
-            .execute(conn)
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let query = schema::fills::table
+                .filter(schema::fills::id.eq(id))
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(editor.budget_id)))
+                .filter(schema::fills::deleted_at.is_null());
+            let fill = query.first::<Fill>(conn).optional()?;
+            diesel::update(query)
+                .set((
+                    schema::fills::deleted_at.eq(Local::now().naive_local()),
+                    schema::fills::version.eq(schema::fills::version + 1),
+                ))
+                .execute(conn)?;
+            audit::record(conn, editor.budget_id, editor.user_id, fill.as_ref(), None)
+        })
     })
     .await
     .unwrap();
 }
 
 #[put("/<id>", data = "<form>")]
-async fn update(db: DbConnection, form: Json<FillForm>, id: i32) -> Json<Fill> {
+async fn update(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    form: Json<FillForm>,
+    id: i32,
+) -> Result<Tagged<Fill>, Custom<String>> {
+    let form = form.into_inner();
+    update_fill(db, editor, if_match, id, move |_| Ok(form)).await
+}
+
+// Changes only the fields present in the JSON object
+#[patch("/<id>", data = "<patch>")]
+async fn patch(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    patch: Json<Value>,
+    id: i32,
+) -> Result<Tagged<Fill>, Custom<String>> {
+    update_fill(db, editor, if_match, id, move |before| {
+        merge(before, &patch)
+    })
+    .await
+}
+
+// Updates a fill with the form built from its current state
+async fn update_fill<F>(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    id: i32,
+    form: F,
+) -> Result<Tagged<Fill>, Custom<String>>
+where
+    F: FnOnce(&Fill) -> Result<FillForm, Custom<String>> + Send + 'static,
+{
     db.run(move |conn| {
-        diesel::update(schema::fills::table)
-            .filter(schema::fills::id.eq(id))
-            .set(&*form)
-            .execute(conn)
-            .unwrap();
-        schema::fills::table
-            .filter(schema::fills::id.eq(id))
-            .first::<Fill>(conn)
+        conn.transaction(|| {
+            let query = schema::fills::table
+                .filter(schema::fills::id.eq(id))
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(editor.budget_id)))
+                .filter(schema::fills::deleted_at.is_null());
+            let before = match query.first::<Fill>(conn).optional()? {
+                Some(before) => before,
+                None => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Fill not found."),
+                    )))
+                }
+            };
+            let form = match if_match.check(&before).and_then(|_| form(&before)) {
+                Ok(form) => form,
+                Err(e) => return Ok(Err(e)),
+            };
+            if check_bucket(conn, editor.budget_id, form.bucket_id)
+                .optional()?
+                .is_none()
+            {
+                return Ok(Err(Custom(
+                    Status::Conflict,
+                    String::from("Bucket not found."),
+                )));
+            }
+            diesel::update(query)
+                .set((&form, schema::fills::version.eq(schema::fills::version + 1)))
+                .execute(conn)?;
+            let after = query.first::<Fill>(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                Some(&before),
+                Some(&after),
+            )?;
+            Ok(Ok(after))
+        })
     })
     .await
-    .map(Json)
-    .unwrap()
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Tagged)
 }
 
+// Deletes every fill of the budget, trash included, restricted to administrators
 #[delete("/")]
-async fn destroy(db: DbConnection) {
-    db.run(|conn| diesel::delete(schema::fills::table).execute(conn))
-        .await
-        .unwrap();
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let query = schema::fills::table
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(editor.budget_id)));
+            let fills = query.load::<Fill>(conn)?;
+            diesel::delete(query).execute(conn)?;
+            for fill in &fills {
+                audit::record(conn, editor.budget_id, editor.user_id, Some(fill), None)?;
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 }
 
 #[get("/bucket/<id>/fills")]
-async fn read_fills_for_bucket(db: DbConnection, id: i32) -> Json<Vec<Fill>> {
+async fn read_fills_for_bucket(db: DbConnection, member: Member, id: i32) -> Json<Vec<Fill>> {
     db.run(move |conn| {
         schema::fills::table
             .filter(schema::fills::bucket_id.eq(id))
+            .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+            .filter(schema::fills::deleted_at.is_null())
             .load::<Fill>(conn)
     })
     .await
@@ -96,6 +217,7 @@ async fn read_fills_for_bucket(db: DbConnection, id: i32) -> Json<Vec<Fill>> {
 #[get("/bucket/<id>/fills/<year>/<month>")]
 async fn read_fills_for_bucket_for_period(
     db: DbConnection,
+    member: Member,
     id: i32,
     year: i32,
     month: u8,
@@ -105,6 +227,8 @@ async fn read_fills_for_bucket_for_period(
     db.run(move |conn| {
         schema::fills::table
             .filter(schema::fills::bucket_id.eq(id))
+            .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+            .filter(schema::fills::deleted_at.is_null())
             .filter(schema::fills::date.ge(from_date))
             .filter(schema::fills::date.lt(to_date))
             .load::<Fill>(conn)
@@ -116,13 +240,11 @@ async fn read_fills_for_bucket_for_period(
 
 // While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
 // Currently replacing this function manually
-async fn get_last_fill(db: &DbConnection) -> Result<Fill, diesel::result::Error> {
-    db.run(|conn| {
-        schema::fills::table
-            .order(schema::fills::id.desc())
-            .first::<Fill>(conn)
-    })
-    .await
+fn get_last_fill(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Fill> {
+    schema::fills::table
+        .filter(schema::fills::bucket_id.eq_any(budget_buckets(budget_id)))
+        .order(schema::fills::id.desc())
+        .first::<Fill>(conn)
 }
 
 pub fn stage() -> AdHoc {
@@ -130,7 +252,7 @@ pub fn stage() -> AdHoc {
         rocket
             .mount(
                 "/fill",
-                routes![read, create, list, delete, update, destroy],
+                routes![read, create, list, delete, update, patch, destroy],
             )
             .mount(
                 "/",
@@ -0,0 +1,412 @@
+use crate::models;
+use crate::schema;
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Local, Months, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::query_dsl::GroupByDsl;
+use diesel::sql_types::{Float, Nullable};
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{get, routes};
+
+use super::budget::Member;
+use super::date::Month;
+use super::exchange_rate::{base_amount, check_rates, Rates};
+use super::reports::month_of;
+use crate::DbConnection;
+use models::{Currency, Frequency, RecurringTransaction};
+
+// Months forecast unless asked otherwise
+const DEFAULT_MONTHS: u32 = 6;
+const MAX_MONTHS: u32 = 60;
+// Full months before the current one whose average is projected forward
+const HISTORY_MONTHS: u32 = 3;
+
+type Totals = HashMap<i32, f32>;
+type MonthlyTotals = HashMap<(i32, String), f32>;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Forecast {
+    // Upcoming months, every series below has one value per month
+    months: Vec<String>,
+    accounts: Vec<AccountForecast>,
+    buckets: Vec<BucketForecast>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AccountForecast {
+    account_id: i32,
+    name: String,
+    // In the currency of the account, like every amount below
+    balance: f32,
+    // Average monthly change over the history, transfers excluded, used for
+    // the months without any scheduled transaction
+    average_change: f32,
+    // Balance at the end of each month
+    forecast: Vec<f32>,
+    // Months ending with a negative balance
+    negative_months: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct BucketForecast {
+    bucket_id: i32,
+    name: String,
+    // Money filled and not spent yet, in the currency of the budget
+    available: f32,
+    // Average monthly spending over the history, positive for expenses, used
+    // for the months without any scheduled spending
+    average_spent: f32,
+    // Money available at the end of each month
+    forecast: Vec<f32>,
+}
+
+// Balances and buckets over the next `months` months, from planned and recurring transactions
+#[get("/forecast?<months>")]
+async fn forecast(
+    db: DbConnection,
+    member: Member,
+    months: Option<u32>,
+) -> Result<Json<Forecast>, Custom<String>> {
+    let months = months.unwrap_or(DEFAULT_MONTHS);
+    if months == 0 || months > MAX_MONTHS {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            String::from("`months` must be between 1 and 60."),
+        ));
+    }
+    let now = Local::now().naive_local();
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        get_forecast(conn, member.budget_id, now, months)
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+fn get_forecast(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    now: NaiveDateTime,
+    months: u32,
+) -> QueryResult<Result<Forecast, Custom<String>>> {
+    let current = Month::of(now.date());
+    let last = (0..months).fold(current, |month, _| month.next());
+    let history = (0..HISTORY_MONTHS).fold(current, |month, _| month.previous());
+    // The rest of the current month only counts what is already planned
+    let months = current.until(last);
+    let accounts = forecast_accounts(conn, budget_id, now, &months, history)?;
+    let buckets = match forecast_buckets(conn, budget_id, now, &months, history)? {
+        Ok(buckets) => buckets,
+        Err(e) => return Ok(Err(e)),
+    };
+    Ok(Ok(Forecast {
+        months: months[1..].iter().map(Month::key).collect(),
+        accounts,
+        buckets,
+    }))
+}
+
+fn forecast_accounts(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    now: NaiveDateTime,
+    months: &[Month],
+    history: Month,
+) -> QueryResult<Vec<AccountForecast>> {
+    let (current, last) = (months[0], months[months.len() - 1]);
+    let transactions = schema::transactions::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::transactions::deleted_at.is_null());
+    let balances: Totals = transactions
+        .filter(schema::transactions::date.le(now))
+        .group_by(schema::accounts::id)
+        .select((
+            schema::accounts::id,
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, total)| (account_id, total.unwrap_or_default()))
+        .collect();
+    let averages: Totals = transactions
+        .filter(schema::transactions::transfer_account_id.is_null())
+        .filter(schema::transactions::opening.eq(false))
+        .filter(schema::transactions::date.ge(history.start()))
+        .filter(schema::transactions::date.lt(current.start()))
+        .group_by(schema::accounts::id)
+        .select((
+            schema::accounts::id,
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, total)| {
+            (
+                account_id,
+                total.unwrap_or_default() / HISTORY_MONTHS as f32,
+            )
+        })
+        .collect();
+    let upcoming = transactions
+        .filter(schema::transactions::date.gt(now))
+        .filter(schema::transactions::date.lt(last.end()));
+    let mut scheduled: MonthlyTotals = upcoming
+        .filter(schema::transactions::transfer_account_id.is_null())
+        .group_by((schema::accounts::id, month_of("transactions")))
+        .select((
+            schema::accounts::id,
+            month_of("transactions"),
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, month, total)| ((account_id, month), total.unwrap_or_default()))
+        .collect();
+    for recurring in schema::recurring_transactions::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .select(schema::recurring_transactions::all_columns)
+        .load::<RecurringTransaction>(conn)?
+    {
+        for date in occurrences(&recurring, now, last.end()) {
+            let key = (recurring.account_id, Month::of(date.date()).key());
+            *scheduled.entry(key).or_default() += recurring.amount;
+        }
+    }
+    let transfers: MonthlyTotals = upcoming
+        .filter(schema::transactions::transfer_account_id.is_not_null())
+        .group_by((schema::accounts::id, month_of("transactions")))
+        .select((
+            schema::accounts::id,
+            month_of("transactions"),
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, month, total)| ((account_id, month), total.unwrap_or_default()))
+        .collect();
+    Ok(schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::accounts::id.asc())
+        .select((schema::accounts::id, schema::accounts::name))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(account_id, name)| {
+            let balance = get(&balances, account_id);
+            let average_change = get(&averages, account_id);
+            let mut forecast = Vec::new();
+            let mut negative_months = Vec::new();
+            let mut running = balance
+                + get_monthly(&scheduled, account_id, current)
+                + get_monthly(&transfers, account_id, current);
+            for month in &months[1..] {
+                running += scheduled
+                    .get(&(account_id, month.key()))
+                    .copied()
+                    .unwrap_or(average_change)
+                    + get_monthly(&transfers, account_id, *month);
+                if running < 0.0 {
+                    negative_months.push(month.key());
+                }
+                forecast.push(running);
+            }
+            AccountForecast {
+                account_id,
+                name,
+                balance,
+                average_change,
+                forecast,
+                negative_months,
+            }
+        })
+        .collect())
+}
+
+fn forecast_buckets(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    now: NaiveDateTime,
+    months: &[Month],
+    history: Month,
+) -> QueryResult<Result<Vec<BucketForecast>, Custom<String>>> {
+    let (current, last) = (months[0], months[months.len() - 1]);
+    let fills = schema::fills::table
+        .inner_join(schema::buckets::table)
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .filter(schema::fills::deleted_at.is_null());
+    let spending = schema::transactions::table
+        .inner_join(schema::buckets::table)
+        .inner_join(schema::accounts::table)
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .filter(schema::transactions::deleted_at.is_null());
+    let filled: Totals = fills
+        .filter(schema::fills::date.le(now))
+        .group_by(schema::buckets::id)
+        .select((
+            schema::buckets::id,
+            sql::<Nullable<Float>>("SUM(fills.amount)"),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, total)| (bucket_id, total.unwrap_or_default()))
+        .collect();
+    let spent: Totals = spending
+        .filter(schema::transactions::date.le(now))
+        .group_by(schema::buckets::id)
+        .select((
+            schema::buckets::id,
+            sql::<Nullable<Float>>(&format!("SUM({})", base_amount())),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, total)| (bucket_id, total.unwrap_or_default()))
+        .collect();
+    let averages: Totals = spending
+        .filter(schema::transactions::date.ge(history.start()))
+        .filter(schema::transactions::date.lt(current.start()))
+        .group_by(schema::buckets::id)
+        .select((
+            schema::buckets::id,
+            sql::<Nullable<Float>>(&format!("SUM({})", base_amount())),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, total)| {
+            (
+                bucket_id,
+                -total.unwrap_or_default() / HISTORY_MONTHS as f32,
+            )
+        })
+        .collect();
+    let planned: MonthlyTotals = fills
+        .filter(schema::fills::date.gt(now))
+        .filter(schema::fills::date.lt(last.end()))
+        .group_by((schema::buckets::id, month_of("fills")))
+        .select((
+            schema::buckets::id,
+            month_of("fills"),
+            sql::<Nullable<Float>>("SUM(fills.amount)"),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, month, total)| ((bucket_id, month), total.unwrap_or_default()))
+        .collect();
+    let mut scheduled: MonthlyTotals = spending
+        .filter(schema::transactions::date.gt(now))
+        .filter(schema::transactions::date.lt(last.end()))
+        .group_by((schema::buckets::id, month_of("transactions")))
+        .select((
+            schema::buckets::id,
+            month_of("transactions"),
+            sql::<Nullable<Float>>(&format!("SUM({})", base_amount())),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, month, total)| ((bucket_id, month), total.unwrap_or_default()))
+        .collect();
+    let rates = Rates::load(conn, budget_id)?;
+    for (recurring, bucket_id, currency) in schema::recurring_transactions::table
+        .inner_join(schema::buckets::table)
+        .inner_join(schema::accounts::table)
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .select((
+            schema::recurring_transactions::all_columns,
+            schema::buckets::id,
+            schema::accounts::currency,
+        ))
+        .load::<(RecurringTransaction, i32, Currency)>(conn)?
+    {
+        for date in occurrences(&recurring, now, last.end()) {
+            let amount = match rates.convert(recurring.amount, &currency, date) {
+                Ok(amount) => amount,
+                Err(e) => return Ok(Err(e)),
+            };
+            *scheduled
+                .entry((bucket_id, Month::of(date.date()).key()))
+                .or_default() += amount;
+        }
+    }
+    Ok(Ok(schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .order(schema::buckets::id.asc())
+        .select((schema::buckets::id, schema::buckets::name))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, name)| {
+            let available = get(&filled, bucket_id) + get(&spent, bucket_id);
+            let average_spent = get(&averages, bucket_id);
+            let mut running = available
+                + get_monthly(&planned, bucket_id, current)
+                + get_monthly(&scheduled, bucket_id, current);
+            let forecast = months[1..]
+                .iter()
+                .map(|month| {
+                    running += get_monthly(&planned, bucket_id, *month)
+                        + scheduled
+                            .get(&(bucket_id, month.key()))
+                            .copied()
+                            .unwrap_or(-average_spent);
+                    running
+                })
+                .collect();
+            BucketForecast {
+                bucket_id,
+                name,
+                available,
+                average_spent,
+                forecast,
+            }
+        })
+        .collect()))
+}
+
+// Dates of a recurring transaction after `now` and before `end`, counted from
+// its next date so that the day of the month doesn't drift
+fn occurrences(
+    recurring: &RecurringTransaction,
+    now: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Vec<NaiveDateTime> {
+    let (date, time) = (recurring.next_date.date(), recurring.next_date.time());
+    (0..)
+        .map_while(|n: u32| {
+            match recurring.frequency {
+                Frequency::Weekly => Some(date + Duration::weeks(n.into())),
+                Frequency::Monthly => date.checked_add_months(Months::new(n)),
+                Frequency::Yearly => date.checked_add_months(Months::new(n * 12)),
+            }
+            .map(|date| date.and_time(time))
+        })
+        .take_while(|date| *date < end)
+        .filter(|date| *date > now)
+        .collect()
+}
+
+fn get(totals: &Totals, id: i32) -> f32 {
+    totals.get(&id).copied().unwrap_or_default()
+}
+
+fn get_monthly(totals: &MonthlyTotals, id: i32, month: Month) -> f32 {
+    totals.get(&(id, month.key())).copied().unwrap_or_default()
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Forecast", |rocket| async {
+        rocket.mount("/reports", routes![forecast])
+    })
+}
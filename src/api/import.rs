@@ -0,0 +1,108 @@
+use crate::models;
+use crate::schema;
+
+use std::collections::HashSet;
+
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{post, routes, FromFormField};
+
+use super::budget::{check_account, Editor};
+use super::transaction::import_transaction;
+use crate::import::{parse_camt053, parse_qif};
+use crate::DbConnection;
+use models::Transaction;
+
+#[derive(Clone, Copy, FromFormField)]
+enum Format {
+    Qif,
+    Camt053,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Import {
+    transactions: Vec<Transaction>,
+    // Transactions of the statement imported before, left out
+    duplicates: usize,
+}
+
+// Adds the transactions of a bank statement to an account, skipping those
+// whose bank reference was already imported; the size is limited by the
+// `import` limit, 10 MiB by default
+#[post("/<account_id>/import?<format>", data = "<data>")]
+async fn import(
+    db: DbConnection,
+    editor: Editor,
+    limits: &Limits,
+    account_id: i32,
+    format: Format,
+    data: Data<'_>,
+) -> Result<Json<Import>, Custom<String>> {
+    let limit = limits.get("import").unwrap_or_else(|| 10.mebibytes());
+    let statement = data
+        .open(limit)
+        .into_string()
+        .await
+        .map_err(|e| Custom(Status::BadRequest, e.to_string()))?;
+    if !statement.is_complete() {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            String::from("Statement exceeds the size limit."),
+        ));
+    }
+    let imported = match format {
+        Format::Qif => parse_qif(&statement, account_id),
+        Format::Camt053 => parse_camt053(&statement, account_id),
+    }
+    .map_err(|e| Custom(Status::UnprocessableEntity, e))?;
+    db.run(move |conn| {
+        conn.transaction(|| {
+            if check_account(conn, editor.budget_id, account_id).is_err() {
+                return Ok(Err(Custom(
+                    Status::NotFound,
+                    String::from("Account not found."),
+                )));
+            }
+            let mut imported_ids: HashSet<String> = schema::transactions::table
+                .filter(schema::transactions::account_id.eq(account_id))
+                .filter(schema::transactions::import_id.is_not_null())
+                .select(schema::transactions::import_id)
+                .load::<Option<String>>(conn)?
+                .into_iter()
+                .flatten()
+                .collect();
+            let mut transactions = Vec::new();
+            let mut duplicates = 0;
+            for transaction in imported {
+                if imported_ids.insert(transaction.import_id.clone()) {
+                    transactions.push(import_transaction(
+                        conn,
+                        &editor,
+                        &transaction.form,
+                        Some(&transaction.import_id),
+                    )?);
+                } else {
+                    duplicates += 1;
+                }
+            }
+            Ok(Ok(Import {
+                transactions,
+                duplicates,
+            }))
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))?
+    .map(Json)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Statement import", |rocket| async {
+        rocket.mount("/account", routes![import])
+    })
+}
@@ -0,0 +1,349 @@
+use crate::models;
+use crate::schema;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::http::ContentType;
+use rocket::{get, routes, FromFormField};
+
+use super::budget::{budget_accounts, budget_buckets, Member};
+use super::date::Date;
+use crate::DbConnection;
+use models::{AccountType, Currency, TransactionStatus};
+
+// Counterparts of the transactions without bucket nor transfer
+const INCOME_ACCOUNT: &str = "Income:Uncategorized";
+const EXPENSE_ACCOUNT: &str = "Expenses:Uncategorized";
+// Counterpart of the opening balances of accounts
+const OPENING_ACCOUNT: &str = "Equity:Opening-Balances";
+// Ledger account fills are taken from
+const AVAILABLE_ACCOUNT: &str = "Budget:Available";
+
+#[derive(Clone, Copy, PartialEq, Eq, FromFormField)]
+enum Format {
+    Ledger,
+    Beancount,
+}
+
+type TransactionRow = (
+    i32,
+    NaiveDateTime,
+    String,
+    f32,
+    i32,
+    Option<i32>,
+    Option<String>,
+    TransactionStatus,
+    Option<i32>,
+    bool,
+    Option<i32>,
+);
+
+// Accounts, buckets, transactions and fills as a ledger or beancount journal,
+// transactions and fills are limited to the days from `from` to `to` included;
+// amounts are in the currency of their account, fills in the one of the budget
+#[get("/journal?<format>&<from>&<to>")]
+async fn journal(
+    db: DbConnection,
+    member: Member,
+    format: Option<Format>,
+    from: Option<Date>,
+    to: Option<Date>,
+) -> (ContentType, String) {
+    let format = format.unwrap_or(Format::Ledger);
+    let journal = db
+        .run(move |conn| write_journal(conn, member.budget_id, format, &from, &to))
+        .await
+        .unwrap();
+    (ContentType::Plain, journal)
+}
+
+fn write_journal(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    format: Format,
+    from: &Option<Date>,
+    to: &Option<Date>,
+) -> QueryResult<String> {
+    let currency = schema::budgets::table
+        .filter(schema::budgets::id.eq(budget_id))
+        .select(schema::budgets::currency)
+        .first::<Currency>(conn)?;
+    let mut accounts: HashMap<i32, (String, Currency)> = schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::accounts::id.asc())
+        .select((
+            schema::accounts::id,
+            schema::accounts::name,
+            schema::accounts::account_type,
+            schema::accounts::currency,
+        ))
+        .load::<(i32, String, AccountType, Currency)>(conn)?
+        .into_iter()
+        .map(|(id, name, account_type, currency)| {
+            let root = match account_type {
+                AccountType::CreditCard | AccountType::Loan => "Liabilities",
+                _ => "Assets",
+            };
+            (id, (account_name(root, &name), currency))
+        })
+        .collect();
+    let mut buckets: HashMap<i32, String> = schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .select((schema::buckets::id, schema::buckets::name))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(id, name)| (id, account_name("Expenses", &name)))
+        .collect();
+    // Names written alike, or like the fixed accounts, get the id of their row
+    let mut counts: HashMap<String, usize> = [INCOME_ACCOUNT, EXPENSE_ACCOUNT, OPENING_ACCOUNT]
+        .into_iter()
+        .map(|name| (String::from(name), 1))
+        .collect();
+    for name in accounts
+        .values()
+        .map(|(name, _)| name)
+        .chain(buckets.values())
+    {
+        *counts.entry(name.clone()).or_default() += 1;
+    }
+    let unique = |id: &i32, name: &mut String| {
+        if counts[name.as_str()] > 1 {
+            write!(name, ":{}", id).unwrap();
+        }
+    };
+    accounts
+        .iter_mut()
+        .for_each(|(id, (name, _))| unique(id, name));
+    buckets.iter_mut().for_each(|(id, name)| unique(id, name));
+    let mut transactions = schema::transactions::table
+        .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .filter(schema::transactions::deleted_at.is_null())
+        .order((
+            schema::transactions::date.asc(),
+            schema::transactions::id.asc(),
+        ))
+        .select((
+            schema::transactions::id,
+            schema::transactions::date,
+            schema::transactions::name,
+            schema::transactions::amount,
+            schema::transactions::account_id,
+            schema::transactions::bucket_id,
+            schema::transactions::memo,
+            schema::transactions::status,
+            schema::transactions::transfer_account_id,
+            schema::transactions::opening,
+            schema::transactions::transfer_id,
+        ))
+        .into_boxed();
+    let mut fills = schema::fills::table
+        .filter(schema::fills::bucket_id.eq_any(budget_buckets(budget_id)))
+        .filter(schema::fills::deleted_at.is_null())
+        .order((schema::fills::date.asc(), schema::fills::id.asc()))
+        .select((
+            schema::fills::date,
+            schema::fills::amount,
+            schema::fills::bucket_id,
+        ))
+        .into_boxed();
+    if let Some(from) = from {
+        transactions = transactions.filter(schema::transactions::date.ge(from.start()));
+        fills = fills.filter(schema::fills::date.ge(from.start()));
+    }
+    if let Some(to) = to {
+        transactions = transactions.filter(schema::transactions::date.lt(to.end()));
+        fills = fills.filter(schema::fills::date.lt(to.end()));
+    }
+    let transactions = transactions.load::<TransactionRow>(conn)?;
+    let fills = fills.load::<(NaiveDateTime, f32, i32)>(conn)?;
+
+    let mut journal = String::new();
+    // Beancount needs every account opened before its first posting
+    let opened = transactions
+        .first()
+        .map(|transaction| transaction.1)
+        .into_iter()
+        .chain(fills.first().map(|fill| fill.0))
+        .min()
+        .unwrap_or_else(|| Local::now().naive_local());
+    let mut names: Vec<&str> = accounts
+        .values()
+        .map(|(name, _)| name)
+        .chain(buckets.values())
+        .map(String::as_str)
+        .collect();
+    names.sort_unstable();
+    names.extend([INCOME_ACCOUNT, EXPENSE_ACCOUNT, OPENING_ACCOUNT]);
+    for name in names {
+        match format {
+            Format::Ledger => writeln!(journal, "account {}", name),
+            Format::Beancount => writeln!(journal, "{} open {}", opened.format("%Y-%m-%d"), name),
+        }
+        .unwrap();
+    }
+
+    // Transfers are written once, from their outgoing leg along with the amount
+    // received, unless only the incoming leg is in the journal
+    let outgoing: HashSet<i32> = transactions
+        .iter()
+        .filter(|transaction| transaction.10 == Some(transaction.0))
+        .map(|transaction| transaction.0)
+        .collect();
+    let mut received: HashMap<i32, f32> = HashMap::new();
+    let mut incoming: HashSet<i32> = HashSet::new();
+    for &(id, _, _, amount, .., transfer_id) in &transactions {
+        match transfer_id {
+            Some(transfer_id) if transfer_id != id && outgoing.contains(&transfer_id) => {
+                received.insert(transfer_id, amount);
+                incoming.insert(id);
+            }
+            _ => {}
+        }
+    }
+
+    for transaction in transactions {
+        let (
+            id,
+            date,
+            name,
+            amount,
+            account_id,
+            bucket_id,
+            memo,
+            status,
+            transfer_account_id,
+            opening,
+            _,
+        ) = transaction;
+        let (account, account_currency) = &accounts[&account_id];
+        // Counterpart account, amount and currency
+        let counterpart = match (transfer_account_id, bucket_id) {
+            _ if opening => (OPENING_ACCOUNT, -amount, account_currency),
+            (Some(_), _) if incoming.contains(&id) => continue,
+            // The other leg may be out of the days of the journal
+            (Some(transfer_account_id), _) => {
+                let (other, other_currency) = &accounts[&transfer_account_id];
+                match received.get(&id) {
+                    Some(&to_amount) => (other.as_str(), to_amount, other_currency),
+                    None => (other.as_str(), -amount, account_currency),
+                }
+            }
+            (None, Some(bucket_id)) => (buckets[&bucket_id].as_str(), -amount, account_currency),
+            (None, None) if amount >= 0.0 => (INCOME_ACCOUNT, -amount, account_currency),
+            (None, None) => (EXPENSE_ACCOUNT, -amount, account_currency),
+        };
+        let date = date.format("%Y-%m-%d");
+        journal.push('\n');
+        match format {
+            Format::Ledger => {
+                let flag = match status {
+                    TransactionStatus::Uncleared => "",
+                    _ => "* ",
+                };
+                writeln!(journal, "{} {}{}", date, flag, single_line(&name)).unwrap();
+                if let Some(memo) = memo {
+                    writeln!(journal, "    ; {}", single_line(&memo)).unwrap();
+                }
+            }
+            Format::Beancount => {
+                let flag = match status {
+                    TransactionStatus::Uncleared => "!",
+                    _ => "*",
+                };
+                writeln!(journal, "{} {} {}", date, flag, quoted(&name)).unwrap();
+                if let Some(memo) = memo {
+                    writeln!(journal, "    memo: {}", quoted(&memo)).unwrap();
+                }
+            }
+        }
+        write_posting(&mut journal, account, amount, account_currency);
+        let (other, other_amount, other_currency) = counterpart;
+        if other_currency == account_currency {
+            write_posting(&mut journal, other, other_amount, other_currency);
+        } else {
+            // Transfers between currencies are priced at the amount sent
+            writeln!(
+                journal,
+                "    {}  {:.2} {} @@ {:.2} {}",
+                other, other_amount, other_currency, -amount, account_currency
+            )
+            .unwrap();
+        }
+    }
+
+    for (date, amount, bucket_id) in fills {
+        let date = date.format("%Y-%m-%d");
+        let bucket = &buckets[&bucket_id];
+        journal.push('\n');
+        match format {
+            // Virtual postings, balanced between themselves
+            Format::Ledger => {
+                writeln!(journal, "{} Fill", date).unwrap();
+                let budget = bucket.replacen("Expenses", "Budget", 1);
+                write_posting(&mut journal, &format!("[{}]", budget), amount, &currency);
+                write_posting(
+                    &mut journal,
+                    &format!("[{}]", AVAILABLE_ACCOUNT),
+                    -amount,
+                    &currency,
+                );
+            }
+            Format::Beancount => {
+                writeln!(
+                    journal,
+                    "{} custom \"fill\" {} {:.2} {}",
+                    date, bucket, amount, currency
+                )
+                .unwrap();
+            }
+        }
+    }
+    Ok(journal)
+}
+
+fn write_posting(journal: &mut String, account: &str, amount: f32, currency: &Currency) {
+    writeln!(journal, "    {}  {:.2} {}", account, amount, currency).unwrap();
+}
+
+// Account under `root`, named after `name` with capitalized words joined by dashes
+// as both formats accept
+fn account_name(root: &str, name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    if words.is_empty() {
+        format!("{}:Unnamed", root)
+    } else {
+        format!("{}:{}", root, words.join("-"))
+    }
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+fn quoted(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        single_line(text).replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Journal export", |rocket| async {
+        rocket.mount("/export", routes![journal])
+    })
+}
@@ -0,0 +1,423 @@
+use crate::models;
+use crate::schema;
+
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use diesel::expression::dsl::{max, sum};
+use diesel::result::DatabaseErrorKind;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::{Created, Custom, NotFound};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::{get, post, routes};
+
+use super::account::insert_account;
+use super::budget::{Editor, Member};
+use super::transaction::{insert_transaction, insert_transfer, TransferForm};
+use crate::DbConnection;
+use models::{AccountForm, Currency, Loan, LoanForm, Transaction, TransactionForm};
+
+// Longest term accepted, also bounding the projected schedules
+const MAX_TERM: i32 = 1200;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct NewLoan {
+    name: String,
+    // The currency of the budget when missing
+    #[serde(default)]
+    currency: Option<Currency>,
+    #[serde(flatten)]
+    terms: LoanForm,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct PaymentForm {
+    name: String,
+    amount: f32,
+    date: NaiveDateTime,
+    from_account_id: i32,
+    // Bucket the interest is spent from
+    bucket_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LoanStatus {
+    #[serde(flatten)]
+    loan: Loan,
+    name: String,
+    currency: Currency,
+    // Payment of every month, in the currency of the account
+    payment: f32,
+    // Principal left to repay
+    remaining: f32,
+    // Date of the last payment at the scheduled amount, of the latest payment
+    // once repaid, none when the payment no longer covers the interest
+    payoff_date: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Installment {
+    number: i32,
+    date: NaiveDateTime,
+    payment: f32,
+    interest: f32,
+    principal: f32,
+    // Principal left to repay after the payment
+    balance: f32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Payment {
+    interest: f32,
+    principal: f32,
+    transactions: Vec<Transaction>,
+}
+
+#[get("/")]
+async fn list(db: DbConnection, member: Member) -> Json<Vec<LoanStatus>> {
+    db.run(move |conn| {
+        find_loans(conn, member.budget_id, None)?
+            .into_iter()
+            .map(|row| get_status(conn, row))
+            .collect::<QueryResult<Vec<LoanStatus>>>()
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<account_id>")]
+async fn read(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+) -> Result<Json<LoanStatus>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        let row = find_loan(conn, member.budget_id, account_id)?;
+        get_status(conn, row)
+    })
+    .await
+    .map_err(|_| NotFound("Loan not found."))
+    .map(Json)
+}
+
+// Opens an off-budget loan account owing the principal from the start date,
+// so that the debt counts toward net worth until repaid
+#[post("/", data = "<form>")]
+async fn create(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<NewLoan>,
+) -> Result<Created<Json<LoanStatus>>, Custom<String>> {
+    let form = form.into_inner();
+    check_terms(&form.terms).map_err(|e| Custom(Status::UnprocessableEntity, String::from(e)))?;
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let terms = form.terms;
+            let mut account_form =
+                AccountForm::loan(form.name, form.currency, terms.principal, terms.start_date);
+            let account = insert_account(conn, &editor, &mut account_form)?;
+            diesel::insert_into(schema::loans::table)
+                .values((&terms, schema::loans::account_id.eq(account.id)))
+                .execute(conn)?;
+            let row = find_loan(conn, editor.budget_id, account.id)?;
+            get_status(conn, row)
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Custom(Status::Conflict, String::from("Account already exists."))
+        }
+        _ => Custom(Status::InternalServerError, e.to_string()),
+    })
+    .map(|loan| Created::new("/").body(Json(loan)))
+}
+
+// Payments of the original terms, from the month after the start date
+#[get("/<account_id>/schedule")]
+async fn schedule(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+) -> Result<Json<Vec<Installment>>, NotFound<&'static str>> {
+    db.run(move |conn| find_loan(conn, member.budget_id, account_id))
+        .await
+        .map_err(|_| NotFound("Loan not found."))
+        .map(|(loan, _, _)| Json(amortize(&loan, loan.principal, 1)))
+}
+
+// Pays a month of interest on the remaining balance as spending from the
+// paying account, and the rest as a transfer repaying the principal; extra
+// payments of principal alone are plain transfers
+#[post("/<account_id>/payment", data = "<form>")]
+async fn pay(
+    db: DbConnection,
+    editor: Editor,
+    account_id: i32,
+    form: Json<PaymentForm>,
+) -> Result<Created<Json<Payment>>, Custom<String>> {
+    let form = form.into_inner();
+    if !(form.amount.is_finite() && form.amount > 0.0) {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            String::from("Payments must be positive."),
+        ));
+    }
+    if form.from_account_id == account_id {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            String::from("Cannot pay a loan from itself."),
+        ));
+    }
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let (loan, _, currency) = match find_loan(conn, editor.budget_id, account_id) {
+                Ok(row) => row,
+                Err(diesel::result::Error::NotFound) => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Loan not found."),
+                    )))
+                }
+                Err(e) => return Err(e),
+            };
+            let from_currency = schema::accounts::table
+                .filter(schema::accounts::id.eq(form.from_account_id))
+                .filter(schema::accounts::budget_id.eq(editor.budget_id))
+                .select(schema::accounts::currency)
+                .first::<Currency>(conn)
+                .optional()?;
+            match from_currency {
+                None => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Account not found."),
+                    )))
+                }
+                Some(from_currency) if from_currency != currency => {
+                    return Ok(Err(Custom(
+                        Status::UnprocessableEntity,
+                        format!("Loans are paid from accounts in {}.", currency),
+                    )))
+                }
+                Some(_) => {}
+            }
+            let remaining = -f64::from(get_balance(conn, account_id, Some(form.date))?);
+            let amount = f64::from(form.amount);
+            let interest = cents(remaining.max(0.0) * monthly_rate(&loan)).min(amount);
+            let principal = cents(amount - interest);
+            if principal > cents(remaining) {
+                return Ok(Err(Custom(
+                    Status::UnprocessableEntity,
+                    String::from("Payment exceeds the remaining balance and interest."),
+                )));
+            }
+            let mut transactions = Vec::new();
+            if interest > 0.0 {
+                let form = TransactionForm::interest(
+                    form.name.clone(),
+                    -interest as f32,
+                    form.date,
+                    form.from_account_id,
+                    form.bucket_id,
+                );
+                transactions.push(insert_transaction(conn, &editor, &form)?);
+            }
+            if principal > 0.0 {
+                let transfer = TransferForm {
+                    name: form.name,
+                    amount: principal as f32,
+                    date: form.date,
+                    from_account_id: form.from_account_id,
+                    to_account_id: account_id,
+                    to_amount: Some(principal as f32),
+                };
+                match insert_transfer(conn, &editor, &transfer)? {
+                    Ok(legs) => transactions.extend(legs),
+                    Err(e) => return Ok(Err(e)),
+                }
+            }
+            Ok(Ok(Payment {
+                interest: interest as f32,
+                principal: principal as f32,
+                transactions,
+            }))
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))?
+    .map(|payment| Created::new("/").body(Json(payment)))
+}
+
+fn check_terms(terms: &LoanForm) -> Result<(), &'static str> {
+    if !(terms.principal.is_finite() && terms.principal > 0.0) {
+        Err("Principal must be positive.")
+    } else if !(terms.interest_rate.is_finite() && terms.interest_rate >= 0.0) {
+        Err("Interest rate cannot be negative.")
+    } else if !(1..=MAX_TERM).contains(&terms.term) {
+        Err("Term must be from 1 to 1200 months.")
+    } else {
+        Ok(())
+    }
+}
+
+// Loans of the budget with the name and currency of their account
+fn find_loans(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    account_id: Option<i32>,
+) -> QueryResult<Vec<(Loan, String, Currency)>> {
+    let mut query = schema::loans::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::loans::account_id.asc())
+        .select((
+            schema::loans::all_columns,
+            schema::accounts::name,
+            schema::accounts::currency,
+        ))
+        .into_boxed();
+    if let Some(account_id) = account_id {
+        query = query.filter(schema::loans::account_id.eq(account_id));
+    }
+    query.load::<(Loan, String, Currency)>(conn)
+}
+
+fn find_loan(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    account_id: i32,
+) -> QueryResult<(Loan, String, Currency)> {
+    find_loans(conn, budget_id, Some(account_id))?
+        .pop()
+        .ok_or(diesel::result::Error::NotFound)
+}
+
+// Remaining balance and payoff date, projected from the month after the
+// latest payment at the scheduled amount
+fn get_status(
+    conn: &SqliteConnection,
+    (loan, name, currency): (Loan, String, Currency),
+) -> QueryResult<LoanStatus> {
+    let remaining = -get_balance(conn, loan.account_id, None)?;
+    // Latest payment, or any other change to the account
+    let paid_until = schema::transactions::table
+        .filter(schema::transactions::account_id.eq(loan.account_id))
+        .filter(schema::transactions::opening.eq(false))
+        .filter(schema::transactions::deleted_at.is_null())
+        .select(max(schema::transactions::date))
+        .first::<Option<NaiveDateTime>>(conn)?
+        .unwrap_or(loan.start_date);
+    let payoff_date = if cents(f64::from(remaining)) <= 0.0 {
+        Some(paid_until)
+    } else {
+        let months = (paid_until.year() - loan.start_date.year()) * 12 + paid_until.month() as i32
+            - loan.start_date.month() as i32;
+        let mut next = months.max(1);
+        while due_date(&loan, next) <= paid_until {
+            next += 1;
+        }
+        amortize(&loan, remaining, next)
+            .last()
+            .filter(|installment| installment.balance <= 0.0)
+            .map(|installment| installment.date)
+    };
+    Ok(LoanStatus {
+        payment: scheduled_payment(&loan),
+        loan,
+        name,
+        currency,
+        remaining,
+        payoff_date,
+    })
+}
+
+// Balance of the account, counting the transactions up to `until` when given
+fn get_balance(
+    conn: &SqliteConnection,
+    account_id: i32,
+    until: Option<NaiveDateTime>,
+) -> QueryResult<f32> {
+    let mut query = schema::transactions::table
+        .filter(schema::transactions::account_id.eq(account_id))
+        .filter(schema::transactions::deleted_at.is_null())
+        .select(sum(schema::transactions::amount))
+        .into_boxed();
+    if let Some(until) = until {
+        query = query.filter(schema::transactions::date.le(until));
+    }
+    Ok(query.first::<Option<f32>>(conn)?.unwrap_or_default())
+}
+
+// Installments repaying `balance` at the scheduled payment, numbered from
+// `first`, interest and principal rounded to the cent; the last one pays what
+// is left, and a balance the payment no longer reduces ends the schedule
+fn amortize(loan: &Loan, balance: f32, first: i32) -> Vec<Installment> {
+    let rate = monthly_rate(loan);
+    let payment = f64::from(scheduled_payment(loan));
+    let mut balance = cents(f64::from(balance));
+    let mut installments = Vec::new();
+    for number in first..first + MAX_TERM {
+        let interest = cents(balance * rate);
+        let principal = cents(balance.min(payment - interest));
+        if balance <= 0.0 || principal <= 0.0 {
+            break;
+        }
+        balance = cents(balance - principal);
+        installments.push(Installment {
+            number,
+            date: due_date(loan, number),
+            payment: (interest + principal) as f32,
+            interest: interest as f32,
+            principal: principal as f32,
+            balance: balance as f32,
+        });
+    }
+    installments
+}
+
+// Payment of every month, rounded up to the cent so that the term is kept
+fn scheduled_payment(loan: &Loan) -> f32 {
+    let principal = f64::from(loan.principal);
+    let term = f64::from(loan.term);
+    let rate = monthly_rate(loan);
+    let payment = if rate == 0.0 {
+        principal / term
+    } else {
+        principal * rate / (1.0 - (1.0 + rate).powf(-term))
+    };
+    ((payment * 100.0 - 1e-6).ceil() / 100.0) as f32
+}
+
+fn monthly_rate(loan: &Loan) -> f64 {
+    f64::from(loan.interest_rate) / 100.0 / 12.0
+}
+
+// Date of the payment `number`, on the day of the start date or the last day
+// of shorter months
+fn due_date(loan: &Loan, number: i32) -> NaiveDateTime {
+    loan.start_date
+        .date()
+        .checked_add_months(Months::new(number as u32))
+        .unwrap_or(NaiveDate::MAX)
+        .and_time(loan.start_date.time())
+}
+
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Loans", |rocket| async {
+        rocket.mount("/loan", routes![list, create, read, schedule, pay])
+    })
+}
@@ -1,4 +1,25 @@
 pub mod account;
+pub mod audit;
+pub mod auth;
+pub mod backup;
 pub mod bucket;
+pub mod budget;
+pub mod bulk;
+pub mod card;
+mod date;
+mod etag;
+pub mod exchange_rate;
+pub mod export;
 pub mod fill;
+pub mod forecast;
+pub mod import;
+pub mod journal;
+pub mod loan;
+mod patch;
+pub mod reconciliation;
+pub mod recurring;
+pub mod reports;
+pub mod summary;
+pub mod tag;
 pub mod transaction;
+pub mod trash;
@@ -0,0 +1,34 @@
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{self, Value};
+use rocket::serde::{de::DeserializeOwned, Serialize};
+
+// Overwrites the fields of a row with those of a JSON object, `null` included,
+// and reads the result as a form so it is validated like a whole update
+pub(crate) fn merge<T, F>(row: &T, patch: &Value) -> Result<F, Custom<String>>
+where
+    T: Serialize,
+    F: Serialize + DeserializeOwned,
+{
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| unprocessable("Expected a JSON object."))?;
+    let mut merged = json::to_value(row).map_err(unprocessable)?;
+    if let Some(fields) = merged.as_object_mut() {
+        fields.extend(patch.clone());
+    }
+    let form = json::from_value::<F>(merged).map_err(unprocessable)?;
+    // Fields of the row which are not part of the form, like `id`, are read only
+    let writable = json::to_value(&form).map_err(unprocessable)?;
+    match patch
+        .keys()
+        .find(|key| writable.get(key.as_str()).is_none())
+    {
+        Some(key) => Err(unprocessable(format!("Field `{}` cannot be changed.", key))),
+        None => Ok(form),
+    }
+}
+
+fn unprocessable(e: impl ToString) -> Custom<String> {
+    Custom(Status::UnprocessableEntity, e.to_string())
+}
@@ -0,0 +1,159 @@
+use crate::models;
+use crate::schema;
+
+use chrono::NaiveDateTime;
+use diesel::expression::dsl::sum;
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::response::status::{Conflict, NotFound};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::{post, routes};
+
+use super::audit;
+use super::budget::{check_account, Editor, Member};
+use crate::DbConnection;
+use models::{Transaction, TransactionStatus};
+
+// Differences below half a cent are rounding noise
+const TOLERANCE: f32 = 0.005;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Statement {
+    date: NaiveDateTime,
+    ending_balance: f32,
+    // Create a transaction for the difference instead of refusing to reconcile
+    #[serde(default)]
+    adjust: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Reconciliation {
+    account_id: i32,
+    date: NaiveDateTime,
+    ending_balance: f32,
+    cleared_balance: f32,
+    difference: f32,
+}
+
+#[post("/<account_id>/reconcile", data = "<statement>")]
+async fn reconcile(
+    db: DbConnection,
+    member: Member,
+    account_id: i32,
+    statement: Json<Statement>,
+) -> Result<Json<Reconciliation>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        check_account(conn, member.budget_id, account_id)?;
+        get_reconciliation(conn, account_id, &statement)
+    })
+    .await
+    .map_err(|_| NotFound("Account not found."))
+    .map(Json)
+}
+
+#[post("/<account_id>/reconcile/confirm", data = "<statement>")]
+async fn confirm(
+    db: DbConnection,
+    editor: Editor,
+    account_id: i32,
+    statement: Json<Statement>,
+) -> Result<Json<Reconciliation>, Conflict<String>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            check_account(conn, editor.budget_id, account_id)?;
+            let reconciliation = get_reconciliation(conn, account_id, &statement)?;
+            if reconciliation.difference.abs() >= TOLERANCE {
+                if !statement.adjust {
+                    return Ok(Err(format!(
+                        "Cleared balance differs from the statement by {}.",
+                        reconciliation.difference
+                    )));
+                }
+                diesel::insert_into(schema::transactions::table)
+                    .values((
+                        schema::transactions::name.eq("Reconciliation adjustment"),
+                        schema::transactions::amount.eq(reconciliation.difference),
+                        schema::transactions::date.eq(statement.date),
+                        schema::transactions::account_id.eq(account_id),
+                        schema::transactions::status.eq(TransactionStatus::Cleared),
+                    ))
+                    .execute(conn)?;
+                let adjustment = schema::transactions::table
+                    .filter(schema::transactions::account_id.eq(account_id))
+                    .order(schema::transactions::id.desc())
+                    .first::<Transaction>(conn)?;
+                audit::record(
+                    conn,
+                    editor.budget_id,
+                    editor.user_id,
+                    None,
+                    Some(&adjustment),
+                )?;
+            }
+            let cleared = schema::transactions::table
+                .filter(schema::transactions::account_id.eq(account_id))
+                .filter(schema::transactions::status.eq(TransactionStatus::Cleared))
+                .filter(schema::transactions::deleted_at.is_null())
+                .filter(schema::transactions::date.le(statement.date));
+            let before = cleared
+                .order(schema::transactions::id)
+                .load::<Transaction>(conn)?;
+            let ids: Vec<i32> = before.iter().map(|transaction| transaction.id).collect();
+            diesel::update(cleared)
+                .set((
+                    schema::transactions::status.eq(TransactionStatus::Reconciled),
+                    schema::transactions::version.eq(schema::transactions::version + 1),
+                ))
+                .execute(conn)?;
+            let after = schema::transactions::table
+                .filter(schema::transactions::id.eq_any(ids))
+                .order(schema::transactions::id)
+                .load::<Transaction>(conn)?;
+            for (before, after) in before.iter().zip(&after) {
+                audit::record(
+                    conn,
+                    editor.budget_id,
+                    editor.user_id,
+                    Some(before),
+                    Some(after),
+                )?;
+            }
+            get_reconciliation(conn, account_id, &statement).map(Ok)
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))?
+    .map_err(|e| Conflict(Some(e)))
+    .map(Json)
+}
+
+// Cleared and reconciled transactions up to the statement date make the cleared balance
+fn get_reconciliation(
+    conn: &SqliteConnection,
+    account_id: i32,
+    statement: &Statement,
+) -> QueryResult<Reconciliation> {
+    let cleared_balance = schema::transactions::table
+        .filter(schema::transactions::account_id.eq(account_id))
+        .filter(schema::transactions::status.ne(TransactionStatus::Uncleared))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.le(statement.date))
+        .select(sum(schema::transactions::amount))
+        .first::<Option<f32>>(conn)?
+        .unwrap_or_default();
+    Ok(Reconciliation {
+        account_id,
+        date: statement.date,
+        ending_balance: statement.ending_balance,
+        cleared_balance,
+        difference: statement.ending_balance - cleared_balance,
+    })
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Account reconciliation", |rocket| async {
+        rocket.mount("/account", routes![reconcile, confirm])
+    })
+}
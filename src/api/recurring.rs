@@ -0,0 +1,190 @@
+use crate::models;
+use crate::schema;
+
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, put, routes};
+
+use super::auth::Admin;
+use super::budget::{budget_accounts, check_account, check_bucket, Editor, Member};
+use crate::DbConnection;
+use models::{RecurringForm, RecurringTransaction};
+
+#[get("/")]
+async fn list(db: DbConnection, member: Member) -> Json<Vec<RecurringTransaction>> {
+    db.run(move |conn| {
+        schema::recurring_transactions::table
+            .filter(
+                schema::recurring_transactions::account_id
+                    .eq_any(budget_accounts(member.budget_id)),
+            )
+            .order((
+                schema::recurring_transactions::next_date.asc(),
+                schema::recurring_transactions::id.asc(),
+            ))
+            .load::<RecurringTransaction>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<id>")]
+async fn read(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Result<Json<RecurringTransaction>, NotFound<&'static str>> {
+    db.run(move |conn| find_recurring(conn, member.budget_id, id))
+        .await
+        .map_err(|_| NotFound("Recurring transaction not found."))
+        .map(Json)
+}
+
+// Plans a transaction repeating from its next date on, only used by the forecast
+#[post("/", data = "<form>")]
+async fn create(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<RecurringForm>,
+) -> Result<Created<Json<RecurringTransaction>>, Conflict<&'static str>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            check_references(conn, editor.budget_id, &form)?;
+            diesel::insert_into(schema::recurring_transactions::table)
+                .values(&*form)
+                .execute(conn)?;
+            get_last_recurring(conn, editor.budget_id)
+        })
+    })
+    .await
+    .map_err(|_: diesel::result::Error| Conflict(Some("Account or bucket not found.")))
+    .map(|recurring| Created::new("/").body(Json(recurring)))
+}
+
+#[put("/<id>", data = "<form>")]
+async fn update(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<RecurringForm>,
+    id: i32,
+) -> Result<Json<RecurringTransaction>, Custom<String>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            if find_recurring(conn, editor.budget_id, id)
+                .optional()?
+                .is_none()
+            {
+                return Ok(Err(Custom(
+                    Status::NotFound,
+                    String::from("Recurring transaction not found."),
+                )));
+            }
+            if check_references(conn, editor.budget_id, &form)
+                .optional()?
+                .is_none()
+            {
+                return Ok(Err(Custom(
+                    Status::Conflict,
+                    String::from("Account or bucket not found."),
+                )));
+            }
+            diesel::update(
+                schema::recurring_transactions::table
+                    .filter(schema::recurring_transactions::id.eq(id)),
+            )
+            .set(&*form)
+            .execute(conn)?;
+            find_recurring(conn, editor.budget_id, id).map(Ok)
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+#[delete("/<id>")]
+async fn delete(db: DbConnection, editor: Editor, id: i32) -> Result<(), NotFound<&'static str>> {
+    let deleted = db
+        .run(move |conn| {
+            diesel::delete(schema::recurring_transactions::table)
+                .filter(schema::recurring_transactions::id.eq(id))
+                .filter(
+                    schema::recurring_transactions::account_id
+                        .eq_any(budget_accounts(editor.budget_id)),
+                )
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+    match deleted {
+        0 => Err(NotFound("Recurring transaction not found.")),
+        _ => Ok(()),
+    }
+}
+
+// Deletes every recurring transaction of the budget, restricted to administrators
+#[delete("/")]
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        diesel::delete(schema::recurring_transactions::table)
+            .filter(
+                schema::recurring_transactions::account_id
+                    .eq_any(budget_accounts(editor.budget_id)),
+            )
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+fn find_recurring(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    id: i32,
+) -> QueryResult<RecurringTransaction> {
+    schema::recurring_transactions::table
+        .filter(schema::recurring_transactions::id.eq(id))
+        .filter(schema::recurring_transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .first::<RecurringTransaction>(conn)
+}
+
+// The account and bucket must belong to the budget
+fn check_references(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    form: &RecurringForm,
+) -> QueryResult<()> {
+    check_account(conn, budget_id, form.account_id)?;
+    if let Some(bucket_id) = form.bucket_id {
+        check_bucket(conn, budget_id, bucket_id)?;
+    }
+    Ok(())
+}
+
+// While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
+// Currently replacing this function manually
+fn get_last_recurring(
+    conn: &SqliteConnection,
+    budget_id: i32,
+) -> QueryResult<RecurringTransaction> {
+    schema::recurring_transactions::table
+        .filter(schema::recurring_transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .order(schema::recurring_transactions::id.desc())
+        .first::<RecurringTransaction>(conn)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Recurring transactions", |rocket| async {
+        rocket.mount(
+            "/recurring",
+            routes![list, read, create, update, delete, destroy],
+        )
+    })
+}
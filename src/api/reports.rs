@@ -0,0 +1,577 @@
+use crate::models;
+use crate::schema;
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{Duration, NaiveDateTime};
+
+use diesel::dsl::sql;
+use diesel::expression::SqlLiteral;
+use diesel::query_dsl::GroupByDsl;
+use diesel::sql_types::{Float, Nullable, Text};
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{get, routes, FromFormField};
+
+use super::budget::Member;
+use super::date::{Date, Month};
+use super::exchange_rate::{base_amount, check_rates, Rates};
+use crate::DbConnection;
+use models::Currency;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct SpendingReport {
+    // Months of the range, every series below has one value per month
+    months: Vec<String>,
+    buckets: Vec<BucketSpending>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct BucketSpending {
+    bucket_id: i32,
+    name: String,
+    // Money spent from the bucket, positive for expenses, refunds lower it
+    spent: Vec<f32>,
+    // Money put in the bucket
+    filled: Vec<f32>,
+    average_spent: f32,
+    average_filled: f32,
+    // Filled minus spent over the range, negative when overspent
+    difference: f32,
+}
+
+#[derive(Clone, Copy, FromFormField, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Interval {
+    Week,
+    Month,
+    Year,
+}
+
+impl Interval {
+    // Formats a date as the key of its period, for chrono and SQLite's `strftime` alike
+    fn format(self) -> &'static str {
+        match self {
+            Interval::Week => "%Y-W%W",
+            Interval::Month => "%Y-%m",
+            Interval::Year => "%Y",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct CashFlowReport {
+    interval: Interval,
+    periods: Vec<CashFlow>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct CashFlow {
+    period: String,
+    income: f32,
+    // Money going out, positive
+    expenses: f32,
+    net: f32,
+    accounts: Vec<AccountCashFlow>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AccountCashFlow {
+    account_id: i32,
+    name: String,
+    income: f32,
+    expenses: f32,
+    net: f32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct NetWorthReport {
+    months: Vec<NetWorth>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct NetWorth {
+    month: String,
+    // Sum of the positive balances
+    assets: f32,
+    // Sum of the negative balances, positive
+    liabilities: f32,
+    net_worth: f32,
+    accounts: Vec<AccountBalance>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AccountBalance {
+    account_id: i32,
+    name: String,
+    // In the currency of the budget
+    balance: f32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AgeOfMoneyReport {
+    months: Vec<AgeOfMoney>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct AgeOfMoney {
+    month: String,
+    // Average days between receiving and spending the money spent in the month,
+    // weighted by amount, `None` when none of it can be traced to income
+    age: Option<f32>,
+    // Money going out, positive
+    spent: f32,
+}
+
+// Spending and fills per bucket per month, `from` and `to` months included
+#[get("/spending?<from>&<to>")]
+async fn spending(
+    db: DbConnection,
+    member: Member,
+    from: Month,
+    to: Month,
+) -> Result<Json<SpendingReport>, Custom<String>> {
+    check_range(from, to)?;
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        get_spending(conn, member.budget_id, from, to).map(Ok)
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+// Income and expenses of on-budget accounts per period in the currency of the
+// budget, transfers excluded, `from` and `to` days included
+#[get("/cash-flow?<from>&<to>&<interval>")]
+async fn cash_flow(
+    db: DbConnection,
+    member: Member,
+    from: Date,
+    to: Date,
+    interval: Option<Interval>,
+) -> Result<Json<CashFlowReport>, Custom<String>> {
+    check_range(from.0, to.0)?;
+    let interval = interval.unwrap_or(Interval::Month);
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        get_cash_flow(conn, member.budget_id, from, to, interval).map(Ok)
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+// Balances at the end of each month, `from` and `to` months included
+#[get("/net-worth?<from>&<to>")]
+async fn net_worth(
+    db: DbConnection,
+    member: Member,
+    from: Month,
+    to: Month,
+) -> Result<Json<NetWorthReport>, Custom<String>> {
+    check_range(from, to)?;
+    db.run(move |conn| get_net_worth(conn, member.budget_id, from, to))
+        .await
+        .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+        .map(Json)
+}
+
+// Age of the money spent from on-budget accounts per month, `from` and `to`
+// months included
+#[get("/age-of-money?<from>&<to>")]
+async fn age_of_money(
+    db: DbConnection,
+    member: Member,
+    from: Month,
+    to: Month,
+) -> Result<Json<AgeOfMoneyReport>, Custom<String>> {
+    check_range(from, to)?;
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        get_age_of_money(conn, member.budget_id, from, to).map(Ok)
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+fn check_range<T: PartialOrd>(from: T, to: T) -> Result<(), Custom<String>> {
+    if from > to {
+        return Err(Custom(
+            Status::UnprocessableEntity,
+            String::from("`from` must not be after `to`."),
+        ));
+    }
+    Ok(())
+}
+
+fn get_spending(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: Month,
+    to: Month,
+) -> QueryResult<SpendingReport> {
+    let months = from.until(to);
+    let spent: HashMap<(i32, String), f32> = schema::transactions::table
+        .inner_join(schema::buckets::table)
+        .inner_join(schema::accounts::table)
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.ge(from.start()))
+        .filter(schema::transactions::date.lt(to.end()))
+        .group_by((schema::buckets::id, month_of("transactions")))
+        .select((
+            schema::buckets::id,
+            month_of("transactions"),
+            sql::<Nullable<Float>>(&format!("SUM({})", base_amount())),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, month, total)| ((bucket_id, month), -total.unwrap_or_default()))
+        .collect();
+    let filled: HashMap<(i32, String), f32> = schema::fills::table
+        .inner_join(schema::buckets::table)
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .filter(schema::fills::deleted_at.is_null())
+        .filter(schema::fills::date.ge(from.start()))
+        .filter(schema::fills::date.lt(to.end()))
+        .group_by((schema::buckets::id, month_of("fills")))
+        .select((
+            schema::buckets::id,
+            month_of("fills"),
+            sql::<Nullable<Float>>("SUM(fills.amount)"),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, month, total)| ((bucket_id, month), total.unwrap_or_default()))
+        .collect();
+    let buckets = schema::buckets::table
+        .filter(schema::buckets::budget_id.eq(budget_id))
+        .order(schema::buckets::id.asc())
+        .select((schema::buckets::id, schema::buckets::name))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .map(|(bucket_id, name)| {
+            let series = |totals: &HashMap<(i32, String), f32>| -> Vec<f32> {
+                months
+                    .iter()
+                    .map(|month| {
+                        totals
+                            .get(&(bucket_id, month.key()))
+                            .copied()
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            };
+            let (spent, filled) = (series(&spent), series(&filled));
+            let total_spent: f32 = spent.iter().sum();
+            let total_filled: f32 = filled.iter().sum();
+            BucketSpending {
+                bucket_id,
+                name,
+                average_spent: total_spent / months.len() as f32,
+                average_filled: total_filled / months.len() as f32,
+                difference: total_filled - total_spent,
+                spent,
+                filled,
+            }
+        })
+        .collect();
+    Ok(SpendingReport {
+        months: months.iter().map(Month::key).collect(),
+        buckets,
+    })
+}
+
+fn get_cash_flow(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: Date,
+    to: Date,
+    interval: Interval,
+) -> QueryResult<CashFlowReport> {
+    let totals: HashMap<(i32, String), (f32, f32)> = schema::transactions::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .filter(schema::transactions::transfer_account_id.is_null())
+        // Opening balances were earned before the account was tracked
+        .filter(schema::transactions::opening.eq(false))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.ge(from.start()))
+        .filter(schema::transactions::date.lt(to.end()))
+        .group_by((
+            schema::accounts::id,
+            period_of("transactions", interval.format()),
+        ))
+        .select((
+            schema::accounts::id,
+            period_of("transactions", interval.format()),
+            sql::<Nullable<Float>>(&format!(
+                "SUM(CASE WHEN transactions.amount > 0 THEN {} ELSE 0 END)",
+                base_amount()
+            )),
+            sql::<Nullable<Float>>(&format!(
+                "SUM(CASE WHEN transactions.amount < 0 THEN -{} ELSE 0 END)",
+                base_amount()
+            )),
+        ))
+        .load::<(i32, String, Option<f32>, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, period, income, expenses)| {
+            (
+                (account_id, period),
+                (income.unwrap_or_default(), expenses.unwrap_or_default()),
+            )
+        })
+        .collect();
+    let accounts = schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .order(schema::accounts::id.asc())
+        .select((schema::accounts::id, schema::accounts::name))
+        .load::<(i32, String)>(conn)?;
+    // Every period of the range, even without transactions
+    let mut periods: Vec<String> = Vec::new();
+    for day in from.0.iter_days().take_while(|day| *day <= to.0) {
+        let period = day.format(interval.format()).to_string();
+        if periods.last() != Some(&period) {
+            periods.push(period);
+        }
+    }
+    let periods = periods
+        .into_iter()
+        .map(|period| {
+            let accounts: Vec<AccountCashFlow> = accounts
+                .iter()
+                .map(|(account_id, name)| {
+                    let (income, expenses) = totals
+                        .get(&(*account_id, period.clone()))
+                        .copied()
+                        .unwrap_or_default();
+                    AccountCashFlow {
+                        account_id: *account_id,
+                        name: name.clone(),
+                        income,
+                        expenses,
+                        net: income - expenses,
+                    }
+                })
+                .collect();
+            let income: f32 = accounts.iter().map(|account| account.income).sum();
+            let expenses: f32 = accounts.iter().map(|account| account.expenses).sum();
+            CashFlow {
+                period,
+                income,
+                expenses,
+                net: income - expenses,
+                accounts,
+            }
+        })
+        .collect();
+    Ok(CashFlowReport { interval, periods })
+}
+
+fn get_net_worth(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: Month,
+    to: Month,
+) -> QueryResult<Result<NetWorthReport, Custom<String>>> {
+    let accounts = schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .order(schema::accounts::id.asc())
+        .select((
+            schema::accounts::id,
+            schema::accounts::name,
+            schema::accounts::currency,
+        ))
+        .load::<(i32, String, Currency)>(conn)?;
+    let rates = Rates::load(conn, budget_id)?;
+    // Balances before the range, then the change of every month
+    let mut balances: HashMap<i32, f32> = schema::transactions::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.lt(from.start()))
+        .group_by(schema::accounts::id)
+        .select((
+            schema::accounts::id,
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, balance)| (account_id, balance.unwrap_or_default()))
+        .collect();
+    let changes: HashMap<(i32, String), f32> = schema::transactions::table
+        .inner_join(schema::accounts::table)
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.ge(from.start()))
+        .filter(schema::transactions::date.lt(to.end()))
+        .group_by((schema::accounts::id, month_of("transactions")))
+        .select((
+            schema::accounts::id,
+            month_of("transactions"),
+            sql::<Nullable<Float>>("SUM(transactions.amount)"),
+        ))
+        .load::<(i32, String, Option<f32>)>(conn)?
+        .into_iter()
+        .map(|(account_id, month, change)| ((account_id, month), change.unwrap_or_default()))
+        .collect();
+    let months = from
+        .until(to)
+        .into_iter()
+        .map(|month| {
+            // Balances are converted at the rates of the last day of the month
+            let last_day = month.end() - Duration::days(1);
+            let month = month.key();
+            let accounts = accounts
+                .iter()
+                .map(|(account_id, name, currency)| {
+                    let balance = balances.entry(*account_id).or_default();
+                    *balance += changes
+                        .get(&(*account_id, month.clone()))
+                        .copied()
+                        .unwrap_or_default();
+                    Ok(AccountBalance {
+                        account_id: *account_id,
+                        name: name.clone(),
+                        balance: rates.convert(*balance, currency, last_day)?,
+                    })
+                })
+                .collect::<Result<Vec<AccountBalance>, Custom<String>>>()?;
+            let assets: f32 = accounts
+                .iter()
+                .map(|account| account.balance.max(0.0))
+                .sum();
+            let liabilities: f32 = accounts
+                .iter()
+                .map(|account| -account.balance.min(0.0))
+                .sum();
+            Ok(NetWorth {
+                month,
+                assets,
+                liabilities,
+                net_worth: assets - liabilities,
+                accounts,
+            })
+        })
+        .collect::<Result<Vec<NetWorth>, Custom<String>>>();
+    Ok(months.map(|months| NetWorthReport { months }))
+}
+
+// Matches every outflow to the oldest income not spent yet, from the first
+// transaction of the budget on
+fn get_age_of_money(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    from: Month,
+    to: Month,
+) -> QueryResult<AgeOfMoneyReport> {
+    let on_budget = schema::accounts::table
+        .filter(schema::accounts::budget_id.eq(budget_id))
+        .filter(schema::accounts::on_budget.eq(true))
+        .select(schema::accounts::id)
+        .load::<i32>(conn)?;
+    let transactions = schema::transactions::table
+        .filter(schema::transactions::account_id.eq_any(&on_budget))
+        .filter(schema::transactions::deleted_at.is_null())
+        .filter(schema::transactions::date.lt(to.end()))
+        .order((
+            schema::transactions::date.asc(),
+            schema::transactions::id.asc(),
+        ))
+        .select((
+            sql::<Float>(&base_amount()),
+            schema::transactions::date,
+            schema::transactions::transfer_account_id,
+        ))
+        .load::<(f32, NaiveDateTime, Option<i32>)>(conn)?;
+    // Income not spent yet, oldest first
+    let mut income: VecDeque<(NaiveDateTime, f32)> = VecDeque::new();
+    // Days weighted by amount, amount traced to income and amount spent, per month
+    let mut spending: HashMap<String, (f32, f32, f32)> = HashMap::new();
+    for (amount, date, transfer_account_id) in transactions {
+        // Money moving between on-budget accounts is neither received nor spent
+        if transfer_account_id.is_some_and(|id| on_budget.contains(&id)) {
+            continue;
+        }
+        // Inflows, opening balances included, are income received on their date
+        if amount > 0.0 {
+            income.push_back((date, amount));
+            continue;
+        }
+        let (days, traced, spent) = spending.entry(Month::of(date.date()).key()).or_default();
+        *spent -= amount;
+        let mut outflow = -amount;
+        while outflow > 0.0 {
+            let (received, available) = match income.front_mut() {
+                Some(front) => front,
+                None => break,
+            };
+            let consumed = outflow.min(*available);
+            *days += (date - *received).num_days() as f32 * consumed;
+            *traced += consumed;
+            *available -= consumed;
+            outflow -= consumed;
+            if *available <= 0.0 {
+                income.pop_front();
+            }
+        }
+    }
+    let months = from
+        .until(to)
+        .into_iter()
+        .map(|month| {
+            let month = month.key();
+            let (days, traced, spent) = spending.get(&month).copied().unwrap_or_default();
+            AgeOfMoney {
+                month,
+                age: (traced > 0.0).then(|| days / traced),
+                spent,
+            }
+        })
+        .collect();
+    Ok(AgeOfMoneyReport { months })
+}
+
+// Period of the rows of a table with a `date` column, formatted as `format`
+fn period_of(table: &str, format: &str) -> SqlLiteral<Text> {
+    sql::<Text>(&format!("strftime('{}', {}.date)", format, table))
+}
+
+// Month of the rows of a table with a `date` column, as formatted by `Month::key`
+pub(crate) fn month_of(table: &str) -> SqlLiteral<Text> {
+    period_of(table, "%Y-%m")
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Reports", |rocket| async {
+        rocket.mount(
+            "/reports",
+            routes![spending, cash_flow, net_worth, age_of_money],
+        )
+    })
+}
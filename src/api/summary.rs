@@ -0,0 +1,106 @@
+use crate::models;
+use crate::schema;
+
+use chrono::Local;
+use diesel::dsl::sql;
+use diesel::expression::dsl::sum;
+use diesel::query_dsl::GroupByDsl;
+use diesel::sql_types::{Float, Nullable};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::{json::Json, Serialize};
+use rocket::{get, routes};
+
+use super::budget::{budget_buckets, Member};
+use super::exchange_rate::{base_amount, check_rates, Rates};
+use crate::DbConnection;
+use models::Currency;
+
+// Amounts in the currency of the budget
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Summary {
+    // Balance of every account, tracking accounts included
+    net_worth: f32,
+    // Balance of on-budget accounts only, every transaction converted at the
+    // rate of its date like spending, so that rate changes don't change the
+    // money to assign
+    on_budget: f32,
+    // Money put in buckets so far
+    filled: f32,
+    // Money spent from buckets so far, negative for expenses
+    spent: f32,
+    // Money on budget that is not in any bucket yet, credit card spending
+    // from buckets is already set aside for repayment
+    to_assign: f32,
+}
+
+#[get("/")]
+async fn read(db: DbConnection, member: Member) -> Result<Json<Summary>, Custom<String>> {
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        // Net worth is valued at the latest rates
+        let rates = Rates::load(conn, member.budget_id)?;
+        let now = Local::now().naive_local();
+        let net_worth = schema::transactions::table
+            .inner_join(schema::accounts::table)
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .filter(schema::transactions::deleted_at.is_null())
+            .group_by(schema::accounts::id)
+            .select((
+                schema::accounts::currency,
+                sql::<Nullable<Float>>("SUM(transactions.amount)"),
+            ))
+            .load::<(Currency, Option<f32>)>(conn)?
+            .into_iter()
+            .map(|(currency, balance)| rates.convert(balance.unwrap_or_default(), &currency, now))
+            .sum::<Result<f32, _>>();
+        let net_worth = match net_worth {
+            Ok(net_worth) => net_worth,
+            Err(e) => return Ok(Err(e)),
+        };
+        let on_budget = schema::transactions::table
+            .inner_join(schema::accounts::table)
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .filter(schema::accounts::on_budget.eq(true))
+            .filter(schema::transactions::deleted_at.is_null())
+            .select(sql::<Nullable<Float>>(&format!("SUM({})", base_amount())))
+            .first::<Option<f32>>(conn)?
+            .unwrap_or_default();
+        let filled = schema::fills::table
+            .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+            .filter(schema::fills::deleted_at.is_null())
+            .select(sum(schema::fills::amount))
+            .first::<Option<f32>>(conn)?
+            .unwrap_or_default();
+        let spent = schema::transactions::table
+            .inner_join(schema::accounts::table)
+            .filter(schema::accounts::budget_id.eq(member.budget_id))
+            .filter(schema::accounts::on_budget.eq(true))
+            .filter(schema::transactions::bucket_id.is_not_null())
+            .filter(schema::transactions::deleted_at.is_null())
+            .select(sql::<Nullable<Float>>(&format!("SUM({})", base_amount())))
+            .first::<Option<f32>>(conn)?
+            .unwrap_or_default();
+        Ok(Ok(Summary {
+            net_worth,
+            on_budget,
+            filled,
+            spent,
+            to_assign: on_budget - filled - spent,
+        }))
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(Json)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Budget summary", |rocket| async {
+        rocket.mount("/summary", routes![read])
+    })
+}
@@ -0,0 +1,329 @@
+use crate::models;
+use crate::schema;
+
+use diesel::dsl::sql;
+use diesel::query_dsl::GroupByDsl;
+use diesel::result::DatabaseErrorKind;
+use diesel::sql_types::{Float, Nullable};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::{json::Json, Serialize};
+use rocket::{delete, get, post, put, routes};
+
+use super::auth::Admin;
+use super::budget::{budget_accounts, Editor, Member};
+use super::date::Date;
+use super::exchange_rate::{base_amount, check_rates};
+use crate::DbConnection;
+use models::{Tag, TagForm, Transaction, TransactionTag};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct TagSpending {
+    tag_id: i32,
+    name: String,
+    // In the currency of the budget
+    total: f32,
+}
+
+#[get("/")]
+async fn list(db: DbConnection, member: Member) -> Json<Vec<Tag>> {
+    db.run(move |conn| {
+        schema::tags::table
+            .filter(schema::tags::budget_id.eq(member.budget_id))
+            .load::<Tag>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<id>")]
+async fn read(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Result<Json<Tag>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        schema::tags::table
+            .filter(schema::tags::id.eq(id))
+            .filter(schema::tags::budget_id.eq(member.budget_id))
+            .first::<Tag>(conn)
+    })
+    .await
+    .map_err(|_| NotFound("Tag not found."))
+    .map(Json)
+}
+
+#[post("/", data = "<form>")]
+async fn create(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<TagForm>,
+) -> Result<Created<Json<Tag>>, Conflict<String>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            diesel::insert_into(schema::tags::table)
+                .values((&*form, schema::tags::budget_id.eq(editor.budget_id)))
+                .execute(conn)?;
+            get_last_tag(conn, editor.budget_id)
+        })
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))
+    .map(|tag| Created::new("/").body(Json(tag)))
+}
+
+#[delete("/<id>")]
+async fn delete(db: DbConnection, editor: Editor, id: i32) -> Result<(), Conflict<String>> {
+    db.run(move |conn| {
+        diesel::delete(schema::tags::table)
+            .filter(schema::tags::id.eq(id))
+            .filter(schema::tags::budget_id.eq(editor.budget_id))
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| Conflict(Some(e.to_string())))?;
+    Ok(())
+}
+
+#[put("/<id>", data = "<form>")]
+async fn update(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<TagForm>,
+    id: i32,
+) -> Result<Json<Tag>, Custom<String>> {
+    db.run(move |conn| {
+        let query = schema::tags::table
+            .filter(schema::tags::id.eq(id))
+            .filter(schema::tags::budget_id.eq(editor.budget_id));
+        diesel::update(query).set(&*form).execute(conn)?;
+        Ok(query
+            .first::<Tag>(conn)
+            .optional()?
+            .ok_or_else(|| Custom(Status::NotFound, String::from("Tag not found."))))
+    })
+    .await
+    .map_err(|e: diesel::result::Error| match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Custom(Status::Conflict, String::from("Tag already exists."))
+        }
+        _ => Custom(Status::InternalServerError, e.to_string()),
+    })?
+    .map(Json)
+}
+
+// Deletes every tag of the budget, restricted to administrators
+#[delete("/")]
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        diesel::delete(schema::tags::table)
+            .filter(schema::tags::budget_id.eq(editor.budget_id))
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+// Sum of transaction amounts per tag, `from` and `to` days included
+#[get("/spending?<from>&<to>")]
+async fn spending(
+    db: DbConnection,
+    member: Member,
+    from: Date,
+    to: Date,
+) -> Result<Json<Vec<TagSpending>>, Custom<String>> {
+    let (from_date, to_date) = (from.start(), to.end());
+    db.run(move |conn| {
+        if let Err(e) = check_rates(conn, member.budget_id)? {
+            return Ok(Err(e));
+        }
+        schema::transaction_tags::table
+            .inner_join(schema::tags::table)
+            .inner_join(schema::transactions::table)
+            .filter(schema::tags::budget_id.eq(member.budget_id))
+            .filter(schema::transactions::date.ge(from_date))
+            .filter(schema::transactions::date.lt(to_date))
+            .filter(schema::transactions::deleted_at.is_null())
+            .group_by(schema::tags::id)
+            .select((
+                schema::tags::id,
+                schema::tags::name,
+                sql::<Nullable<Float>>(&format!("SUM({})", base_amount())),
+            ))
+            .load::<(i32, String, Option<f32>)>(conn)
+            .map(Ok)
+    })
+    .await
+    .map_err(|e: diesel::result::Error| Custom(Status::InternalServerError, e.to_string()))?
+    .map(|rows| {
+        rows.into_iter()
+            .map(|(tag_id, name, total)| TagSpending {
+                tag_id,
+                name,
+                total: total.unwrap_or_default(),
+            })
+            .collect()
+    })
+    .map(Json)
+}
+
+#[get("/<id>/transactions")]
+async fn read_transactions_for_tag(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Json<Vec<Transaction>> {
+    db.run(move |conn| {
+        schema::transactions::table
+            .inner_join(schema::transaction_tags::table)
+            .filter(schema::transaction_tags::tag_id.eq(id))
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
+            .select(schema::transactions::all_columns)
+            .load::<Transaction>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/<id>/transactions?<from>&<to>")]
+async fn read_transactions_for_tag_for_period(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+    from: Date,
+    to: Date,
+) -> Json<Vec<Transaction>> {
+    let (from_date, to_date) = (from.start(), to.end());
+    db.run(move |conn| {
+        schema::transactions::table
+            .inner_join(schema::transaction_tags::table)
+            .filter(schema::transaction_tags::tag_id.eq(id))
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
+            .filter(schema::transactions::date.ge(from_date))
+            .filter(schema::transactions::date.lt(to_date))
+            .select(schema::transactions::all_columns)
+            .load::<Transaction>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[get("/transaction/<id>/tags")]
+async fn read_tags_for_transaction(db: DbConnection, member: Member, id: i32) -> Json<Vec<Tag>> {
+    db.run(move |conn| {
+        schema::tags::table
+            .inner_join(schema::transaction_tags::table)
+            .filter(schema::transaction_tags::transaction_id.eq(id))
+            .filter(schema::tags::budget_id.eq(member.budget_id))
+            .select(schema::tags::all_columns)
+            .load::<Tag>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+#[put("/transaction/<id>/tags/<tag_id>")]
+async fn add_tag_to_transaction(
+    db: DbConnection,
+    editor: Editor,
+    id: i32,
+    tag_id: i32,
+) -> Result<(), Conflict<String>> {
+    db.run(move |conn| {
+        check_link(conn, editor.budget_id, id, tag_id)?;
+        diesel::insert_into(schema::transaction_tags::table)
+            .values(&TransactionTag {
+                transaction_id: id,
+                tag_id,
+            })
+            .execute(conn)
+    })
+    .await
+    .map_err(|e| Conflict(Some(e.to_string())))?;
+    Ok(())
+}
+
+#[delete("/transaction/<id>/tags/<tag_id>")]
+async fn remove_tag_from_transaction(db: DbConnection, editor: Editor, id: i32, tag_id: i32) {
+    db.run(move |conn| {
+        if check_link(conn, editor.budget_id, id, tag_id).is_err() {
+            return Ok(0);
+        }
+        diesel::delete(schema::transaction_tags::table)
+            .filter(schema::transaction_tags::transaction_id.eq(id))
+            .filter(schema::transaction_tags::tag_id.eq(tag_id))
+            .execute(conn)
+    })
+    .await
+    .unwrap();
+}
+
+// Transactions are only tagged with tags of their own budget
+fn check_link(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    transaction_id: i32,
+    tag_id: i32,
+) -> QueryResult<()> {
+    schema::transactions::table
+        .filter(schema::transactions::id.eq(transaction_id))
+        .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .filter(schema::transactions::deleted_at.is_null())
+        .select(schema::transactions::id)
+        .first::<i32>(conn)?;
+    schema::tags::table
+        .filter(schema::tags::id.eq(tag_id))
+        .filter(schema::tags::budget_id.eq(budget_id))
+        .select(schema::tags::id)
+        .first::<i32>(conn)
+        .map(|_| ())
+}
+
+// While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
+// Currently replacing this function manually
+fn get_last_tag(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Tag> {
+    schema::tags::table
+        .filter(schema::tags::budget_id.eq(budget_id))
+        .order(schema::tags::id.desc())
+        .first::<Tag>(conn)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Tag CRUD", |rocket| async {
+        rocket
+            .mount(
+                "/tag",
+                routes![
+                    read,
+                    create,
+                    list,
+                    delete,
+                    update,
+                    destroy,
+                    spending,
+                    read_transactions_for_tag,
+                    read_transactions_for_tag_for_period
+                ],
+            )
+            .mount(
+                "/",
+                routes![
+                    read_tags_for_transaction,
+                    add_tag_to_transaction,
+                    remove_tag_from_transaction
+                ],
+            )
+    })
+}
@@ -1,164 +1,617 @@
 use crate::models;
 use crate::schema;
 
-use chrono::NaiveDate;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SqliteConnection,
+};
 use rocket::fairing::AdHoc;
-use rocket::response::status::{Conflict, Created, NotFound};
-use rocket::serde::json::Json;
-use rocket::{delete, get, post, put, routes};
+use rocket::http::Status;
+use rocket::response::status::{Conflict, Created, Custom, NotFound};
+use rocket::serde::json::{Json, Value};
+use rocket::serde::Deserialize;
+use rocket::{delete, get, patch, post, put, routes};
 
+use super::audit;
+use super::auth::Admin;
+use super::budget::{budget_accounts, check_account, check_bucket, Editor, Member};
+use super::etag::{IfMatch, Tagged};
+use super::exchange_rate::Rates;
+use super::patch::merge;
 use crate::DbConnection;
-use models::{Transaction, TransactionForm};
+use models::{Currency, Transaction, TransactionForm, TransactionStatus};
 
-#[get("/")]
-async fn list(db: DbConnection) -> Json<Vec<Transaction>> {
-    db.run(|conn| schema::transactions::table.load::<Transaction>(conn))
-        .await
-        .map(Json)
-        .unwrap()
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TransferForm {
+    pub(crate) name: String,
+    pub(crate) amount: f32,
+    pub(crate) date: NaiveDateTime,
+    pub(crate) from_account_id: i32,
+    pub(crate) to_account_id: i32,
+    // Amount received in the currency of the other account, converted from
+    // `amount` at the rates of the date when missing
+    pub(crate) to_amount: Option<f32>,
+}
+
+#[get("/?<status>")]
+async fn list(
+    db: DbConnection,
+    member: Member,
+    status: Option<TransactionStatus>,
+) -> Json<Vec<Transaction>> {
+    db.run(move |conn| {
+        let mut query = schema::transactions::table
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(schema::transactions::status.eq(status));
+        }
+        query.load::<Transaction>(conn)
+    })
+    .await
+    .map(Json)
+    .unwrap()
 }
 
 #[get("/<id>")]
-async fn read(db: DbConnection, id: i32) -> Result<Json<Transaction>, NotFound<&'static str>> {
+async fn read(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+) -> Result<Tagged<Transaction>, NotFound<&'static str>> {
     db.run(move |conn| {
         schema::transactions::table
             .filter(schema::transactions::id.eq(id))
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
             .first::<Transaction>(conn)
     })
     .await
     .map_err(|_| NotFound("Transaction not found."))
-    .map(Json)
+    .map(Tagged)
 }
 
 #[post("/", data = "<form>")]
 async fn create(
     db: DbConnection,
+    editor: Editor,
     form: Json<TransactionForm>,
-) -> Result<Created<Json<Transaction>>, Conflict<String>> {
-    db.run(move |conn| {
-        diesel::insert_into(schema::transactions::table)
-            .values(&*form)
-            .execute(conn)
-    })
-    .await
-    .map_err(|e| Conflict(Some(e.to_string())))?;
-    Ok(Created::new("/").body(get_last_transaction(&db).await.map(Json).unwrap()))
+) -> Result<Created<Json<Transaction>>, Custom<String>> {
+    check_status(&form)
+        .and_then(|_| check_transfer(None, &form))
+        .map_err(|e| Custom(Status::UnprocessableEntity, e))?;
+    db.run(move |conn| conn.transaction(|| insert_transaction(conn, &editor, &form)))
+        .await
+        .map_err(|e: diesel::result::Error| Custom(Status::Conflict, e.to_string()))
+        .map(|transaction| Created::new("/").body(Json(transaction)))
 }
 
+// Moves money between two accounts as a pair of linked transactions
+#[post("/transfer", data = "<form>")]
+async fn transfer(
+    db: DbConnection,
+    editor: Editor,
+    form: Json<TransferForm>,
+) -> Result<Created<Json<Vec<Transaction>>>, Conflict<String>> {
+    if form.from_account_id == form.to_account_id {
+        return Err(Conflict(Some(String::from(
+            "Cannot transfer to the same account.",
+        ))));
+    }
+    db.run(move |conn| conn.transaction(|| insert_transfer(conn, &editor, &form)))
+        .await
+        .map_err(|e: diesel::result::Error| Conflict(Some(e.to_string())))?
+        .map_err(|Custom(_, e)| Conflict(Some(e)))
+        .map(|legs| Created::new("/").body(Json(legs)))
+}
+
+// Moves the transaction to the trash, from where it can be restored until purged
 #[delete("/<id>")]
-async fn delete(db: DbConnection, id: i32) {
+async fn delete(db: DbConnection, editor: Editor, id: i32) -> Result<(), Conflict<&'static str>> {
     db.run(move |conn| {
-        diesel::delete(schema::transactions::table)
-            .filter(schema::transactions::id.eq(id))
-            .execute(conn)
+        conn.transaction(
+            || match find_transaction(conn, editor.budget_id, id).optional()? {
+                Some(transaction) => match check_not_reconciled(conn, &transaction)? {
+                    Ok(()) => trash_transaction(conn, &editor, &transaction).map(Ok),
+                    Err(e) => Ok(Err(Conflict(Some(e)))),
+                },
+                None => Ok(Ok(())),
+            },
+        )
     })
     .await
-    .unwrap();
+    .unwrap()
 }
 
 #[put("/<id>", data = "<form>")]
-async fn update(db: DbConnection, form: Json<TransactionForm>, id: i32) -> Json<Transaction> {
+async fn update(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    form: Json<TransactionForm>,
+    id: i32,
+) -> Result<Tagged<Transaction>, Custom<String>> {
+    let form = form.into_inner();
+    update_transaction(db, editor, if_match, id, move |_| Ok(form)).await
+}
+
+// Changes only the fields present, `null` clears optional fields like `bucket_id`
+#[patch("/<id>", data = "<patch>")]
+async fn patch(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    patch: Json<Value>,
+    id: i32,
+) -> Result<Tagged<Transaction>, Custom<String>> {
+    update_transaction(db, editor, if_match, id, move |before| {
+        merge(before, &patch)
+    })
+    .await
+}
+
+// Updates a transaction with the form built from its current state
+async fn update_transaction<F>(
+    db: DbConnection,
+    editor: Editor,
+    if_match: IfMatch,
+    id: i32,
+    form: F,
+) -> Result<Tagged<Transaction>, Custom<String>>
+where
+    F: FnOnce(&Transaction) -> Result<TransactionForm, Custom<String>> + Send + 'static,
+{
     db.run(move |conn| {
-        diesel::update(schema::transactions::table)
-            .filter(schema::transactions::id.eq(id))
-            .set(&*form)
-            .execute(conn)
-            .unwrap();
-        schema::transactions::table
-            .filter(schema::transactions::id.eq(id))
-            .first::<Transaction>(conn)
+        conn.transaction(|| {
+            let before = match find_transaction(conn, editor.budget_id, id).optional()? {
+                Some(before) => before,
+                None => {
+                    return Ok(Err(Custom(
+                        Status::NotFound,
+                        String::from("Transaction not found."),
+                    )))
+                }
+            };
+            if let Err(e) = check_not_reconciled(conn, &before)? {
+                return Ok(Err(Custom(Status::Conflict, e.to_string())));
+            }
+            let form = match if_match.check(&before).and_then(|_| form(&before)) {
+                Ok(form) => form,
+                Err(e) => return Ok(Err(e)),
+            };
+            if let Err(e) = check_status(&form).and_then(|_| check_transfer(Some(&before), &form)) {
+                return Ok(Err(Custom(Status::UnprocessableEntity, e)));
+            }
+            replace_transaction(conn, &editor, &before, &form).map(Ok)
+        })
     })
     .await
-    .map(Json)
-    .unwrap()
+    .map_err(|_: diesel::result::Error| {
+        Custom(
+            Status::Conflict,
+            String::from("Account or bucket not found."),
+        )
+    })?
+    .map(Tagged)
 }
 
+// Deletes every transaction of the budget, trash included, restricted to administrators
 #[delete("/")]
-async fn destroy(db: DbConnection) {
-    db.run(|conn| diesel::delete(schema::transactions::table).execute(conn))
-        .await
-        .unwrap();
+async fn destroy(db: DbConnection, editor: Editor, _admin: Admin) {
+    db.run(move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let query = schema::transactions::table
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(editor.budget_id)));
+            let transactions = query.load::<Transaction>(conn)?;
+            diesel::delete(query).execute(conn)?;
+            for transaction in &transactions {
+                audit::record(
+                    conn,
+                    editor.budget_id,
+                    editor.user_id,
+                    Some(transaction),
+                    None,
+                )?;
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
 }
 
-#[get("/account/<account_id>/transactions")]
+#[get("/account/<account_id>/transactions?<status>")]
 async fn read_transactions_for_account(
     db: DbConnection,
+    member: Member,
     account_id: i32,
+    status: Option<TransactionStatus>,
 ) -> Json<Vec<Transaction>> {
     db.run(move |conn| {
-        schema::transactions::table
+        let mut query = schema::transactions::table
             .filter(schema::transactions::account_id.eq(account_id))
-            .load::<Transaction>(conn)
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(schema::transactions::status.eq(status));
+        }
+        query.load::<Transaction>(conn)
     })
     .await
     .map(Json)
     .unwrap()
 }
 
-#[get("/account/<account_id>/transactions/<year>/<month>")]
+#[get("/account/<account_id>/transactions/<year>/<month>?<status>")]
 async fn read_transactions_for_account_for_period(
     db: DbConnection,
+    member: Member,
     account_id: i32,
     year: i32,
     month: u8,
+    status: Option<TransactionStatus>,
 ) -> Json<Vec<Transaction>> {
     let from_date = NaiveDate::from_ymd(year, month.into(), 1).and_hms(0, 0, 0);
     let to_date = NaiveDate::from_ymd(year, month as u32 + 1, 1).and_hms(0, 0, 0);
     db.run(move |conn| {
-        schema::transactions::table
+        let mut query = schema::transactions::table
             .filter(schema::transactions::account_id.eq(account_id))
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
             .filter(schema::transactions::date.ge(from_date))
             .filter(schema::transactions::date.lt(to_date))
-            .load::<Transaction>(conn)
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(schema::transactions::status.eq(status));
+        }
+        query.load::<Transaction>(conn)
     })
     .await
     .map(Json)
     .unwrap()
 }
 
-#[get("/bucket/<id>/transactions")]
-async fn read_transactions_for_bucket(db: DbConnection, id: i32) -> Json<Vec<Transaction>> {
+#[get("/bucket/<id>/transactions?<status>")]
+async fn read_transactions_for_bucket(
+    db: DbConnection,
+    member: Member,
+    id: i32,
+    status: Option<TransactionStatus>,
+) -> Json<Vec<Transaction>> {
     db.run(move |conn| {
-        schema::transactions::table
+        let mut query = schema::transactions::table
             .filter(schema::transactions::bucket_id.eq(id))
-            .load::<Transaction>(conn)
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(schema::transactions::status.eq(status));
+        }
+        query.load::<Transaction>(conn)
     })
     .await
     .map(Json)
     .unwrap()
 }
 
-#[get("/bucket/<id>/transactions/<year>/<month>")]
+#[get("/bucket/<id>/transactions/<year>/<month>?<status>")]
 async fn read_transactions_for_bucket_for_period(
     db: DbConnection,
+    member: Member,
     id: i32,
     year: i32,
     month: u8,
+    status: Option<TransactionStatus>,
 ) -> Json<Vec<Transaction>> {
     let from_date = NaiveDate::from_ymd(year, month.into(), 1).and_hms(0, 0, 0);
     let to_date = NaiveDate::from_ymd(year, month as u32 + 1, 1).and_hms(0, 0, 0);
     db.run(move |conn| {
-        schema::transactions::table
+        let mut query = schema::transactions::table
             .filter(schema::transactions::bucket_id.eq(id))
+            .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+            .filter(schema::transactions::deleted_at.is_null())
             .filter(schema::transactions::date.ge(from_date))
             .filter(schema::transactions::date.lt(to_date))
-            .load::<Transaction>(conn)
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(schema::transactions::status.eq(status));
+        }
+        query.load::<Transaction>(conn)
     })
     .await
     .map(Json)
     .unwrap()
 }
 
+// Transaction of the budget which is not in the trash
+pub(crate) fn find_transaction(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    id: i32,
+) -> QueryResult<Transaction> {
+    schema::transactions::table
+        .filter(schema::transactions::id.eq(id))
+        .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .filter(schema::transactions::deleted_at.is_null())
+        .first::<Transaction>(conn)
+}
+
+// Inserts both legs of a transfer, the outgoing one first
+pub(crate) fn insert_transfer(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    form: &TransferForm,
+) -> QueryResult<Result<Vec<Transaction>, Custom<String>>> {
+    check_account(conn, editor.budget_id, form.from_account_id)?;
+    check_account(conn, editor.budget_id, form.to_account_id)?;
+    let currency = |account_id: i32| {
+        schema::accounts::table
+            .filter(schema::accounts::id.eq(account_id))
+            .select(schema::accounts::currency)
+            .first::<Currency>(conn)
+    };
+    let (from, to) = (
+        currency(form.from_account_id)?,
+        currency(form.to_account_id)?,
+    );
+    let to_amount = match form.to_amount {
+        Some(to_amount) => to_amount,
+        None if from == to => form.amount,
+        // Converted through the currency of the budget
+        None => {
+            let rates = Rates::load(conn, editor.budget_id)?;
+            let amount = rates
+                .convert(form.amount, &from, form.date)
+                .and_then(|amount| rates.rate(&to, form.date).map(|rate| amount / rate));
+            match amount {
+                Ok(amount) => amount,
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+    };
+    let legs = [
+        (form.from_account_id, form.to_account_id, -form.amount),
+        (form.to_account_id, form.from_account_id, to_amount),
+    ];
+    let mut transfer_id = None;
+    for (account_id, transfer_account_id, amount) in legs {
+        diesel::insert_into(schema::transactions::table)
+            .values((
+                schema::transactions::name.eq(&form.name),
+                schema::transactions::amount.eq(amount),
+                schema::transactions::date.eq(form.date),
+                schema::transactions::account_id.eq(account_id),
+                schema::transactions::transfer_account_id.eq(transfer_account_id),
+                schema::transactions::transfer_id.eq(transfer_id),
+            ))
+            .execute(conn)?;
+        // Both legs share the id of the outgoing one
+        if transfer_id.is_none() {
+            let id = get_last_transaction(conn, editor.budget_id)?.id;
+            diesel::update(schema::transactions::table.filter(schema::transactions::id.eq(id)))
+                .set(schema::transactions::transfer_id.eq(id))
+                .execute(conn)?;
+            transfer_id = Some(id);
+        }
+    }
+    let legs = schema::transactions::table
+        .filter(schema::transactions::transfer_id.eq(transfer_id))
+        .order(schema::transactions::id.asc())
+        .load::<Transaction>(conn)?;
+    for leg in &legs {
+        audit::record(conn, editor.budget_id, editor.user_id, None, Some(leg))?;
+    }
+    Ok(Ok(legs))
+}
+
+pub(crate) fn insert_transaction(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    form: &TransactionForm,
+) -> QueryResult<Transaction> {
+    import_transaction(conn, editor, form, None)
+}
+
+// Inserts a transaction along with the reference of the bank it was imported from
+pub(crate) fn import_transaction(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    form: &TransactionForm,
+    import_id: Option<&str>,
+) -> QueryResult<Transaction> {
+    check_references(conn, editor.budget_id, form)?;
+    diesel::insert_into(schema::transactions::table)
+        .values((form, schema::transactions::import_id.eq(import_id)))
+        .execute(conn)?;
+    let transaction = get_last_transaction(conn, editor.budget_id)?;
+    audit::record(
+        conn,
+        editor.budget_id,
+        editor.user_id,
+        None,
+        Some(&transaction),
+    )?;
+    Ok(transaction)
+}
+
+// Inserts the starting entry of an account, part of its balance but not income
+pub(crate) fn insert_opening(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    form: &TransactionForm,
+) -> QueryResult<Transaction> {
+    diesel::insert_into(schema::transactions::table)
+        .values((form, schema::transactions::opening.eq(true)))
+        .execute(conn)?;
+    let transaction = get_last_transaction(conn, editor.budget_id)?;
+    audit::record(
+        conn,
+        editor.budget_id,
+        editor.user_id,
+        None,
+        Some(&transaction),
+    )?;
+    Ok(transaction)
+}
+
+// Updates a transaction, and the other leg of a transfer to match it
+pub(crate) fn replace_transaction(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    before: &Transaction,
+    form: &TransactionForm,
+) -> QueryResult<Transaction> {
+    let after = update_row(conn, editor, before, form)?;
+    for other in find_legs(conn, before)? {
+        if other.id != before.id {
+            update_row(conn, editor, &other, &form.other_leg(before, &other))?;
+        }
+    }
+    Ok(after)
+}
+
+fn update_row(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    before: &Transaction,
+    form: &TransactionForm,
+) -> QueryResult<Transaction> {
+    check_references(conn, editor.budget_id, form)?;
+    let query = schema::transactions::table.filter(schema::transactions::id.eq(before.id));
+    diesel::update(query)
+        .set((
+            form,
+            schema::transactions::version.eq(schema::transactions::version + 1),
+        ))
+        .execute(conn)?;
+    let after = query.first::<Transaction>(conn)?;
+    audit::record(
+        conn,
+        editor.budget_id,
+        editor.user_id,
+        Some(before),
+        Some(&after),
+    )?;
+    Ok(after)
+}
+
+// Moves the transaction to the trash, along with the other leg of a transfer
+pub(crate) fn trash_transaction(
+    conn: &SqliteConnection,
+    editor: &Editor,
+    transaction: &Transaction,
+) -> QueryResult<()> {
+    let deleted_at = Local::now().naive_local();
+    for leg in find_legs(conn, transaction)? {
+        diesel::update(schema::transactions::table.filter(schema::transactions::id.eq(leg.id)))
+            .set((
+                schema::transactions::deleted_at.eq(deleted_at),
+                schema::transactions::version.eq(schema::transactions::version + 1),
+            ))
+            .execute(conn)?;
+        audit::record(conn, editor.budget_id, editor.user_id, Some(&leg), None)?;
+    }
+    Ok(())
+}
+
+// Both legs of a transfer, on the same side of the trash as `transaction`,
+// or the transaction alone
+pub(crate) fn find_legs(
+    conn: &SqliteConnection,
+    transaction: &Transaction,
+) -> QueryResult<Vec<Transaction>> {
+    let mut query = schema::transactions::table
+        .filter(schema::transactions::id.eq(transaction.id))
+        .into_boxed();
+    if let Some(transfer_id) = transaction.transfer_id {
+        query = schema::transactions::table
+            .filter(schema::transactions::transfer_id.eq(transfer_id))
+            .order(schema::transactions::id.asc())
+            .into_boxed();
+    }
+    if transaction.deleted_at.is_some() {
+        query = query.filter(schema::transactions::deleted_at.is_not_null());
+    } else {
+        query = query.filter(schema::transactions::deleted_at.is_null());
+    }
+    query.load::<Transaction>(conn)
+}
+
+// Transfers are only made through `/transaction/transfer`, after which a leg
+// stays a transfer, moved along with its other leg
+pub(crate) fn check_transfer(
+    before: Option<&Transaction>,
+    form: &TransactionForm,
+) -> Result<(), String> {
+    let transfer = before.and_then(|before| before.transfer_account_id);
+    match (transfer, form.transfer_account_id) {
+        (None, None) => Ok(()),
+        (None, Some(_)) => Err(String::from(
+            "Transfers are made with `/transaction/transfer`.",
+        )),
+        (Some(_), None) => Err(String::from(
+            "A transfer can't become a plain transaction, delete it instead.",
+        )),
+        (Some(_), Some(transfer_account_id)) if transfer_account_id == form.account_id => {
+            Err(String::from("Cannot transfer to the same account."))
+        }
+        (Some(_), Some(_)) => Ok(()),
+    }
+}
+
+// Accounts and buckets of a transaction must belong to its budget
+fn check_references(
+    conn: &SqliteConnection,
+    budget_id: i32,
+    form: &TransactionForm,
+) -> QueryResult<()> {
+    check_account(conn, budget_id, form.account_id)?;
+    if let Some(bucket_id) = form.bucket_id {
+        check_bucket(conn, budget_id, bucket_id)?;
+    }
+    if let Some(transfer_account_id) = form.transfer_account_id {
+        check_account(conn, budget_id, transfer_account_id)?;
+    }
+    Ok(())
+}
+
+// Reconciled transactions are locked against edits, along with every leg of a
+// transfer they are part of, checked within the database transaction making the edit
+pub(crate) fn check_not_reconciled(
+    conn: &SqliteConnection,
+    transaction: &Transaction,
+) -> QueryResult<Result<(), &'static str>> {
+    let reconciled = find_legs(conn, transaction)?
+        .iter()
+        .any(|leg| leg.status == TransactionStatus::Reconciled);
+    Ok(if reconciled {
+        Err("Transaction is reconciled.")
+    } else {
+        Ok(())
+    })
+}
+
+// Only a reconciliation marks transactions as reconciled
+pub(crate) fn check_status(form: &TransactionForm) -> Result<(), String> {
+    match form.status {
+        TransactionStatus::Reconciled => Err(String::from(
+            "Transactions are reconciled with `/account/<id>/reconcile/confirm`.",
+        )),
+        _ => Ok(()),
+    }
+}
+
 // While diesel 2.0.0 isn't compatible with Rocket, we can't use `get_result`
 // Currently replacing this function manually
-async fn get_last_transaction(db: &DbConnection) -> Result<Transaction, diesel::result::Error> {
-    db.run(|conn| {
-        schema::transactions::table
-            .order(schema::transactions::id.desc())
-            .first::<Transaction>(conn)
-    })
-    .await
+fn get_last_transaction(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Transaction> {
+    schema::transactions::table
+        .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+        .order(schema::transactions::id.desc())
+        .first::<Transaction>(conn)
 }
 
 pub fn stage() -> AdHoc {
@@ -166,7 +619,7 @@ pub fn stage() -> AdHoc {
         rocket
             .mount(
                 "/transaction",
-                routes![read, create, list, delete, update, destroy],
+                routes![read, create, list, delete, update, patch, destroy, transfer],
             )
             .mount(
                 "/",
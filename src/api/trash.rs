@@ -0,0 +1,232 @@
+use crate::models;
+use crate::schema;
+
+use std::env;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::fairing::AdHoc;
+use rocket::response::status::NotFound;
+use rocket::serde::{json::Json, Serialize};
+use rocket::tokio::{task, time};
+use rocket::{delete, get, post, routes};
+
+use super::audit;
+use super::budget::{budget_accounts, budget_buckets, Editor, Member};
+use super::transaction::find_legs;
+use crate::DbConnection;
+use models::{Fill, Transaction};
+
+// Days a deleted row stays in the trash before being purged
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Trash {
+    transactions: Vec<Transaction>,
+    fills: Vec<Fill>,
+}
+
+#[get("/")]
+async fn list(db: DbConnection, member: Member) -> Json<Trash> {
+    db.run(move |conn| -> QueryResult<Trash> {
+        Ok(Trash {
+            transactions: schema::transactions::table
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(member.budget_id)))
+                .filter(schema::transactions::deleted_at.is_not_null())
+                .order(schema::transactions::deleted_at.desc())
+                .load::<Transaction>(conn)?,
+            fills: schema::fills::table
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(member.budget_id)))
+                .filter(schema::fills::deleted_at.is_not_null())
+                .order(schema::fills::deleted_at.desc())
+                .load::<Fill>(conn)?,
+        })
+    })
+    .await
+    .map(Json)
+    .unwrap()
+}
+
+// Permanently deletes everything in the trash of the budget, recorded as a
+// second deletion of the trashed rows
+#[delete("/")]
+async fn empty(db: DbConnection, editor: Editor) {
+    db.run(move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let transactions = schema::transactions::table
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(editor.budget_id)))
+                .filter(schema::transactions::deleted_at.is_not_null());
+            for transaction in transactions.load::<Transaction>(conn)? {
+                audit::record(
+                    conn,
+                    editor.budget_id,
+                    editor.user_id,
+                    Some(&transaction),
+                    None,
+                )?;
+            }
+            diesel::delete(transactions).execute(conn)?;
+            let fills = schema::fills::table
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(editor.budget_id)))
+                .filter(schema::fills::deleted_at.is_not_null());
+            for fill in fills.load::<Fill>(conn)? {
+                audit::record(conn, editor.budget_id, editor.user_id, Some(&fill), None)?;
+            }
+            diesel::delete(fills).execute(conn)
+        })
+    })
+    .await
+    .unwrap();
+}
+
+#[post("/transaction/<id>/restore")]
+async fn restore_transaction(
+    db: DbConnection,
+    editor: Editor,
+    id: i32,
+) -> Result<Json<Transaction>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let transaction = schema::transactions::table
+                .filter(schema::transactions::id.eq(id))
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(editor.budget_id)))
+                .filter(schema::transactions::deleted_at.is_not_null())
+                .first::<Transaction>(conn)?;
+            // Both legs of a transfer come back together
+            for before in find_legs(conn, &transaction)? {
+                let query =
+                    schema::transactions::table.filter(schema::transactions::id.eq(before.id));
+                diesel::update(query)
+                    .set((
+                        schema::transactions::deleted_at.eq(None::<NaiveDateTime>),
+                        schema::transactions::version.eq(schema::transactions::version + 1),
+                    ))
+                    .execute(conn)?;
+                let after = query.first::<Transaction>(conn)?;
+                audit::record(
+                    conn,
+                    editor.budget_id,
+                    editor.user_id,
+                    Some(&before),
+                    Some(&after),
+                )?;
+            }
+            schema::transactions::table
+                .filter(schema::transactions::id.eq(id))
+                .first::<Transaction>(conn)
+        })
+    })
+    .await
+    .map_err(|_: diesel::result::Error| NotFound("Transaction not found in the trash."))
+    .map(Json)
+}
+
+#[post("/fill/<id>/restore")]
+async fn restore_fill(
+    db: DbConnection,
+    editor: Editor,
+    id: i32,
+) -> Result<Json<Fill>, NotFound<&'static str>> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            let query = schema::fills::table
+                .filter(schema::fills::id.eq(id))
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(editor.budget_id)))
+                .filter(schema::fills::deleted_at.is_not_null());
+            let before = query.first::<Fill>(conn)?;
+            diesel::update(query)
+                .set((
+                    schema::fills::deleted_at.eq(None::<NaiveDateTime>),
+                    schema::fills::version.eq(schema::fills::version + 1),
+                ))
+                .execute(conn)?;
+            let after = schema::fills::table
+                .filter(schema::fills::id.eq(id))
+                .first::<Fill>(conn)?;
+            audit::record(
+                conn,
+                editor.budget_id,
+                editor.user_id,
+                Some(&before),
+                Some(&after),
+            )?;
+            Ok(after)
+        })
+    })
+    .await
+    .map_err(|_: diesel::result::Error| NotFound("Fill not found in the trash."))
+    .map(Json)
+}
+
+// Permanently deletes rows of every budget that were trashed before `deleted_before`
+// Purged rows are audited as deleted by the user who trashed them
+fn purge(conn: &SqliteConnection, deleted_before: NaiveDateTime) -> QueryResult<usize> {
+    conn.transaction(|| {
+        let transactions =
+            schema::transactions::table.filter(schema::transactions::deleted_at.lt(deleted_before));
+        for transaction in transactions.load::<Transaction>(conn)? {
+            if let Some((budget_id, user_id)) = audit::last_change(conn, &transaction)? {
+                audit::record(conn, budget_id, user_id, Some(&transaction), None)?;
+            }
+        }
+        let transactions = diesel::delete(transactions).execute(conn)?;
+        let fills = schema::fills::table.filter(schema::fills::deleted_at.lt(deleted_before));
+        for fill in fills.load::<Fill>(conn)? {
+            if let Some((budget_id, user_id)) = audit::last_change(conn, &fill)? {
+                audit::record(conn, budget_id, user_id, Some(&fill), None)?;
+            }
+        }
+        let fills = diesel::delete(fills).execute(conn)?;
+        Ok(transactions + fills)
+    })
+}
+
+// Opens a connection for a single purge, set up like the ones of the pool
+fn purge_database(database_url: &str, deleted_before: NaiveDateTime) -> Result<usize, String> {
+    let conn = SqliteConnection::establish(database_url).map_err(|e| e.to_string())?;
+    conn.batch_execute("PRAGMA busy_timeout = 1000; PRAGMA foreign_keys = ON;")
+        .and_then(|_| purge(&conn, deleted_before))
+        .map_err(|e| e.to_string())
+}
+
+// Purges the trash periodically, keeping rows for `TRASH_RETENTION_DAYS`.
+// The pool has no connection to lend outside of a request, so every run opens
+// its own, and a failed run is logged and retried at the next tick.
+async fn purge_periodically(database_url: String) {
+    let retention = env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    let mut interval = time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let deleted_before = Local::now().naive_local() - chrono::Duration::days(retention);
+        let database_url = database_url.clone();
+        match task::spawn_blocking(move || purge_database(&database_url, deleted_before)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => rocket::error!("Trash purge failed: {}", e),
+            Err(e) => rocket::error!("Trash purge failed: {}", e),
+        }
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Trash", |rocket| async {
+        rocket
+            .mount("/trash", routes![list, empty])
+            .mount("/", routes![restore_transaction, restore_fill])
+            .attach(AdHoc::on_liftoff("Trash purge", |rocket| {
+                Box::pin(async move {
+                    let database_url = rocket
+                        .figment()
+                        .extract_inner::<String>("databases.sqlite.url")
+                        .expect("database url");
+                    rocket::tokio::spawn(purge_periodically(database_url));
+                })
+            }))
+    })
+}
@@ -0,0 +1,180 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use rocket::serde::{json, Deserialize, Serialize};
+
+use crate::api::budget::{budget_accounts, budget_buckets};
+use crate::models::{
+    Account, AuditRow, Bucket, Budget, BudgetMember, ExchangeRate, Fill, Loan,
+    RecurringTransaction, Tag, Transaction, TransactionTag, UserRow,
+};
+use crate::schema;
+
+// Latest migration, bumped with every new one: an instance backup is only
+// restored into the schema it was taken from
+pub const SCHEMA_VERSION: &str = "2026-10-19-230000";
+
+// Every row of a budget, written to disk before destructive operations
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BudgetBackup {
+    pub created_at: NaiveDateTime,
+    pub budget: Budget,
+    pub accounts: Vec<Account>,
+    pub loans: Vec<Loan>,
+    pub buckets: Vec<Bucket>,
+    pub tags: Vec<Tag>,
+    pub transactions: Vec<Transaction>,
+    pub transaction_tags: Vec<TransactionTag>,
+    pub fills: Vec<Fill>,
+    pub recurring_transactions: Vec<RecurringTransaction>,
+}
+
+impl BudgetBackup {
+    pub fn read(conn: &SqliteConnection, budget_id: i32) -> QueryResult<Self> {
+        Ok(Self {
+            created_at: Local::now().naive_local(),
+            budget: schema::budgets::table
+                .filter(schema::budgets::id.eq(budget_id))
+                .first::<Budget>(conn)?,
+            accounts: schema::accounts::table
+                .filter(schema::accounts::budget_id.eq(budget_id))
+                .load::<Account>(conn)?,
+            loans: schema::loans::table
+                .filter(schema::loans::account_id.eq_any(budget_accounts(budget_id)))
+                .load::<Loan>(conn)?,
+            buckets: schema::buckets::table
+                .filter(schema::buckets::budget_id.eq(budget_id))
+                .load::<Bucket>(conn)?,
+            tags: schema::tags::table
+                .filter(schema::tags::budget_id.eq(budget_id))
+                .load::<Tag>(conn)?,
+            transactions: schema::transactions::table
+                .filter(schema::transactions::account_id.eq_any(budget_accounts(budget_id)))
+                .load::<Transaction>(conn)?,
+            transaction_tags: schema::transaction_tags::table
+                .inner_join(schema::tags::table)
+                .filter(schema::tags::budget_id.eq(budget_id))
+                .select(schema::transaction_tags::all_columns)
+                .load::<TransactionTag>(conn)?,
+            fills: schema::fills::table
+                .filter(schema::fills::bucket_id.eq_any(budget_buckets(budget_id)))
+                .load::<Fill>(conn)?,
+            recurring_transactions: schema::recurring_transactions::table
+                .filter(
+                    schema::recurring_transactions::account_id.eq_any(budget_accounts(budget_id)),
+                )
+                .load::<RecurringTransaction>(conn)?,
+        })
+    }
+
+    // Writes the backup as JSON in `BACKUP_DIR` and returns the file path
+    pub fn write(&self, budget_id: i32) -> io::Result<PathBuf> {
+        let directory = PathBuf::from(env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".into()));
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!(
+            "budget_{}_{}.json",
+            budget_id,
+            self.created_at.format("%Y%m%dT%H%M%S%.f")
+        ));
+        fs::write(&path, json::to_pretty_string(self)?)?;
+        Ok(path)
+    }
+}
+
+// Every row of the instance but the sessions, to move it to another server
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InstanceBackup {
+    pub schema_version: String,
+    pub created_at: NaiveDateTime,
+    pub users: Vec<UserRow>,
+    pub budgets: Vec<Budget>,
+    pub budget_members: Vec<BudgetMember>,
+    pub exchange_rates: Vec<ExchangeRate>,
+    pub accounts: Vec<Account>,
+    pub loans: Vec<Loan>,
+    pub buckets: Vec<Bucket>,
+    pub tags: Vec<Tag>,
+    pub transactions: Vec<Transaction>,
+    pub transaction_tags: Vec<TransactionTag>,
+    pub fills: Vec<Fill>,
+    pub recurring_transactions: Vec<RecurringTransaction>,
+    pub audit_log: Vec<AuditRow>,
+}
+
+impl InstanceBackup {
+    // Reads every table within one transaction, for a consistent snapshot
+    pub fn read(conn: &SqliteConnection) -> QueryResult<Self> {
+        conn.transaction(|| {
+            Ok(Self {
+                schema_version: String::from(SCHEMA_VERSION),
+                created_at: Local::now().naive_local(),
+                users: schema::users::table.load::<UserRow>(conn)?,
+                budgets: schema::budgets::table.load::<Budget>(conn)?,
+                budget_members: schema::budget_members::table.load::<BudgetMember>(conn)?,
+                exchange_rates: schema::exchange_rates::table.load::<ExchangeRate>(conn)?,
+                accounts: schema::accounts::table.load::<Account>(conn)?,
+                loans: schema::loans::table.load::<Loan>(conn)?,
+                buckets: schema::buckets::table.load::<Bucket>(conn)?,
+                tags: schema::tags::table.load::<Tag>(conn)?,
+                transactions: schema::transactions::table.load::<Transaction>(conn)?,
+                transaction_tags: schema::transaction_tags::table.load::<TransactionTag>(conn)?,
+                fills: schema::fills::table.load::<Fill>(conn)?,
+                recurring_transactions: schema::recurring_transactions::table
+                    .load::<RecurringTransaction>(conn)?,
+                audit_log: schema::audit_log::table.load::<AuditRow>(conn)?,
+            })
+        })
+    }
+
+    // Inserts every row with its id, referenced tables first
+    pub fn restore(&self, conn: &SqliteConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(schema::users::table)
+                .values(&self.users)
+                .execute(conn)?;
+            diesel::insert_into(schema::budgets::table)
+                .values(&self.budgets)
+                .execute(conn)?;
+            diesel::insert_into(schema::budget_members::table)
+                .values(&self.budget_members)
+                .execute(conn)?;
+            diesel::insert_into(schema::exchange_rates::table)
+                .values(&self.exchange_rates)
+                .execute(conn)?;
+            diesel::insert_into(schema::accounts::table)
+                .values(&self.accounts)
+                .execute(conn)?;
+            diesel::insert_into(schema::loans::table)
+                .values(&self.loans)
+                .execute(conn)?;
+            diesel::insert_into(schema::buckets::table)
+                .values(&self.buckets)
+                .execute(conn)?;
+            diesel::insert_into(schema::tags::table)
+                .values(&self.tags)
+                .execute(conn)?;
+            diesel::insert_into(schema::transactions::table)
+                .values(&self.transactions)
+                .execute(conn)?;
+            diesel::insert_into(schema::transaction_tags::table)
+                .values(&self.transaction_tags)
+                .execute(conn)?;
+            diesel::insert_into(schema::fills::table)
+                .values(&self.fills)
+                .execute(conn)?;
+            diesel::insert_into(schema::recurring_transactions::table)
+                .values(&self.recurring_transactions)
+                .execute(conn)?;
+            diesel::insert_into(schema::audit_log::table)
+                .values(&self.audit_log)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+}
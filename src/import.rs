@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use roxmltree::{Document, Node};
+use sha2::{Digest, Sha256};
+
+use crate::models::TransactionForm;
+
+// Name of the transactions whose statement names neither a party nor a memo
+const DEFAULT_NAME: &str = "Imported transaction";
+// QIF account types holding bank transactions, others are skipped
+const QIF_TYPES: [&str; 5] = ["Bank", "Cash", "CCard", "Oth A", "Oth L"];
+
+// Transaction read from a bank statement
+pub struct ImportedTransaction {
+    pub form: TransactionForm,
+    // Reference of the bank, or a digest of the transaction when there is none
+    pub import_id: String,
+}
+
+// Fields of a QIF record, named after their codes
+#[derive(Default)]
+struct QifRecord {
+    line: usize,
+    date: Option<String>,
+    amount: Option<String>,
+    payee: Option<String>,
+    memo: Option<String>,
+    number: Option<String>,
+}
+
+// Reads the bank, cash and credit card transactions of a QIF file
+pub fn parse_qif(text: &str, account_id: i32) -> Result<Vec<ImportedTransaction>, String> {
+    let mut transactions = Vec::new();
+    let mut digests = Digests::default();
+    let mut record = QifRecord::default();
+    // Records are skipped in account lists and sections of other types
+    let mut skipping = false;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let mut chars = line.chars();
+        let (code, value) = match chars.next() {
+            Some(code) => (code, chars.as_str().trim()),
+            None => continue,
+        };
+        if record.line == 0 {
+            record.line = index + 1;
+        }
+        match code {
+            '!' => {
+                if let Some(kind) = value.strip_prefix("Type:") {
+                    skipping = !QIF_TYPES.contains(&kind);
+                } else if value == "Account" {
+                    skipping = true;
+                }
+                record = QifRecord::default();
+            }
+            '^' => {
+                if !skipping {
+                    transactions.push(qif_transaction(&record, account_id, &mut digests)?);
+                }
+                record = QifRecord::default();
+            }
+            'D' => record.date = Some(value.to_string()),
+            // `U` repeats the amount with a higher precision in some exports
+            'T' | 'U' => record.amount = Some(value.to_string()),
+            'P' => record.payee = Some(value.to_string()),
+            'M' => record.memo = Some(value.to_string()),
+            'N' => record.number = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    // The last record may lack its caret
+    if !skipping && (record.date.is_some() || record.amount.is_some()) {
+        transactions.push(qif_transaction(&record, account_id, &mut digests)?);
+    }
+    Ok(transactions)
+}
+
+fn qif_transaction(
+    record: &QifRecord,
+    account_id: i32,
+    digests: &mut Digests,
+) -> Result<ImportedTransaction, String> {
+    let date = record
+        .date
+        .as_deref()
+        .and_then(parse_qif_date)
+        .ok_or_else(|| format!("Invalid date in the record of line {}.", record.line))?;
+    let amount = record
+        .amount
+        .as_deref()
+        .and_then(parse_amount)
+        .ok_or_else(|| format!("Invalid amount in the record of line {}.", record.line))?;
+    let memo = record.memo.clone().filter(|memo| !memo.is_empty());
+    let name = record
+        .payee
+        .clone()
+        .filter(|payee| !payee.is_empty())
+        .or_else(|| memo.clone())
+        .unwrap_or_else(|| String::from(DEFAULT_NAME));
+    let import_id = digests.next(&[
+        &date.to_string(),
+        &format!("{:.2}", amount),
+        &name,
+        memo.as_deref().unwrap_or_default(),
+        record.number.as_deref().unwrap_or_default(),
+    ]);
+    Ok(ImportedTransaction {
+        form: TransactionForm::imported(name, amount, date, account_id, memo),
+        import_id,
+    })
+}
+
+// QIF dates are month first as written by Quicken, like `7/2/2022` or `7/ 2'22`,
+// unless separated by dots or dashes
+fn parse_qif_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.replace('\'', "/").replace(' ', "");
+    let short_year = value
+        .rsplit(['/', '.'])
+        .next()
+        .is_some_and(|year| year.len() == 2);
+    let format = if value.contains('-') {
+        "%Y-%m-%d"
+    } else if value.contains('.') {
+        if short_year {
+            "%d.%m.%y"
+        } else {
+            "%d.%m.%Y"
+        }
+    } else if short_year {
+        "%m/%d/%y"
+    } else {
+        "%m/%d/%Y"
+    };
+    NaiveDate::parse_from_str(&value, format)
+        .ok()
+        .map(|date| date.and_hms(0, 0, 0))
+}
+
+// Reads amounts with a decimal point or comma, and any thousands separator
+fn parse_amount(value: &str) -> Option<f32> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let decimal = match (value.rfind('.'), value.rfind(',')) {
+        (Some(point), Some(comma)) => point.max(comma),
+        (Some(point), None) => point,
+        // A lone comma followed by up to two digits is a decimal comma
+        (None, Some(comma)) if value.len() - comma <= 3 => comma,
+        _ => value.len(),
+    };
+    let integer: String = value[..decimal]
+        .chars()
+        .filter(|c| !matches!(c, '.' | ','))
+        .collect();
+    let fraction = value.get(decimal + 1..).unwrap_or_default();
+    format!("{}.{}", integer, fraction).parse().ok()
+}
+
+// Reads the booked entries of every statement of a CAMT.053 document
+pub fn parse_camt053(xml: &str, account_id: i32) -> Result<Vec<ImportedTransaction>, String> {
+    let document = Document::parse(xml).map_err(|e| e.to_string())?;
+    let statements = child(document.root_element(), "BkToCstmrStmt")
+        .ok_or_else(|| String::from("Not a CAMT.053 statement."))?;
+    let mut transactions = Vec::new();
+    let mut digests = Digests::default();
+    let entries = children(statements, "Stmt").flat_map(|statement| children(statement, "Ntry"));
+    for entry in entries {
+        // Pending entries may still change, they are imported once booked
+        let status = path(entry, &["Sts", "Cd"]).or_else(|| child(entry, "Sts"));
+        if status.and_then(|status| status.text()).map(str::trim) != Some("BOOK") {
+            continue;
+        }
+        let line = document.text_pos_at(entry.range().start).row;
+        let credit = text(entry, &["CdtDbtInd"]).as_deref() == Some("CRDT");
+        let amount = text(entry, &["Amt"])
+            .and_then(|amount| amount.parse::<f32>().ok())
+            .map(|amount| if credit { amount } else { -amount })
+            .ok_or_else(|| format!("Invalid amount in the entry of line {}.", line))?;
+        let date = text(entry, &["BookgDt", "Dt"])
+            .or_else(|| text(entry, &["BookgDt", "DtTm"]))
+            .or_else(|| text(entry, &["ValDt", "Dt"]))
+            .and_then(|date| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok())
+            .map(|date| date.and_hms(0, 0, 0))
+            .ok_or_else(|| format!("Invalid date in the entry of line {}.", line))?;
+        let details = path(entry, &["NtryDtls", "TxDtls"]);
+        // The other party, who paid a credit or was paid a debit
+        let party = details
+            .and_then(|details| {
+                path(
+                    details,
+                    &["RltdPties", if credit { "Dbtr" } else { "Cdtr" }],
+                )
+            })
+            .and_then(|party| {
+                party
+                    .descendants()
+                    .find(|node| node.tag_name().name() == "Nm")
+            })
+            .and_then(|name| name.text())
+            .map(|name| name.trim().to_string());
+        let memo = details
+            .and_then(|details| child(details, "RmtInf"))
+            .map(|remittance| {
+                children(remittance, "Ustrd")
+                    .filter_map(|line| line.text())
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .or_else(|| text(entry, &["AddtlNtryInf"]))
+            .filter(|memo| !memo.is_empty());
+        let name = party
+            .filter(|party| !party.is_empty())
+            .or_else(|| memo.clone())
+            .unwrap_or_else(|| String::from(DEFAULT_NAME));
+        let reference = text(entry, &["AcctSvcrRef"])
+            .or_else(|| details.and_then(|details| text(details, &["Refs", "AcctSvcrRef"])))
+            .or_else(|| details.and_then(|details| text(details, &["Refs", "EndToEndId"])))
+            .filter(|reference| reference != "NOTPROVIDED");
+        let import_id = match reference {
+            Some(reference) => reference,
+            None => digests.next(&[
+                &date.to_string(),
+                &format!("{:.2}", amount),
+                &name,
+                memo.as_deref().unwrap_or_default(),
+            ]),
+        };
+        transactions.push(ImportedTransaction {
+            form: TransactionForm::imported(name, amount, date, account_id, memo),
+            import_id,
+        });
+    }
+    Ok(transactions)
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.tag_name().name() == name)
+}
+
+// First child named `name`, whatever its namespace as it varies between versions
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+fn path<'a, 'input>(node: Node<'a, 'input>, names: &[&'static str]) -> Option<Node<'a, 'input>> {
+    names.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn text(node: Node, names: &[&'static str]) -> Option<String> {
+    path(node, names)
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+}
+
+// Identifies transactions without a reference by their content, identical
+// transactions of a file are told apart by their rank
+#[derive(Default)]
+struct Digests(HashMap<String, usize>);
+
+impl Digests {
+    fn next(&mut self, fields: &[&str]) -> String {
+        let content = fields.join("\u{1f}");
+        let rank = self.0.entry(content.clone()).or_default();
+        *rank += 1;
+        Sha256::digest(format!("{}\u{1f}{}", content, rank).as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
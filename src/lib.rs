@@ -1,6 +1,8 @@
 mod schema;
 pub mod models;
 pub mod api;
+mod backup;
+mod import;
 
 #[macro_use]
 extern crate diesel;
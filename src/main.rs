@@ -1,5 +1,7 @@
 use std::env;
 
+use diesel::connection::SimpleConnection;
+use diesel_migrations::RunMigrationsError;
 use dotenvy::dotenv;
 use rocket::{
     fairing::AdHoc,
@@ -12,7 +14,11 @@ use rocket::{
 #[macro_use]
 extern crate diesel_migrations;
 
-use oba_api::api::{account, bucket, fill, transaction};
+use oba_api::api::{
+    account, audit, auth, backup, bucket, budget, bulk, card, exchange_rate, export, fill,
+    forecast, import, journal, loan, reconciliation, recurring, reports, summary, tag, transaction,
+    trash,
+};
 use oba_api::DbConnection;
 
 async fn run_migrations(rocket: Rocket<Build>) -> Rocket<Build> {
@@ -20,9 +26,15 @@ async fn run_migrations(rocket: Rocket<Build>) -> Rocket<Build> {
     let db = DbConnection::get_one(&rocket)
         .await
         .expect("database connection");
-    db.run(|conn| embedded_migrations::run(conn))
-        .await
-        .expect("diesel migrations");
+    // Migrations rebuilding tables need foreign keys off, which can't be changed
+    // inside the transaction of a migration
+    db.run(|conn| -> Result<(), RunMigrationsError> {
+        conn.batch_execute("PRAGMA foreign_keys = OFF")?;
+        embedded_migrations::run(conn)?;
+        Ok(conn.batch_execute("PRAGMA foreign_keys = ON")?)
+    })
+    .await
+    .expect("diesel migrations");
     rocket
 }
 
@@ -39,10 +51,28 @@ async fn main() -> Result<(), rocket::Error> {
     let _rocket = rocket::custom(figment)
         .attach(DbConnection::fairing())
         .attach(AdHoc::on_ignite("Diesel Migrations", run_migrations))
+        .attach(auth::stage())
+        .attach(backup::stage())
+        .attach(budget::stage())
+        .attach(exchange_rate::stage())
         .attach(account::stage())
         .attach(transaction::stage())
+        .attach(bulk::stage())
         .attach(bucket::stage())
         .attach(fill::stage())
+        .attach(tag::stage())
+        .attach(reconciliation::stage())
+        .attach(import::stage())
+        .attach(summary::stage())
+        .attach(reports::stage())
+        .attach(forecast::stage())
+        .attach(export::stage())
+        .attach(journal::stage())
+        .attach(loan::stage())
+        .attach(recurring::stage())
+        .attach(card::stage())
+        .attach(audit::stage())
+        .attach(trash::stage())
         .launch()
         .await?;
     Ok(())
@@ -1,35 +1,316 @@
-use chrono::NaiveDateTime;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Write;
+
+use chrono::{Local, NaiveDateTime};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
+use diesel::dsl::Eq;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+use diesel::ExpressionMethods;
 use rocket::serde::{Deserialize, Serialize};
+use rocket::FromFormField;
+
+use super::schema::{
+    accounts, audit_log, buckets, budget_members, budgets, exchange_rates, fills, loans,
+    recurring_transactions, tags, tokens, transaction_tags, transactions, users,
+};
+
+// Stores a C-like enum as text, using the same names as its JSON representation
+macro_rules! text_enum {
+    ($name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text),+
+                }
+            }
+        }
 
-use super::schema::{accounts, buckets, fills, transactions};
+        impl ToSql<Text, Sqlite> for $name {
+            fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+                <str as ToSql<Text, Sqlite>>::to_sql(self.as_str(), out)
+            }
+        }
+
+        impl FromSql<Text, Sqlite> for $name {
+            fn from_sql(bytes: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+                match <String as FromSql<Text, Sqlite>>::from_sql(bytes)?.as_str() {
+                    $($text => Ok($name::$variant),)+
+                    value => Err(format!("Unknown {}: {}", stringify!($name), value).into()),
+                }
+            }
+        }
+    };
+}
 
-#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[table_name = "accounts"]
 pub struct Account {
-    id: i32,
+    pub id: i32,
     name: String,
+    account_type: AccountType,
+    on_budget: bool,
+    budget_id: i32,
+    pub version: i32,
+    // Currency of its transactions, converted in the budget calculations
+    pub currency: Currency,
 }
 
-#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
-#[table_name = "accounts"]
 pub struct AccountForm {
     name: String,
+    #[serde(default)]
+    account_type: AccountType,
+    // Tracking accounts count toward net worth but not toward money to assign
+    #[serde(default = "default_on_budget")]
+    on_budget: bool,
+    // The currency of the budget when missing on creation, unchanged on updates
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    // Balance before the first tracked transaction, only read on creation
+    #[serde(default, skip_serializing)]
+    opening_balance: Option<f32>,
+    // Date of the opening balance, today when missing
+    #[serde(default, skip_serializing)]
+    opening_date: Option<NaiveDateTime>,
 }
 
-#[derive(Queryable, Identifiable, Associations, Serialize, Deserialize)]
+type AccountColumns<'a> = (
+    Eq<accounts::name, &'a String>,
+    Eq<accounts::account_type, AccountType>,
+    Eq<accounts::on_budget, bool>,
+    Option<Eq<accounts::currency, &'a Currency>>,
+);
+
+impl AccountForm {
+    // Values of the account row, the opening balance is kept as a transaction
+    pub fn columns(&self) -> AccountColumns<'_> {
+        (
+            accounts::name.eq(&self.name),
+            accounts::account_type.eq(self.account_type),
+            accounts::on_budget.eq(self.on_budget),
+            self.currency
+                .as_ref()
+                .map(|currency| accounts::currency.eq(currency)),
+        )
+    }
+
+    // Off-budget loan account owing `principal` from `date` on
+    pub fn loan(
+        name: String,
+        currency: Option<Currency>,
+        principal: f32,
+        date: NaiveDateTime,
+    ) -> Self {
+        Self {
+            name,
+            account_type: AccountType::Loan,
+            on_budget: false,
+            currency,
+            opening_balance: Some(-principal),
+            opening_date: Some(date),
+        }
+    }
+
+    // Starting entry of the account, none for a zero balance
+    pub fn opening(&self, account_id: i32) -> Option<TransactionForm> {
+        let amount = self.opening_balance.filter(|amount| *amount != 0.0)?;
+        Some(TransactionForm {
+            name: String::from("Opening balance"),
+            amount,
+            date: self
+                .opening_date
+                .unwrap_or_else(|| Local::now().naive_local()),
+            account_id,
+            bucket_id: None,
+            memo: None,
+            status: TransactionStatus::Cleared,
+            transfer_account_id: None,
+        })
+    }
+}
+
+fn default_on_budget() -> bool {
+    true
+}
+
+// ISO 4217 code of a currency, like `EUR`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", try_from = "String", into = "String")]
+#[sql_type = "Text"]
+pub struct Currency(String);
+
+impl Currency {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = String;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) {
+            Ok(Currency(code))
+        } else {
+            Err(format!(
+                "Invalid currency `{}`, expected a code like `EUR`.",
+                code
+            ))
+        }
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.0
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ToSql<Text, Sqlite> for Currency {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+        <str as ToSql<Text, Sqlite>>::to_sql(&self.0, out)
+    }
+}
+
+impl FromSql<Text, Sqlite> for Currency {
+    fn from_sql(bytes: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+        Ok(Currency(<String as FromSql<Text, Sqlite>>::from_sql(
+            bytes,
+        )?))
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum AccountType {
+    #[default]
+    Checking,
+    Savings,
+    Cash,
+    CreditCard,
+    Loan,
+    Investment,
+}
+
+text_enum!(AccountType {
+    Checking => "checking",
+    Savings => "savings",
+    Cash => "cash",
+    CreditCard => "credit_card",
+    Loan => "loan",
+    Investment => "investment",
+});
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[primary_key(account_id)]
+#[table_name = "loans"]
+pub struct Loan {
+    pub account_id: i32,
+    pub principal: f32,
+    // Yearly rate in percent
+    pub interest_rate: f32,
+    // Number of monthly payments
+    pub term: i32,
+    pub start_date: NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "loans"]
+pub struct LoanForm {
+    pub principal: f32,
+    pub interest_rate: f32,
+    pub term: i32,
+    pub start_date: NaiveDateTime,
+}
+
+#[derive(Queryable, Identifiable, Insertable, Associations, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[diesel(belongs_to(Account, foreign_key = account_id))]
+#[diesel(belongs_to(Bucket, foreign_key = bucket_id))]
+#[table_name = "recurring_transactions"]
+pub struct RecurringTransaction {
+    pub id: i32,
+    pub name: String,
+    pub amount: f32,
+    // Date of the next occurrence, the following ones are `frequency` apart
+    pub next_date: NaiveDateTime,
+    pub frequency: Frequency,
+    pub account_id: i32,
+    pub bucket_id: Option<i32>,
+}
+
+#[derive(Insertable, AsChangeset, Associations, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[diesel(belongs_to(Account, foreign_key = account_id))]
+#[diesel(belongs_to(Bucket, foreign_key = bucket_id))]
+#[table_name = "recurring_transactions"]
+// Updates replace every field, `None` clears the bucket
+#[changeset_options(treat_none_as_null = "true")]
+pub struct RecurringForm {
+    name: String,
+    amount: f32,
+    next_date: NaiveDateTime,
+    frequency: Frequency,
+    pub account_id: i32,
+    pub bucket_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum Frequency {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+text_enum!(Frequency {
+    Weekly => "weekly",
+    Monthly => "monthly",
+    Yearly => "yearly",
+});
+
+#[derive(Queryable, Identifiable, Insertable, Associations, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[diesel(belongs_to(Account, foreign_key = account_id))]
 #[diesel(belongs_to(Bucket, foreign_key = bucket_id))]
 #[table_name = "transactions"]
 pub struct Transaction {
-    id: i32,
+    pub id: i32,
     name: String,
     amount: f32,
     date: NaiveDateTime,
     account_id: i32,
     bucket_id: Option<i32>,
+    memo: Option<String>,
+    pub status: TransactionStatus,
+    pub transfer_account_id: Option<i32>,
+    // Set while the transaction is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
+    pub version: i32,
+    // Reference of the bank for imported transactions
+    import_id: Option<String>,
+    // Starting entry of the account, part of its balance but not income
+    opening: bool,
+    // Shared by both legs of a transfer, the id of the outgoing one
+    pub transfer_id: Option<i32>,
 }
 
 #[derive(Insertable, AsChangeset, Associations, Serialize, Deserialize)]
@@ -37,20 +318,120 @@ pub struct Transaction {
 #[diesel(belongs_to(Account, foreign_key = account_id))]
 #[diesel(belongs_to(Bucket, foreign_key = bucket_id))]
 #[table_name = "transactions"]
+// Updates replace every field, `None` clears the bucket, memo or transfer account
+#[changeset_options(treat_none_as_null = "true")]
 pub struct TransactionForm {
     name: String,
     amount: f32,
     date: NaiveDateTime,
-    account_id: i32,
-    bucket_id: Option<i32>,
+    pub account_id: i32,
+    pub bucket_id: Option<i32>,
+    memo: Option<String>,
+    #[serde(default)]
+    pub status: TransactionStatus,
+    // Other account of a transfer, transfers are neither income nor spending
+    pub transfer_account_id: Option<i32>,
 }
 
-#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+impl TransactionForm {
+    // Transaction read from a bank statement, already cleared by the bank
+    pub fn imported(
+        name: String,
+        amount: f32,
+        date: NaiveDateTime,
+        account_id: i32,
+        memo: Option<String>,
+    ) -> Self {
+        Self {
+            name,
+            amount,
+            date,
+            account_id,
+            bucket_id: None,
+            memo,
+            status: TransactionStatus::Cleared,
+            transfer_account_id: None,
+        }
+    }
+
+    // Other leg of a transfer edited through `before`, keeping its bucket,
+    // status and the rate between the amounts of both legs
+    pub fn other_leg(&self, before: &Transaction, other: &Transaction) -> Self {
+        let rate = if before.amount == 0.0 {
+            1.0
+        } else {
+            -other.amount / before.amount
+        };
+        Self {
+            name: self.name.clone(),
+            amount: -self.amount * rate,
+            date: self.date,
+            account_id: self.transfer_account_id.unwrap_or(other.account_id),
+            bucket_id: other.bucket_id,
+            memo: self.memo.clone(),
+            status: other.status,
+            transfer_account_id: Some(self.account_id),
+        }
+    }
+
+    // Interest part of a loan payment, spent from the paying account
+    pub fn interest(
+        name: String,
+        amount: f32,
+        date: NaiveDateTime,
+        account_id: i32,
+        bucket_id: Option<i32>,
+    ) -> Self {
+        Self {
+            name,
+            amount,
+            date,
+            account_id,
+            bucket_id,
+            memo: Some(String::from("Interest")),
+            status: TransactionStatus::default(),
+            transfer_account_id: None,
+        }
+    }
+}
+
+/// Where a transaction stands against the bank statement
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    FromFormField,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum TransactionStatus {
+    #[default]
+    Uncleared,
+    Cleared,
+    Reconciled,
+}
+
+text_enum!(TransactionStatus {
+    Uncleared => "uncleared",
+    Cleared => "cleared",
+    Reconciled => "reconciled",
+});
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[table_name = "buckets"]
 pub struct Bucket {
-    id: i32,
+    pub id: i32,
     name: String,
+    budget_id: i32,
+    pub version: i32,
 }
 
 #[derive(Insertable, AsChangeset, Serialize, Deserialize)]
@@ -60,15 +441,18 @@ pub struct BucketForm {
     name: String,
 }
 
-#[derive(Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[derive(Queryable, Identifiable, Insertable, Associations, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 #[diesel(belongs_to(Bucket, foreign_key = bucket_id))]
 #[table_name = "fills"]
 pub struct Fill {
-    id: i32,
+    pub id: i32,
     amount: f32,
     date: NaiveDateTime,
     bucket_id: i32,
+    // Set while the fill is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
+    pub version: i32,
 }
 
 #[derive(Insertable, AsChangeset, Associations, Serialize, Deserialize)]
@@ -78,5 +462,234 @@ pub struct Fill {
 pub struct FillForm {
     amount: f32,
     date: NaiveDateTime,
-    bucket_id: i32,
+    pub bucket_id: i32,
+}
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "tags"]
+pub struct Tag {
+    id: i32,
+    name: String,
+    budget_id: i32,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "tags"]
+pub struct TagForm {
+    name: String,
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "transaction_tags"]
+pub struct TransactionTag {
+    pub transaction_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Queryable, Identifiable, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "users"]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    // Administrators may wipe and reset budgets
+    pub admin: bool,
+}
+
+// User with their password hash, only read to back up the instance
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "users"]
+pub struct UserRow {
+    id: i32,
+    username: String,
+    password_hash: String,
+    admin: bool,
+}
+
+#[derive(Insertable)]
+#[table_name = "users"]
+pub struct NewUser {
+    pub username: String,
+    pub password_hash: String,
+    pub admin: bool,
+}
+
+#[derive(Insertable)]
+#[table_name = "tokens"]
+pub struct NewToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "budgets"]
+pub struct Budget {
+    id: i32,
+    name: String,
+    // Currency the amounts of every account are converted to
+    pub currency: Currency,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "budgets"]
+pub struct BudgetForm {
+    name: String,
+    // US dollars when missing on creation, unchanged on updates
+    #[serde(default)]
+    pub currency: Option<Currency>,
+}
+
+impl Budget {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl BudgetForm {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            currency: None,
+        }
+    }
+}
+
+#[derive(Queryable, Identifiable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "exchange_rates"]
+pub struct ExchangeRate {
+    pub id: i32,
+    pub currency: Currency,
+    pub date: NaiveDateTime,
+    // Value of one unit of the currency in the currency of the budget
+    pub rate: f32,
+    budget_id: i32,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "exchange_rates"]
+pub struct ExchangeRateForm {
+    pub currency: Currency,
+    pub date: NaiveDateTime,
+    pub rate: f32,
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "budget_members"]
+pub struct BudgetMember {
+    pub budget_id: i32,
+    pub user_id: i32,
+    pub role: Role,
+}
+
+// What a member may do in a budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum Role {
+    // Manages the budget and its members
+    Owner,
+    // Changes accounts, buckets, transactions and fills
+    Editor,
+    // Only reads
+    Viewer,
+}
+
+text_enum!(Role {
+    Owner => "owner",
+    Editor => "editor",
+    Viewer => "viewer",
+});
+
+// Audit entry as stored, only read to back up the instance
+#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+#[table_name = "audit_log"]
+pub struct AuditRow {
+    id: i32,
+    budget_id: i32,
+    user_id: i32,
+    entity: AuditEntity,
+    entity_id: i32,
+    action: AuditAction,
+    before: Option<String>,
+    after: Option<String>,
+    created_at: NaiveDateTime,
 }
+
+#[derive(Insertable)]
+#[table_name = "audit_log"]
+pub struct NewAuditEntry {
+    pub budget_id: i32,
+    pub user_id: i32,
+    pub entity: AuditEntity,
+    pub entity_id: i32,
+    pub action: AuditAction,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// Kinds of rows whose changes are audited
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    FromFormField,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum AuditEntity {
+    Account,
+    Bucket,
+    Transaction,
+    Fill,
+}
+
+text_enum!(AuditEntity {
+    Account => "account",
+    Bucket => "bucket",
+    Transaction => "transaction",
+    Fill => "fill",
+});
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    AsExpression,
+    FromSqlRow,
+    FromFormField,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+text_enum!(AuditAction {
+    Create => "create",
+    Update => "update",
+    Delete => "delete",
+});
@@ -2,6 +2,25 @@ table! {
     accounts (id) {
         id -> Integer,
         name -> Text,
+        account_type -> Text,
+        on_budget -> Bool,
+        budget_id -> Integer,
+        version -> Integer,
+        currency -> Text,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Integer,
+        budget_id -> Integer,
+        user_id -> Integer,
+        entity -> Text,
+        entity_id -> Integer,
+        action -> Text,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -9,6 +28,34 @@ table! {
     buckets (id) {
         id -> Integer,
         name -> Text,
+        budget_id -> Integer,
+        version -> Integer,
+    }
+}
+
+table! {
+    budget_members (budget_id, user_id) {
+        budget_id -> Integer,
+        user_id -> Integer,
+        role -> Text,
+    }
+}
+
+table! {
+    budgets (id) {
+        id -> Integer,
+        name -> Text,
+        currency -> Text,
+    }
+}
+
+table! {
+    exchange_rates (id) {
+        id -> Integer,
+        currency -> Text,
+        date -> Timestamp,
+        rate -> Float,
+        budget_id -> Integer,
     }
 }
 
@@ -18,6 +65,54 @@ table! {
         amount -> Float,
         date -> Timestamp,
         bucket_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
+        version -> Integer,
+    }
+}
+
+table! {
+    loans (account_id) {
+        account_id -> Integer,
+        principal -> Float,
+        interest_rate -> Float,
+        term -> Integer,
+        start_date -> Timestamp,
+    }
+}
+
+table! {
+    recurring_transactions (id) {
+        id -> Integer,
+        name -> Text,
+        amount -> Float,
+        next_date -> Timestamp,
+        frequency -> Text,
+        account_id -> Integer,
+        bucket_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+        budget_id -> Integer,
+    }
+}
+
+table! {
+    tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        token_hash -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    transaction_tags (transaction_id, tag_id) {
+        transaction_id -> Integer,
+        tag_id -> Integer,
     }
 }
 
@@ -29,11 +124,57 @@ table! {
         date -> Timestamp,
         account_id -> Integer,
         bucket_id -> Nullable<Integer>,
+        memo -> Nullable<Text>,
+        status -> Text,
+        transfer_account_id -> Nullable<Integer>,
+        deleted_at -> Nullable<Timestamp>,
+        version -> Integer,
+        import_id -> Nullable<Text>,
+        opening -> Bool,
+        transfer_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    users (id) {
+        id -> Integer,
+        username -> Text,
+        password_hash -> Text,
+        admin -> Bool,
     }
 }
 
+joinable!(accounts -> budgets (budget_id));
+joinable!(audit_log -> budgets (budget_id));
+joinable!(audit_log -> users (user_id));
+joinable!(buckets -> budgets (budget_id));
+joinable!(budget_members -> budgets (budget_id));
+joinable!(budget_members -> users (user_id));
+joinable!(exchange_rates -> budgets (budget_id));
 joinable!(fills -> buckets (bucket_id));
+joinable!(loans -> accounts (account_id));
+joinable!(recurring_transactions -> accounts (account_id));
+joinable!(recurring_transactions -> buckets (bucket_id));
+joinable!(tags -> budgets (budget_id));
+joinable!(tokens -> users (user_id));
+joinable!(transaction_tags -> tags (tag_id));
+joinable!(transaction_tags -> transactions (transaction_id));
 joinable!(transactions -> accounts (account_id));
 joinable!(transactions -> buckets (bucket_id));
 
-allow_tables_to_appear_in_same_query!(accounts, buckets, fills, transactions,);
+allow_tables_to_appear_in_same_query!(
+    accounts,
+    audit_log,
+    buckets,
+    budget_members,
+    budgets,
+    exchange_rates,
+    fills,
+    loans,
+    recurring_transactions,
+    tags,
+    tokens,
+    transaction_tags,
+    transactions,
+    users,
+);
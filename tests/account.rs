@@ -1,10 +1,13 @@
 mod common;
 
+use chrono::NaiveDateTime;
 use rocket::http::Status;
+use rocket::serde::json::{json, Value};
 
 use common::Setup;
 use common::ACCOUNT_NUMBER;
-use common::Account;
+use common::if_match;
+use common::{Account, Balance, Transaction, URL_TRANSACTION};
 
 const URL: &str = "/account";
 
@@ -123,6 +126,7 @@ fn test_account_update() {
     let new_account = Account::new(String::from("new_name"));
     let response_update = client
         .put(format!("{}/{}", URL, account_id))
+        .header(if_match(1))
         .json(&new_account)
         .dispatch();
     assert_eq!(response_update.status(), Status::Ok);
@@ -135,6 +139,24 @@ fn test_account_update() {
     assert_eq!(response_read.into_json::<Account>(), Some(new_account));
 }
 
+#[test]
+fn test_account_patch() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Move the account off budget without resending its name
+    let response = client
+        .patch(format!("{}/{}", URL, account_id))
+        .header(if_match(1))
+        .json(&json!({ "on_budget": false }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let account = response.into_json::<Account>().unwrap();
+    assert!(!account.on_budget);
+    assert!(account.name.starts_with("account_"));
+}
+
 #[test]
 fn test_account_destroy() {
     let client = &Setup::new().client;
@@ -144,3 +166,161 @@ fn test_account_destroy() {
         Some(vec![])
     );
 }
+
+#[test]
+fn test_account_create_with_type() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Create an off-budget investment account
+    let account_form = Account::new(String::from("account_name"))
+        .with_type("investment")
+        .off_budget();
+    let response = client.post(URL).json(&account_form).dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(response.into_json::<Account>(), Some(account_form));
+}
+
+#[test]
+fn test_account_create_invalid_type() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Try creating an account with an unknown type
+    let account_form = Account::new(String::from("account_name")).with_type("piggy_bank");
+    let response = client.post(URL).json(&account_form).dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn test_account_balance() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Create transactions
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for amount in [1000.0, -250.0, -50.0] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("transaction_name"),
+                amount,
+                date,
+                account_id,
+                None,
+            ))
+            .dispatch();
+    }
+    // Read balance
+    let response = client
+        .get(format!("{}/{}/balance", URL, account_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let balance = response.into_json::<Balance>().unwrap();
+    assert_eq!(balance.account_id, account_id);
+    assert_eq!(balance.balance, 700.0);
+}
+
+#[test]
+fn test_account_create_with_opening_balance() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    // Create an account holding money already
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let account_form = Account::new(String::from("account_name")).with_opening(1200.0, date);
+    let response = client.post(URL).json(&account_form).dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let account_id = response.into_json::<Account>().unwrap().id.unwrap();
+    // The opening balance is the balance of the account
+    let balance = client
+        .get(format!("{}/{}/balance", URL, account_id))
+        .dispatch()
+        .into_json::<Balance>()
+        .unwrap();
+    assert_eq!(balance.balance, 1200.0);
+    // It is stored as a starting entry on the opening date
+    let transactions = client
+        .get(format!("{}/{}/transactions", URL, account_id))
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0]["amount"], 1200.0);
+    assert_eq!(transactions[0]["date"], "2022-07-01T00:00:00");
+    assert_eq!(transactions[0]["opening"], true);
+}
+
+#[test]
+fn test_account_balance_not_found() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Try reading a balance
+    let response = client.get(format!("{}/0/balance", URL)).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_account_currency_change() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // The currency may change while the account is empty
+    let response = client
+        .patch(format!("{}/{}", URL, account_id))
+        .header(if_match(1))
+        .json(&json!({ "currency": "EUR" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // Not once amounts are stored in it
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("transaction_name"),
+            10.0,
+            date,
+            account_id,
+            None,
+        ))
+        .dispatch();
+    let response = client
+        .patch(format!("{}/{}", URL, account_id))
+        .header(if_match(2))
+        .json(&json!({ "currency": "USD" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let account = client
+        .get(format!("{}/{}", URL, account_id))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap();
+    assert_eq!(account.currency.as_deref(), Some("EUR"));
+}
+
+#[test]
+fn test_account_update_errors() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let other_id = setup.create_account();
+    let other = client
+        .get(format!("{}/{}", URL, other_id))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap();
+    // Missing accounts aren't found, names taken are a conflict
+    let response = client
+        .patch(format!("{}/{}", URL, 0))
+        .header(if_match(1))
+        .json(&json!({ "name": "missing" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client
+        .patch(format!("{}/{}", URL, account_id))
+        .header(if_match(1))
+        .json(&json!({ "name": other.name }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+
+use common::{if_match, Account, AuditEntry, Setup, Transaction, TEST_USERNAME};
+use common::{URL_ACCOUNT, URL_AUDIT, URL_TRANSACTION};
+
+#[test]
+fn test_audit_transaction_history() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let transaction = client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("before"),
+            -10.0,
+            date,
+            account_id,
+            None,
+        ))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    let id = transaction.id.unwrap();
+    client
+        .put(format!("{}/{}", URL_TRANSACTION, id))
+        .header(if_match(1))
+        .json(&transaction.with_name(String::from("after")))
+        .dispatch();
+    client
+        .delete(format!("{}/{}", URL_TRANSACTION, id))
+        .dispatch();
+    // Every change is kept, even once the transaction is deleted
+    let response = client
+        .get(format!("{}/{}/history", URL_TRANSACTION, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let history = response.into_json::<Vec<AuditEntry>>().unwrap();
+    let actions: Vec<&str> = history.iter().map(|entry| entry.action.as_str()).collect();
+    assert_eq!(actions, ["create", "update", "delete"]);
+    assert!(history[0].before.is_none());
+    assert_eq!(history[1].before.as_ref().unwrap()["name"], "before");
+    assert_eq!(history[1].after.as_ref().unwrap()["name"], "after");
+    assert!(history[2].after.is_none());
+    assert!(history.iter().all(|entry| entry.username == TEST_USERNAME));
+}
+
+#[test]
+fn test_audit_filter() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    client
+        .put(format!("{}/{}", URL_ACCOUNT, account_id))
+        .header(if_match(1))
+        .json(&Account::new(format!("renamed_{}", account_id)))
+        .dispatch();
+    // Filter the audit log by entity and action
+    let response = client
+        .get(format!(
+            "{}?entity=account&entity_id={}&action=update",
+            URL_AUDIT, account_id
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let entries = response.into_json::<Vec<AuditEntry>>().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].entity, "account");
+    assert_eq!(
+        entries[0].after.as_ref().unwrap()["name"],
+        format!("renamed_{}", account_id)
+    );
+    // History of the account holds its creation too
+    let history = client
+        .get(format!("{}/{}/history", URL_ACCOUNT, account_id))
+        .dispatch()
+        .into_json::<Vec<AuditEntry>>()
+        .unwrap();
+    assert_eq!(history.len(), 2);
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use rocket::http::{Header, Status};
+
+use common::{Credentials, Setup, Token, User, TEST_USERNAME, URL_ACCOUNT, URL_AUTH, URL_LOGIN};
+use common::{URL_BUCKET, URL_FILL, URL_REGISTER, URL_SUMMARY, URL_TAG, URL_TRANSACTION};
+
+#[test]
+fn test_auth_required() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client.anonymous;
+    // Try every resource without a token
+    for url in [URL_ACCOUNT, URL_BUCKET, URL_FILL, URL_TAG, URL_TRANSACTION] {
+        assert_eq!(client.get(url).dispatch().status(), Status::Unauthorized);
+        assert_eq!(client.delete(url).dispatch().status(), Status::Unauthorized);
+    }
+    assert_eq!(
+        client.get(URL_SUMMARY).dispatch().status(),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn test_auth_invalid_token() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client.anonymous;
+    // Try with an unknown token
+    let response = client
+        .get(URL_ACCOUNT)
+        .header(Header::new("Authorization", "Bearer not_a_token"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn test_auth_me() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Read the signed in user
+    let response = client.get(format!("{}/me", URL_AUTH)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let user = response.into_json::<User>().unwrap();
+    assert_eq!(user.username, TEST_USERNAME);
+    // The first user administrates the instance
+    assert!(user.admin);
+}
+
+#[test]
+fn test_auth_destroy_requires_admin() {
+    // Setup test
+    let setup = Setup::new();
+    let (_, authorization) = setup.create_user();
+    // Only administrators wipe every row of a budget
+    for url in [URL_ACCOUNT, URL_BUCKET, URL_FILL, URL_TAG, URL_TRANSACTION] {
+        let response = setup
+            .client
+            .anonymous
+            .delete(url)
+            .header(authorization.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+}
+
+#[test]
+fn test_auth_login_wrong_password() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client.anonymous;
+    // Try signing in with a wrong password
+    let response = client
+        .post(URL_LOGIN)
+        .json(&Credentials::new(TEST_USERNAME, "wrong_password"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn test_auth_register() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let username = format!("user_{}", chrono::Local::now().to_rfc3339());
+    let credentials = Credentials::new(&username, "password");
+    // Registering requires an administrator once a user exists
+    let response = client
+        .anonymous
+        .post(URL_REGISTER)
+        .json(&credentials)
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let response = client.post(URL_REGISTER).json(&credentials).dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(response.into_json::<User>().unwrap().username, username);
+    // Usernames are unique
+    let response = client.post(URL_REGISTER).json(&credentials).dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Other users can't register anyone
+    let (_, authorization) = setup.create_user();
+    let response = client
+        .anonymous
+        .post(URL_REGISTER)
+        .header(authorization)
+        .json(&Credentials::new(&format!("{}_2", username), "password"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    // The new user can sign in
+    let response = client
+        .anonymous
+        .post(URL_LOGIN)
+        .json(&credentials)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_auth_logout() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client.anonymous;
+    // Sign in a second time and revoke that token
+    let token = client
+        .post(URL_LOGIN)
+        .json(&Credentials::new(TEST_USERNAME, common::TEST_PASSWORD))
+        .dispatch()
+        .into_json::<Token>()
+        .unwrap()
+        .token;
+    let authorization = Header::new("Authorization", format!("Bearer {}", token));
+    let response = client
+        .delete(format!("{}/token", URL_AUTH))
+        .header(authorization.clone())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // The revoked token is refused, the other one still works
+    let response = client.get(URL_ACCOUNT).header(authorization).dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let response = setup.client.get(URL_ACCOUNT).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+#[macro_use]
+extern crate diesel_migrations;
+
+use std::env;
+use std::fs;
+
+use chrono::Local;
+use diesel::{Connection, SqliteConnection};
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+use rocket::serde::json::Value;
+
+use common::{Account, Credentials, Setup, Token, TEST_PASSWORD, TEST_USERNAME};
+use common::{URL_ACCOUNT, URL_LOGIN};
+
+embed_migrations!("./migrations");
+
+const URL_BACKUP: &str = "/backup";
+const URL_RESTORE: &str = "/restore";
+
+#[test]
+fn test_backup() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    setup.create_account();
+    let response = client.get(URL_BACKUP).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let backup = response.into_json::<Value>().unwrap();
+    // The schema version is the latest migration
+    let latest = fs::read_dir("migrations")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .max()
+        .unwrap();
+    assert!(latest.starts_with(backup["schema_version"].as_str().unwrap()));
+    assert!(backup["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|user| user["username"] == TEST_USERNAME));
+    assert!(!backup["accounts"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_backup_requires_admin() {
+    // Setup test
+    let setup = Setup::new();
+    let (_, authorization) = setup.create_user();
+    let response = setup
+        .client
+        .anonymous
+        .get(URL_BACKUP)
+        .header(authorization)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn test_restore_requires_empty_instance() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let backup = client
+        .get(URL_BACKUP)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    // Users already exist
+    let response = client.anonymous.post(URL_RESTORE).json(&backup).dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn test_restore_schema_version() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let mut backup = client
+        .get(URL_BACKUP)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    // Backups of another schema are refused
+    backup["schema_version"] = Value::from("2022-08-27-082352");
+    let response = client.anonymous.post(URL_RESTORE).json(&backup).dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn test_restore() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let name = format!("restored_{}", Local::now().to_rfc3339());
+    client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(name.clone()))
+        .dispatch();
+    let backup = client
+        .get(URL_BACKUP)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    // Create an empty instance
+    let path = env::temp_dir().join(format!("restore_{}.sqlite", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let database_url = path.to_str().unwrap();
+    embedded_migrations::run(&SqliteConnection::establish(database_url).unwrap()).unwrap();
+    let empty = Client::tracked(common::rocket(database_url)).unwrap();
+    assert_eq!(
+        empty.post(URL_RESTORE).json(&backup).dispatch().status(),
+        Status::Ok
+    );
+    // Users sign in with their former password and find their data
+    let token = empty
+        .post(URL_LOGIN)
+        .json(&Credentials::new(TEST_USERNAME, TEST_PASSWORD))
+        .dispatch()
+        .into_json::<Token>()
+        .unwrap()
+        .token;
+    let accounts = empty
+        .get(URL_ACCOUNT)
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch()
+        .into_json::<Vec<Account>>()
+        .unwrap();
+    assert!(accounts.iter().any(|account| account.name == name));
+    // A second restore finds the instance populated
+    assert_eq!(
+        empty.post(URL_RESTORE).json(&backup).dispatch().status(),
+        Status::Conflict
+    );
+    drop(empty);
+    fs::remove_file(&path).unwrap();
+}
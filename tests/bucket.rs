@@ -2,7 +2,7 @@ mod common;
 
 use rocket::http::Status;
 
-use common::{Bucket, Setup, BUCKET_NUMBER, URL_BUCKET};
+use common::{if_match, Bucket, Setup, BUCKET_NUMBER, URL_BUCKET};
 
 #[test]
 fn test_bucket_create() {
@@ -122,6 +122,7 @@ fn test_bucket_update() {
     let new_bucket = Bucket::new(String::from("new_name"));
     let response_update = client
         .put(format!("{}/{}", URL_BUCKET, bucket_id))
+        .header(if_match(1))
         .json(&new_bucket)
         .dispatch();
     assert_eq!(response_update.status(), Status::Ok);
@@ -0,0 +1,331 @@
+mod common;
+
+use chrono::Local;
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use common::URL_TRANSACTION;
+use common::{budget_header, Account, AuthClient, Budget, MemberRole, Membership, Reset};
+use common::{ResetForm, RoleForm, Setup, TEST_USERNAME, URL_ACCOUNT, URL_BUDGET};
+
+fn create_budget(client: &AuthClient) -> i32 {
+    client
+        .post(URL_BUDGET)
+        .json(&Budget::new(format!(
+            "budget_{}",
+            Local::now().to_rfc3339()
+        )))
+        .dispatch()
+        .into_json::<Budget>()
+        .unwrap()
+        .id
+        .unwrap()
+}
+
+#[test]
+fn test_budget_list() {
+    // Setup test
+    let client = &Setup::new().client;
+    let budget_id = create_budget(client);
+    // The test user owns their budgets
+    let budgets = client
+        .get(URL_BUDGET)
+        .dispatch()
+        .into_json::<Vec<Membership>>()
+        .unwrap();
+    let membership = budgets
+        .iter()
+        .find(|membership| membership.budget_id == budget_id)
+        .unwrap();
+    assert_eq!(membership.role, "owner");
+    // Cleanup
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_isolation() {
+    // Setup test
+    let client = &Setup::new().client;
+    let budget_id = create_budget(client);
+    // Create an account in the new budget
+    let response = client
+        .post(URL_ACCOUNT)
+        .header(budget_header(budget_id))
+        .json(&Account::new(String::from("isolated")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let account_id = response.into_json::<Account>().unwrap().id.unwrap();
+    // The default budget does not see it
+    let accounts = client
+        .get(URL_ACCOUNT)
+        .dispatch()
+        .into_json::<Vec<Account>>()
+        .unwrap();
+    assert!(accounts.is_empty());
+    let response = client
+        .get(format!("{}/{}", URL_ACCOUNT, account_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    // Transactions cannot use an account of another budget
+    let response = client
+        .post(URL_TRANSACTION)
+        .json(&common::Transaction::new(
+            String::from("name"),
+            1.0,
+            Local::now().naive_local(),
+            account_id,
+            None,
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // A budget with accounts cannot be deleted
+    let response = client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Cleanup
+    client
+        .delete(format!("{}/{}", URL_ACCOUNT, account_id))
+        .header(budget_header(budget_id))
+        .dispatch();
+    let response = client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_budget_not_member() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let budget_id = create_budget(client);
+    let (_, authorization) = setup.create_user();
+    // Another user can neither select nor read the budget
+    let response = client
+        .anonymous
+        .get(URL_ACCOUNT)
+        .header(authorization.clone())
+        .header(budget_header(budget_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .anonymous
+        .get(format!("{}/{}", URL_BUDGET, budget_id))
+        .header(authorization)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    // Cleanup
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_viewer() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let budget_id = create_budget(client);
+    let (username, authorization) = setup.create_user();
+    // Share the budget read only
+    let response = client
+        .put(format!("{}/{}/members/{}", URL_BUDGET, budget_id, username))
+        .json(&RoleForm::new("viewer"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let members = client
+        .get(format!("{}/{}/members", URL_BUDGET, budget_id))
+        .dispatch()
+        .into_json::<Vec<MemberRole>>()
+        .unwrap();
+    assert_eq!(members.len(), 2);
+    assert_eq!(members[1].username, username);
+    assert_eq!(members[1].role, "viewer");
+    // The viewer reads but does not write
+    let response = client
+        .anonymous
+        .get(URL_ACCOUNT)
+        .header(authorization.clone())
+        .header(budget_header(budget_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .anonymous
+        .post(URL_ACCOUNT)
+        .header(authorization.clone())
+        .header(budget_header(budget_id))
+        .json(&Account::new(String::from("viewer")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    // Viewers do not manage members
+    let response = client
+        .anonymous
+        .put(format!(
+            "{}/{}/members/{}",
+            URL_BUDGET, budget_id, TEST_USERNAME
+        ))
+        .header(authorization)
+        .json(&RoleForm::new("viewer"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    // Cleanup
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_currency() {
+    // Setup test
+    let client = &Setup::new().client;
+    let response = client
+        .post(URL_BUDGET)
+        .json(&json!({"name": format!("budget_{}", Local::now().to_rfc3339()), "currency": "CHF"}))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let budget_id = response.into_json::<Budget>().unwrap().id.unwrap();
+    // Accounts are in the currency of their budget unless told otherwise
+    let account = client
+        .post(URL_ACCOUNT)
+        .header(budget_header(budget_id))
+        .json(&Account::new(String::from("franken")))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap();
+    assert_eq!(account.currency.as_deref(), Some("CHF"));
+    // Renaming the budget keeps its currency
+    let budget = client
+        .put(format!("{}/{}", URL_BUDGET, budget_id))
+        .json(&Budget::new(String::from("renamed")))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(budget["currency"], "CHF");
+    // Try changing the currency once an account uses another one
+    let other = client
+        .post(URL_ACCOUNT)
+        .header(budget_header(budget_id))
+        .json(&Account::new(String::from("euros")).with_currency("EUR"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap();
+    let response = client
+        .put(format!("{}/{}", URL_BUDGET, budget_id))
+        .json(&json!({"name": "renamed", "currency": "USD"}))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Cleanup
+    for account_id in [account.id.unwrap(), other.id.unwrap()] {
+        client
+            .delete(format!("{}/{}", URL_ACCOUNT, account_id))
+            .header(budget_header(budget_id))
+            .dispatch();
+    }
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_keeps_owner() {
+    // Setup test
+    let client = &Setup::new().client;
+    let budget_id = create_budget(client);
+    // The only owner can neither leave nor step down
+    let response = client
+        .delete(format!(
+            "{}/{}/members/{}",
+            URL_BUDGET, budget_id, TEST_USERNAME
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .put(format!(
+            "{}/{}/members/{}",
+            URL_BUDGET, budget_id, TEST_USERNAME
+        ))
+        .json(&RoleForm::new("editor"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Cleanup
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_reset() {
+    // Setup test
+    let client = &Setup::new().client;
+    let budget_id = create_budget(client);
+    let name = client
+        .get(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch()
+        .into_json::<Budget>()
+        .unwrap()
+        .name;
+    client
+        .post(URL_ACCOUNT)
+        .header(budget_header(budget_id))
+        .json(&Account::new(String::from("reset")))
+        .dispatch();
+    // Refuse a reset without the right confirmation
+    let response = client
+        .post(format!("{}/{}/reset", URL_BUDGET, budget_id))
+        .json(&ResetForm::new("wrong name"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .post(format!("{}/{}/reset", URL_BUDGET, 0))
+        .json(&ResetForm::new(&name))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    // Reset the budget, a backup is written first
+    let response = client
+        .post(format!("{}/{}/reset", URL_BUDGET, budget_id))
+        .json(&ResetForm::new(&name))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let backup = response.into_json::<Reset>().unwrap().backup;
+    let content = std::fs::read_to_string(&backup).unwrap();
+    assert!(content.contains("\"reset\""));
+    let accounts = client
+        .get(URL_ACCOUNT)
+        .header(budget_header(budget_id))
+        .dispatch()
+        .into_json::<Vec<Account>>()
+        .unwrap();
+    assert!(accounts.is_empty());
+    // Cleanup
+    std::fs::remove_file(backup).unwrap();
+    client
+        .delete(format!("{}/{}", URL_BUDGET, budget_id))
+        .dispatch();
+}
+
+#[test]
+fn test_budget_reset_requires_admin() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let (_, authorization) = setup.create_user();
+    // A user cannot reset their own budget without the admin role
+    let budget_id = client
+        .anonymous
+        .get(URL_BUDGET)
+        .header(authorization.clone())
+        .dispatch()
+        .into_json::<Vec<Membership>>()
+        .unwrap()[0]
+        .budget_id;
+    let response = client
+        .anonymous
+        .post(format!("{}/{}/reset", URL_BUDGET, budget_id))
+        .header(authorization)
+        .json(&ResetForm::new(""))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
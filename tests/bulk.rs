@@ -0,0 +1,140 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use common::{AuthClient, Setup, Transaction, TRANSACTION_NUMBER, URL_TRANSACTION};
+
+fn default_transaction(account_id: i32) -> Transaction {
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    Transaction::new(
+        String::from("transaction_name"),
+        -12.5,
+        date,
+        account_id,
+        None,
+    )
+}
+
+fn create_transactions(client: &AuthClient, account_id: i32) -> Vec<i32> {
+    let forms: Vec<Transaction> = (0..TRANSACTION_NUMBER)
+        .map(|_| default_transaction(account_id))
+        .collect();
+    let response = client
+        .post(format!("{}/bulk", URL_TRANSACTION))
+        .json(&forms)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let bulk = response.into_json::<Value>().unwrap();
+    assert_eq!(bulk["committed"], true);
+    bulk["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["transaction"]["id"].as_i64().unwrap() as i32)
+        .collect()
+}
+
+fn list(client: &AuthClient) -> Vec<Transaction> {
+    client
+        .get(URL_TRANSACTION)
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap()
+}
+
+#[test]
+fn test_bulk_create() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Create every transaction in one request
+    let ids = create_transactions(client, account_id);
+    assert_eq!(ids.len(), TRANSACTION_NUMBER);
+    assert_eq!(list(client).len(), TRANSACTION_NUMBER);
+}
+
+#[test]
+fn test_bulk_create_rollback() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // One invalid item cancels the whole batch
+    let forms = [
+        default_transaction(account_id),
+        default_transaction(account_id + 1),
+    ];
+    let response = client
+        .post(format!("{}/bulk", URL_TRANSACTION))
+        .json(&forms)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let bulk = response.into_json::<Value>().unwrap();
+    assert_eq!(bulk["committed"], false);
+    assert!(bulk["results"][0]["error"].is_null());
+    assert!(bulk["results"][1]["error"].is_string());
+    assert!(list(client).is_empty());
+}
+
+#[test]
+fn test_bulk_update() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    let ids = create_transactions(client, account_id);
+    // Recategorize every transaction at once
+    let response = client
+        .patch(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({
+            "ids": ids,
+            "patch": { "bucket_id": bucket_id, "status": "cleared" },
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    for transaction in list(client) {
+        assert_eq!(transaction.bucket_id, Some(bucket_id));
+        assert_eq!(transaction.status, "cleared");
+        assert_eq!(transaction.name, "transaction_name");
+    }
+    // Invalid values are reported per transaction
+    let response = client
+        .patch(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({ "ids": ids, "patch": { "status": "lost" } }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let bulk = response.into_json::<Value>().unwrap();
+    assert_eq!(
+        bulk["results"].as_array().unwrap().len(),
+        TRANSACTION_NUMBER
+    );
+}
+
+#[test]
+fn test_bulk_delete() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let ids = create_transactions(client, account_id);
+    // Unknown transactions cancel the deletion
+    let response = client
+        .delete(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({ "ids": [ids[0], -1] }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(list(client).len(), TRANSACTION_NUMBER);
+    // Delete all but the last transaction
+    let response = client
+        .delete(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({ "ids": ids[..ids.len() - 1] }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let transactions = list(client);
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].id, ids.last().copied());
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+
+use common::{Account, Fill, Reserve, Setup, Summary, Transaction, Transfer};
+use common::{URL_ACCOUNT, URL_CARD, URL_FILL, URL_SUMMARY, URL_TRANSACTION};
+
+fn create_card(setup: &Setup, name: &str) -> i32 {
+    setup
+        .client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from(name)).with_type("credit_card"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap()
+}
+
+fn get_reserve(setup: &Setup, card_id: i32) -> f32 {
+    setup
+        .client
+        .get(format!("{}/{}/reserve", URL_CARD, card_id))
+        .dispatch()
+        .into_json::<Reserve>()
+        .unwrap()
+        .reserved
+}
+
+fn get_to_assign(setup: &Setup) -> f32 {
+    setup
+        .client
+        .get(URL_SUMMARY)
+        .dispatch()
+        .into_json::<Summary>()
+        .unwrap()
+        .to_assign
+}
+
+#[test]
+fn test_card_reserve() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let card_id = create_card(&setup, "card");
+    let bucket_id = setup.create_bucket();
+    // Receive an income and fill a bucket
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("Income"),
+            1000.0,
+            date,
+            checking_id,
+            None,
+        ))
+        .dispatch();
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(300.0, date, bucket_id))
+        .dispatch();
+    assert_eq!(get_to_assign(&setup), 700.0);
+    // Spend from the bucket with the card
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("Groceries"),
+            -100.0,
+            date,
+            card_id,
+            Some(bucket_id),
+        ))
+        .dispatch();
+    assert_eq!(get_reserve(&setup, card_id), 100.0);
+    assert_eq!(get_to_assign(&setup), 700.0);
+    // Pay part of the card from checking
+    let response = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("Card payment"),
+            amount: 60.0,
+            date,
+            from_account_id: checking_id,
+            to_account_id: card_id,
+        })
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(get_reserve(&setup, card_id), 40.0);
+    assert_eq!(get_to_assign(&setup), 700.0);
+    // The payment is not spent from the bucket again
+    let spent: f32 = client
+        .get(format!("/bucket/{}/transactions", bucket_id))
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction.amount)
+        .sum();
+    assert_eq!(spent, -100.0);
+}
+
+#[test]
+fn test_card_reserve_list() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    setup.create_account();
+    let card_1_id = create_card(&setup, "card_1");
+    let card_2_id = create_card(&setup, "card_2");
+    // Only credit cards are listed
+    let response = client.get(format!("{}/reserve", URL_CARD)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let reserves = response.into_json::<Vec<Reserve>>().unwrap();
+    assert_eq!(
+        reserves
+            .iter()
+            .map(|reserve| reserve.account_id)
+            .collect::<Vec<i32>>(),
+        [card_1_id, card_2_id]
+    );
+}
+
+#[test]
+fn test_card_reserve_not_a_card() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Try reading the reserve of a checking account
+    let response = client
+        .get(format!("{}/{}/reserve", URL_CARD, account_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
@@ -1,4 +1,5 @@
 use std::env;
+use std::fmt::Display;
 
 use chrono::{Local, NaiveDateTime};
 use dotenvy::dotenv;
@@ -6,14 +7,83 @@ use rocket::figment::{
     util::map,
     value::{Map, Value},
 };
-use rocket::local::blocking::Client;
-use rocket::serde::{Deserialize, Serialize};
+use rocket::http::{uri::Origin, Header, Status};
+use rocket::local::blocking::{Client, LocalRequest};
+use rocket::serde::{json, Deserialize, Serialize};
+use rocket::{Build, Rocket};
 
-use oba_api::api::{account, bucket, fill, transaction};
+use oba_api::api::{
+    account, audit, auth, backup, bucket, budget, bulk, card, exchange_rate, export, fill,
+    forecast, import, journal, loan, reconciliation, recurring, reports, summary, tag, transaction,
+    trash,
+};
 use oba_api::DbConnection;
 
 pub struct Setup {
-    pub client: Client,
+    pub client: AuthClient,
+}
+
+// Client sending the bearer token of the test user with every request
+pub struct AuthClient {
+    pub anonymous: Client,
+    pub token: String,
+}
+
+impl AuthClient {
+    fn login(client: Client) -> Self {
+        let credentials = Credentials::new(TEST_USERNAME, TEST_PASSWORD);
+        let mut response = client.post(URL_LOGIN).json(&credentials).dispatch();
+        // Register the test user on a fresh database
+        if response.status() == Status::Unauthorized {
+            client.post(URL_REGISTER).json(&credentials).dispatch();
+            response = client.post(URL_LOGIN).json(&credentials).dispatch();
+        }
+        let token = response.into_json::<Token>().unwrap().token;
+        Self {
+            anonymous: client,
+            token,
+        }
+    }
+
+    pub fn authorization(&self) -> Header<'static> {
+        Header::new("Authorization", format!("Bearer {}", self.token))
+    }
+
+    pub fn get<'c, 'u: 'c, U>(&'c self, uri: U) -> LocalRequest<'c>
+    where
+        U: TryInto<Origin<'u>> + Display,
+    {
+        self.anonymous.get(uri).header(self.authorization())
+    }
+
+    pub fn put<'c, 'u: 'c, U>(&'c self, uri: U) -> LocalRequest<'c>
+    where
+        U: TryInto<Origin<'u>> + Display,
+    {
+        self.anonymous.put(uri).header(self.authorization())
+    }
+
+    pub fn post<'c, 'u: 'c, U>(&'c self, uri: U) -> LocalRequest<'c>
+    where
+        U: TryInto<Origin<'u>> + Display,
+    {
+        self.anonymous.post(uri).header(self.authorization())
+    }
+
+    #[allow(dead_code)]
+    pub fn patch<'c, 'u: 'c, U>(&'c self, uri: U) -> LocalRequest<'c>
+    where
+        U: TryInto<Origin<'u>> + Display,
+    {
+        self.anonymous.patch(uri).header(self.authorization())
+    }
+
+    pub fn delete<'c, 'u: 'c, U>(&'c self, uri: U) -> LocalRequest<'c>
+    where
+        U: TryInto<Origin<'u>> + Display,
+    {
+        self.anonymous.delete(uri).header(self.authorization())
+    }
 }
 
 impl Setup {
@@ -21,24 +91,12 @@ impl Setup {
         // Configure database from .env
         dotenv().ok();
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let db: Map<_, Value> = map! {
-            "url" => database_url.into()
-        };
-        let figment = rocket::Config::figment().merge(("databases", map!["sqlite" => db]));
-
-        let client = Client::tracked(
-            rocket::custom(figment)
-                .attach(DbConnection::fairing())
-                .attach(account::stage())
-                .attach(transaction::stage())
-                .attach(bucket::stage())
-                .attach(fill::stage()),
-        )
-        .unwrap();
+        let client = AuthClient::login(Client::tracked(rocket(&database_url)).unwrap());
         client.delete(URL_TRANSACTION).dispatch().status();
         client.delete(URL_FILL).dispatch().status();
         client.delete(URL_BUCKET).dispatch().status();
         client.delete(URL_ACCOUNT).dispatch().status();
+        client.delete(URL_TAG).dispatch().status();
         Self { client }
     }
 
@@ -71,14 +129,82 @@ impl Setup {
             .id
             .unwrap()
     }
+
+    // Registers another user, returns their name and authorization header
+    #[allow(dead_code)]
+    pub fn create_user(&self) -> (String, Header<'static>) {
+        let username = format!("user_{}", Local::now().to_rfc3339());
+        let credentials = Credentials::new(&username, "password");
+        self.client.post(URL_REGISTER).json(&credentials).dispatch();
+        let token = self
+            .client
+            .anonymous
+            .post(URL_LOGIN)
+            .json(&credentials)
+            .dispatch()
+            .into_json::<Token>()
+            .unwrap()
+            .token;
+        (
+            username,
+            Header::new("Authorization", format!("Bearer {}", token)),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn create_tag(&self) -> i32 {
+        self.client
+            .post(URL_TAG)
+            .json(&Tag::new(format!("tag_{}", Local::now().to_rfc3339())))
+            .dispatch()
+            .into_json::<Tag>()
+            .unwrap()
+            .id
+            .unwrap()
+    }
+}
+
+// Server with every stage on the database at `database_url`
+pub fn rocket(database_url: &str) -> Rocket<Build> {
+    let db: Map<_, Value> = map! {
+        "url" => database_url.into()
+    };
+    let figment = rocket::Config::figment().merge(("databases", map!["sqlite" => db]));
+    rocket::custom(figment)
+        .attach(DbConnection::fairing())
+        .attach(auth::stage())
+        .attach(backup::stage())
+        .attach(budget::stage())
+        .attach(exchange_rate::stage())
+        .attach(account::stage())
+        .attach(transaction::stage())
+        .attach(bulk::stage())
+        .attach(bucket::stage())
+        .attach(fill::stage())
+        .attach(tag::stage())
+        .attach(reconciliation::stage())
+        .attach(import::stage())
+        .attach(summary::stage())
+        .attach(reports::stage())
+        .attach(forecast::stage())
+        .attach(export::stage())
+        .attach(journal::stage())
+        .attach(loan::stage())
+        .attach(recurring::stage())
+        .attach(card::stage())
+        .attach(audit::stage())
+        .attach(trash::stage())
 }
 
 impl Drop for Setup {
     fn drop(&mut self) {
         self.client.delete(URL_TRANSACTION).dispatch();
+        self.client.delete(URL_RECURRING).dispatch();
         self.client.delete(URL_FILL).dispatch();
         self.client.delete(URL_BUCKET).dispatch();
         self.client.delete(URL_ACCOUNT).dispatch();
+        self.client.delete(URL_TAG).dispatch();
+        self.client.delete(URL_EXCHANGE_RATE).dispatch();
     }
 }
 
@@ -88,17 +214,60 @@ pub struct Account {
     #[serde(skip_serializing)]
     pub id: Option<i32>,
     pub name: String,
+    pub account_type: String,
+    pub on_budget: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opening_balance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opening_date: Option<NaiveDateTime>,
 }
 
 impl Account {
     pub fn new(name: String) -> Self {
-        Self { id: None, name }
+        Self {
+            id: None,
+            name,
+            account_type: String::from("checking"),
+            on_budget: true,
+            currency: None,
+            opening_balance: None,
+            opening_date: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_type(mut self, account_type: &str) -> Self {
+        self.account_type = String::from(account_type);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn off_budget(mut self) -> Self {
+        self.on_budget = false;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_currency(mut self, currency: &str) -> Self {
+        self.currency = Some(String::from(currency));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_opening(mut self, balance: f32, date: NaiveDateTime) -> Self {
+        self.opening_balance = Some(balance);
+        self.opening_date = Some(date);
+        self
     }
 }
 
 impl PartialEq for Account {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        (self.name == other.name)
+            && (self.account_type == other.account_type)
+            && (self.on_budget == other.on_budget)
     }
 }
 
@@ -112,6 +281,9 @@ pub struct Transaction {
     pub date: NaiveDateTime,
     pub account_id: i32,
     pub bucket_id: Option<i32>,
+    pub memo: Option<String>,
+    pub status: String,
+    pub transfer_account_id: Option<i32>,
 }
 
 impl Transaction {
@@ -130,6 +302,9 @@ impl Transaction {
             date,
             account_id,
             bucket_id,
+            memo: None,
+            status: String::from("uncleared"),
+            transfer_account_id: None,
         }
     }
 
@@ -138,6 +313,18 @@ impl Transaction {
         self.name = name;
         self
     }
+
+    #[allow(dead_code)]
+    pub fn with_memo(mut self, memo: String) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_status(mut self, status: &str) -> Self {
+        self.status = String::from(status);
+        self
+    }
 }
 
 impl PartialEq for Transaction {
@@ -147,6 +334,9 @@ impl PartialEq for Transaction {
             && (self.date == other.date)
             && (self.account_id == other.account_id)
             && (self.bucket_id == other.bucket_id)
+            && (self.memo == other.memo)
+            && (self.status == other.status)
+            && (self.transfer_account_id == other.transfer_account_id)
     }
 }
 
@@ -206,10 +396,259 @@ impl PartialEq for Fill {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Tag {
+    #[serde(skip_serializing)]
+    pub id: Option<i32>,
+    pub name: String,
+}
+
+impl Tag {
+    pub fn new(name: String) -> Self {
+        Self { id: None, name }
+    }
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TagSpending {
+    pub tag_id: i32,
+    pub name: String,
+    pub total: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Statement {
+    pub date: NaiveDateTime,
+    pub ending_balance: f32,
+    pub adjust: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Reconciliation {
+    pub account_id: i32,
+    pub date: NaiveDateTime,
+    pub ending_balance: f32,
+    pub cleared_balance: f32,
+    pub difference: f32,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Balance {
+    pub account_id: i32,
+    pub balance: f32,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Summary {
+    pub net_worth: f32,
+    pub on_budget: f32,
+    pub filled: f32,
+    pub spent: f32,
+    pub to_assign: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Transfer {
+    pub name: String,
+    pub amount: f32,
+    pub date: NaiveDateTime,
+    pub from_account_id: i32,
+    pub to_account_id: i32,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Reserve {
+    pub account_id: i32,
+    pub name: String,
+    pub reserved: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: String::from(username),
+            password: String::from(password),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Token {
+    pub token: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub admin: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Budget {
+    #[serde(skip_serializing)]
+    pub id: Option<i32>,
+    pub name: String,
+}
+
+impl Budget {
+    #[allow(dead_code)]
+    pub fn new(name: String) -> Self {
+        Self { id: None, name }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Membership {
+    pub budget_id: i32,
+    pub name: String,
+    pub role: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MemberRole {
+    pub user_id: i32,
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RoleForm {
+    pub role: String,
+}
+
+impl RoleForm {
+    #[allow(dead_code)]
+    pub fn new(role: &str) -> Self {
+        Self {
+            role: String::from(role),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResetForm {
+    pub confirm: String,
+}
+
+impl ResetForm {
+    #[allow(dead_code)]
+    pub fn new(confirm: &str) -> Self {
+        Self {
+            confirm: String::from(confirm),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Reset {
+    pub budget_id: i32,
+    pub backup: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditEntry {
+    pub id: i32,
+    pub entity: String,
+    pub entity_id: i32,
+    pub action: String,
+    pub before: Option<json::Value>,
+    pub after: Option<json::Value>,
+    pub user_id: i32,
+    pub username: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Trash {
+    pub transactions: Vec<Transaction>,
+    pub fills: Vec<Fill>,
+}
+
+// Selects the budget of a request instead of the first budget of the user
+#[allow(dead_code)]
+pub fn budget_header(budget_id: i32) -> Header<'static> {
+    Header::new("X-Budget-Id", budget_id.to_string())
+}
+
+// Version of a row last read, required to update it
+#[allow(dead_code)]
+pub fn if_match(version: i32) -> Header<'static> {
+    Header::new("If-Match", format!("\"{}\"", version))
+}
+
+pub const TEST_USERNAME: &str = "test_user";
+pub const TEST_PASSWORD: &str = "test_password";
+
 pub const URL_TRANSACTION: &str = "/transaction";
 pub const URL_ACCOUNT: &str = "/account";
 pub const URL_BUCKET: &str = "/bucket";
 pub const URL_FILL: &str = "/fill";
+pub const URL_TAG: &str = "/tag";
+pub const URL_EXCHANGE_RATE: &str = "/exchange-rate";
+pub const URL_REGISTER: &str = "/auth/register";
+pub const URL_LOGIN: &str = "/auth/login";
+#[allow(dead_code)]
+pub const URL_AUTH: &str = "/auth";
+#[allow(dead_code)]
+pub const URL_SUMMARY: &str = "/summary";
+#[allow(dead_code)]
+pub const URL_CARD: &str = "/card";
+#[allow(dead_code)]
+pub const URL_BUDGET: &str = "/budget";
+#[allow(dead_code)]
+pub const URL_AUDIT: &str = "/audit";
+#[allow(dead_code)]
+pub const URL_REPORTS: &str = "/reports";
+#[allow(dead_code)]
+pub const URL_TRASH: &str = "/trash";
+#[allow(dead_code)]
+pub const URL_EXPORT: &str = "/export";
+#[allow(dead_code)]
+pub const URL_LOAN: &str = "/loan";
+pub const URL_RECURRING: &str = "/recurring";
 #[allow(dead_code)]
 pub const TRANSACTION_NUMBER: usize = 3;
 #[allow(dead_code)]
@@ -218,3 +657,5 @@ pub const ACCOUNT_NUMBER: usize = 3;
 pub const BUCKET_NUMBER: usize = 3;
 #[allow(dead_code)]
 pub const FILL_NUMBER: usize = 3;
+#[allow(dead_code)]
+pub const TAG_NUMBER: usize = 3;
@@ -0,0 +1,313 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::{json, Value};
+
+use common::{Account, Setup, Transaction, URL_ACCOUNT, URL_BUCKET, URL_EXCHANGE_RATE};
+use common::{AuthClient, Bucket, Summary, URL_REPORTS, URL_SUMMARY, URL_TRANSACTION};
+
+fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[test]
+fn test_exchange_rate_create() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Create a rate
+    let response = client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "EUR", "date": date("2022-07-01"), "rate": 1.25}))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    // A rate of the same day replaces it
+    client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "EUR", "date": date("2022-07-01"), "rate": 1.5}))
+        .dispatch();
+    let rates = client
+        .get(format!("{}?currency=EUR", URL_EXCHANGE_RATE))
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(rates.len(), 1);
+    assert_eq!(rates[0]["rate"], 1.5);
+}
+
+#[test]
+fn test_exchange_rate_create_invalid() {
+    // Setup test
+    let client = &Setup::new().client;
+    for (currency, rate) in [("USD", 1.0), ("EUR", 0.0), ("eur", 1.0)] {
+        let response = client
+            .post(URL_EXCHANGE_RATE)
+            .json(&json!({"currency": currency, "date": date("2022-07-01"), "rate": rate}))
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+}
+
+#[test]
+fn test_exchange_rate_import() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Import rates of two currencies
+    let response = client
+        .post(format!("{}/import", URL_EXCHANGE_RATE))
+        .header(ContentType::CSV)
+        .body("date,currency,rate\n2022-07-01,EUR,1.25\n2022-07-01, CHF, 1.5\n")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_json::<Vec<Value>>().unwrap().len(), 2);
+    let rates = client
+        .get(URL_EXCHANGE_RATE)
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(rates[0]["currency"], "CHF");
+    assert_eq!(rates[1]["currency"], "EUR");
+    // Nothing is imported from a file with an invalid line
+    let response = client
+        .post(format!("{}/import", URL_EXCHANGE_RATE))
+        .header(ContentType::CSV)
+        .body("date,currency,rate\n2022-08-01,EUR,1.5\n08/01/2022,EUR,1.5\n")
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let rates = client
+        .get(URL_EXCHANGE_RATE)
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(rates.len(), 2);
+}
+
+#[test]
+fn test_exchange_rate_delete() {
+    // Setup test
+    let client = &Setup::new().client;
+    let id = client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "EUR", "date": date("2022-07-01"), "rate": 1.25}))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap()["id"]
+        .clone();
+    // Delete the rate
+    let response = client
+        .delete(format!("{}/{}", URL_EXCHANGE_RATE, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .delete(format!("{}/{}", URL_EXCHANGE_RATE, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_exchange_rate_conversion() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let dollars_id = setup.create_account();
+    let euros_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("euros")).with_currency("EUR"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    let bucket_id = client
+        .post(URL_BUCKET)
+        .json(&Bucket::new(String::from("travel")))
+        .dispatch()
+        .into_json::<Bucket>()
+        .unwrap()
+        .id
+        .unwrap();
+    for (day, rate) in [("2022-07-01", 1.25), ("2022-08-01", 1.5)] {
+        client
+            .post(URL_EXCHANGE_RATE)
+            .json(&json!({"currency": "EUR", "date": date(day), "rate": rate}))
+            .dispatch();
+    }
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("hotel"),
+            -100.0,
+            date("2022-07-15"),
+            euros_id,
+            Some(bucket_id),
+        ))
+        .dispatch();
+    // The amount sent is converted at the rate of the day
+    let legs = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&json!({
+            "name": "change",
+            "amount": 100.0,
+            "date": date("2022-08-02"),
+            "from_account_id": euros_id,
+            "to_account_id": dollars_id,
+        }))
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(legs[0]["amount"], -100.0);
+    assert_eq!(legs[1]["amount"], 150.0);
+    // Spending is converted at the rate of its date
+    let report = client
+        .get(format!("{}/spending?from=2022-07&to=2022-07", URL_REPORTS))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    let bucket = report["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|bucket| bucket["bucket_id"] == bucket_id)
+        .unwrap();
+    assert_eq!(bucket["spent"], json!([125.0]));
+    // Balances are converted at the rate of the end of each month
+    let report = client
+        .get(format!("{}/net-worth?from=2022-07&to=2022-08", URL_REPORTS))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(report["months"][0]["net_worth"], -125.0);
+    assert_eq!(report["months"][1]["net_worth"], -150.0);
+    // The balance of an account keeps its currency
+    let balance = client
+        .get(format!("{}/{}/balance", URL_ACCOUNT, euros_id))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(balance["balance"], -200.0);
+    assert_eq!(balance["currency"], "EUR");
+    assert_eq!(balance["base_balance"], -300.0);
+    // The amount received may be given instead
+    let legs = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&json!({
+            "name": "change",
+            "amount": 100.0,
+            "to_amount": 140.0,
+            "date": date("2022-08-03"),
+            "from_account_id": euros_id,
+            "to_account_id": dollars_id,
+        }))
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(legs[1]["amount"], 140.0);
+}
+
+#[test]
+fn test_exchange_rate_missing() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let dollars_id = setup.create_account();
+    let francs_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("francs")).with_currency("CHF"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("salary"),
+            1000.0,
+            date("2022-07-01"),
+            francs_id,
+            None,
+        ))
+        .dispatch();
+    // Amounts without any rate aren't taken as if in the currency of the budget
+    let response = client.get(URL_SUMMARY).dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .get(format!("{}/{}/balance", URL_ACCOUNT, francs_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .get(format!(
+            "{}/cash-flow?from=2022-07-01&to=2022-07-31",
+            URL_REPORTS
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&json!({
+            "name": "change",
+            "amount": 100.0,
+            "date": date("2022-07-02"),
+            "from_account_id": francs_id,
+            "to_account_id": dollars_id,
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Once there is a rate, every amount converts
+    client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "CHF", "date": date("2022-07-01"), "rate": 1.1}))
+        .dispatch();
+    let response = client.get(URL_SUMMARY).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_exchange_rate_to_assign() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let euros_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("euros")).with_currency("EUR"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "EUR", "date": date("2022-07-01"), "rate": 1.25}))
+        .dispatch();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("salary"),
+            100.0,
+            date("2022-07-02"),
+            euros_id,
+            None,
+        ))
+        .dispatch();
+    let to_assign = |client: &AuthClient| {
+        client
+            .get(URL_SUMMARY)
+            .dispatch()
+            .into_json::<Summary>()
+            .unwrap()
+            .to_assign
+    };
+    assert_eq!(to_assign(client), 125.0);
+    // A later rate changes the net worth but not the money to assign
+    client
+        .post(URL_EXCHANGE_RATE)
+        .json(&json!({"currency": "EUR", "date": date("2022-08-01"), "rate": 1.5}))
+        .dispatch();
+    assert_eq!(to_assign(client), 125.0);
+    let summary = client
+        .get(URL_SUMMARY)
+        .dispatch()
+        .into_json::<Summary>()
+        .unwrap();
+    assert_eq!(summary.net_worth, 150.0);
+}
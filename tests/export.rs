@@ -0,0 +1,252 @@
+mod common;
+
+use std::env;
+
+use chrono::NaiveDateTime;
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Value;
+
+use common::{Account, Bucket, Fill, Setup, Transaction, Transfer};
+use common::{URL_ACCOUNT, URL_BUCKET, URL_EXPORT, URL_FILL, URL_TRANSACTION};
+
+fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[test]
+fn test_export_json() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    for (name, day) in [("june", "2022-06-30"), ("july", "2022-07-01")] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from(name),
+                -10.0,
+                date(day),
+                account_id,
+                Some(bucket_id),
+            ))
+            .dispatch();
+    }
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(50.0, date("2022-06-01"), bucket_id))
+        .dispatch();
+    // Transactions and fills are limited to the range
+    let response = client
+        .get(format!("{}?from=2022-07-01&to=2022-07-31", URL_EXPORT))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let export = response.into_json::<Value>().unwrap();
+    assert_eq!(export["version"], 4);
+    assert_eq!(export["accounts"].as_array().unwrap().len(), 1);
+    assert_eq!(export["buckets"].as_array().unwrap().len(), 1);
+    let transactions = export["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0]["name"], "july");
+    assert!(export["fills"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_export_csv() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("groceries"),
+            -12.5,
+            date("2022-07-01"),
+            account_id,
+            None,
+        ))
+        .dispatch();
+    // One line per transaction after the header
+    let response = client
+        .get(format!("{}/transactions.csv", URL_EXPORT))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::CSV));
+    let body = response.into_string().unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("id,name,amount,date,account_id,bucket_id"));
+    assert!(lines[1].contains(",groceries,-12.5,2022-07-01T00:00:00,"));
+    // The header is written even without rows
+    let body = client
+        .get(format!("{}/loans.csv", URL_EXPORT))
+        .dispatch()
+        .into_string()
+        .unwrap();
+    assert_eq!(body, "account_id,principal,interest_rate,term,start_date\n");
+    let body = client
+        .get(format!("{}/recurring_transactions.csv", URL_EXPORT))
+        .dispatch()
+        .into_string()
+        .unwrap();
+    assert_eq!(
+        body,
+        "id,name,amount,next_date,frequency,account_id,bucket_id\n"
+    );
+    // Unknown entities are not found
+    let response = client.get(format!("{}/users.csv", URL_EXPORT)).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_export_journal() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = client
+        .post(URL_ACCOUNT)
+        .json(
+            &Account::new(String::from("main checking"))
+                .with_currency("EUR")
+                .with_opening(500.0, date("2022-07-01")),
+        )
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    let card_id = client
+        .post(URL_ACCOUNT)
+        .json(
+            &Account::new(String::from("visa"))
+                .with_type("credit_card")
+                .with_currency("EUR"),
+        )
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    let bucket_id = client
+        .post(URL_BUCKET)
+        .json(&Bucket::new(String::from("groceries")))
+        .dispatch()
+        .into_json::<Bucket>()
+        .unwrap()
+        .id
+        .unwrap();
+    client
+        .post(URL_TRANSACTION)
+        .json(
+            &Transaction::new(
+                String::from("market"),
+                -12.5,
+                date("2022-07-02"),
+                checking_id,
+                Some(bucket_id),
+            )
+            .with_memo(String::from("vegetables"))
+            .with_status("cleared"),
+        )
+        .dispatch();
+    client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("card payment"),
+            amount: 100.0,
+            date: date("2022-07-03"),
+            from_account_id: checking_id,
+            to_account_id: card_id,
+        })
+        .dispatch();
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(50.0, date("2022-07-01"), bucket_id))
+        .dispatch();
+    // Ledger journal with fills as virtual postings, in the currency of the budget
+    let response = client.get(format!("{}/journal", URL_EXPORT)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let journal = response.into_string().unwrap();
+    assert!(journal.contains("account Assets:Main-Checking\n"));
+    assert!(journal.contains("account Liabilities:Visa\n"));
+    assert!(journal.contains(
+        "2022-07-02 * market\n    ; vegetables\n    Assets:Main-Checking  -12.50 EUR\n    Expenses:Groceries  12.50 EUR\n"
+    ));
+    // Opening balances come from equity rather than income
+    assert!(journal.contains(
+        "2022-07-01 * Opening balance\n    Assets:Main-Checking  500.00 EUR\n    Equity:Opening-Balances  -500.00 EUR\n"
+    ));
+    // Transfers are written once
+    assert_eq!(journal.matches("card payment").count(), 1);
+    assert!(journal.contains(
+        "2022-07-03 card payment\n    Assets:Main-Checking  -100.00 EUR\n    Liabilities:Visa  100.00 EUR\n"
+    ));
+    assert!(journal.contains("[Budget:Groceries]  50.00 USD"));
+    // Beancount journal with fills as custom directives
+    let journal = client
+        .get(format!("{}/journal?format=beancount", URL_EXPORT))
+        .dispatch()
+        .into_string()
+        .unwrap();
+    assert!(journal.contains("2022-07-01 open Expenses:Groceries\n"));
+    assert!(journal.contains("2022-07-02 * \"market\"\n    memo: \"vegetables\"\n"));
+    assert!(journal.contains("2022-07-03 ! \"card payment\"\n"));
+    assert!(journal.contains("2022-07-01 custom \"fill\" Expenses:Groceries 50.00 USD\n"));
+}
+
+#[test]
+fn test_export_journal_accounts() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let [savings_id, other_id] = ["savings", "Savings!"].map(|name| {
+        client
+            .post(URL_ACCOUNT)
+            .json(&Account::new(String::from(name)))
+            .dispatch()
+            .into_json::<Account>()
+            .unwrap()
+            .id
+            .unwrap()
+    });
+    let legs = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("saving"),
+            amount: 100.0,
+            date: date("2022-07-03"),
+            from_account_id: savings_id,
+            to_account_id: other_id,
+        })
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap();
+    // Accounts named alike are told apart by their id
+    let journal = client
+        .get(format!("{}/journal?format=beancount", URL_EXPORT))
+        .dispatch()
+        .into_string()
+        .unwrap();
+    assert!(journal.contains(&format!("open Assets:Savings:{}\n", savings_id)));
+    assert!(journal.contains(&format!("open Assets:Savings:{}\n", other_id)));
+    assert!(!journal.contains("open Assets:Savings\n"));
+    // A leg written apart from the other one is kept in the days of the journal
+    let conn = SqliteConnection::establish(&env::var("DATABASE_URL").unwrap()).unwrap();
+    conn.batch_execute(&format!(
+        "UPDATE transactions SET date = '2022-08-01 00:00:00' WHERE id = {}",
+        legs[1].id.unwrap()
+    ))
+    .unwrap();
+    let journal = client
+        .get(format!("{}/journal?from=2022-08-01", URL_EXPORT))
+        .dispatch()
+        .into_string()
+        .unwrap();
+    assert!(journal.contains(&format!(
+        "2022-08-01 saving\n    Assets:Savings:{}  100.00 USD\n    Assets:Savings:{}  -100.00 USD\n",
+        other_id, savings_id
+    )));
+}
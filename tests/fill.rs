@@ -3,7 +3,7 @@ mod common;
 use chrono::{Duration, NaiveDateTime};
 use rocket::http::Status;
 
-use common::{Fill, Setup, FILL_NUMBER, URL_BUCKET, URL_FILL};
+use common::{if_match, Fill, Setup, FILL_NUMBER, URL_BUCKET, URL_FILL};
 
 fn default_fill(bucket_id: i32) -> Fill {
     let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
@@ -157,6 +157,7 @@ fn test_fill_update() {
     let new_fill = fill_form.with_amount(342.4);
     let response_update = client
         .put(format!("{}/{}", URL_FILL, fill_id))
+        .header(if_match(1))
         .json(&new_fill)
         .dispatch();
     assert_eq!(response_update.status(), Status::Ok);
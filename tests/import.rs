@@ -0,0 +1,144 @@
+mod common;
+
+use rocket::http::Status;
+use rocket::serde::json::Value;
+
+use common::{Setup, URL_ACCOUNT};
+
+const QIF: &str = "!Type:Bank
+D07/01/2022
+T-1,234.50
+PLandlord
+MJuly rent
+^
+D7/ 2'22
+T12,50
+PRefund
+^
+";
+
+const CAMT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="EUR">42.10</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2022-07-03</Dt></BookgDt>
+        <AcctSvcrRef>REF-0001</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties><Cdtr><Nm>Grocery Store</Nm></Cdtr></RltdPties>
+            <RmtInf><Ustrd>Card payment</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">2000.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><DtTm>2022-07-04T08:00:00+02:00</DtTm></BookgDt>
+        <AcctSvcrRef>REF-0002</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties><Dbtr><Nm>Employer</Nm></Dbtr></RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">5.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2022-07-05</Dt></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+
+#[test]
+fn test_import_qif() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let url = format!("{}/{}/import?format=qif", URL_ACCOUNT, account_id);
+    let response = client.post(&url).body(QIF).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let import = response.into_json::<Value>().unwrap();
+    let transactions = import["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0]["name"], "Landlord");
+    assert_eq!(transactions[0]["memo"], "July rent");
+    assert_eq!(transactions[0]["amount"], -1234.5);
+    assert_eq!(transactions[0]["status"], "cleared");
+    assert_eq!(transactions[1]["date"], "2022-07-02T00:00:00");
+    assert_eq!(transactions[1]["amount"], 12.5);
+    // Importing the same file again adds nothing
+    let import = client
+        .post(&url)
+        .body(QIF)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert!(import["transactions"].as_array().unwrap().is_empty());
+    assert_eq!(import["duplicates"], 2);
+}
+
+#[test]
+fn test_import_camt053() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let url = format!("{}/{}/import?format=camt053", URL_ACCOUNT, account_id);
+    let response = client.post(&url).body(CAMT).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let import = response.into_json::<Value>().unwrap();
+    // Pending entries wait until booked
+    let transactions = import["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0]["name"], "Grocery Store");
+    assert_eq!(transactions[0]["memo"], "Card payment");
+    assert_eq!(transactions[0]["amount"], -42.1);
+    assert_eq!(transactions[0]["import_id"], "REF-0001");
+    assert_eq!(transactions[1]["name"], "Employer");
+    assert_eq!(transactions[1]["amount"], 2000.0);
+    assert_eq!(transactions[1]["date"], "2022-07-04T00:00:00");
+    // Entries are deduplicated by their bank reference
+    let import = client
+        .post(&url)
+        .body(CAMT)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(import["duplicates"], 2);
+}
+
+#[test]
+fn test_import_invalid() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let response = client
+        .post(format!("{}/{}/import?format=qif", URL_ACCOUNT, account_id))
+        .body("!Type:Bank\nDyesterday\nT1.00\n^\n")
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let response = client
+        .post(format!(
+            "{}/{}/import?format=camt053",
+            URL_ACCOUNT, account_id
+        ))
+        .body("<Document/>")
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    // Only accounts of the budget take imports
+    let response = client
+        .post(format!("{}/{}/import?format=qif", URL_ACCOUNT, -1))
+        .body(QIF)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use common::{Account, AuthClient, Setup, Summary, URL_ACCOUNT, URL_LOAN, URL_SUMMARY};
+
+fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+// Loan of 12000 at 6% a year, repaid in 12 months from January 15th, 2022
+fn create_loan(client: &AuthClient) -> i32 {
+    client
+        .post(URL_LOAN)
+        .json(&json!({
+            "name": "car",
+            "principal": 12000.0,
+            "interest_rate": 6.0,
+            "term": 12,
+            "start_date": date("2022-01-15"),
+        }))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap()["account_id"]
+        .as_i64()
+        .unwrap() as i32
+}
+
+fn pay(client: &AuthClient, loan_id: i32, from_account_id: i32, amount: f32, day: &str) -> Status {
+    client
+        .post(format!("{}/{}/payment", URL_LOAN, loan_id))
+        .json(&json!({
+            "name": "car payment",
+            "amount": amount,
+            "date": date(day),
+            "from_account_id": from_account_id,
+        }))
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn test_loan_create() {
+    // Setup test
+    let client = &Setup::new().client;
+    let loan_id = create_loan(client);
+    let loan = client
+        .get(format!("{}/{}", URL_LOAN, loan_id))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(loan["name"], "car");
+    assert_eq!(loan["payment"], 1032.8);
+    assert_eq!(loan["remaining"], 12000.0);
+    assert_eq!(loan["payoff_date"], json!(date("2023-01-15")));
+    // The loan is an off-budget account owing the principal
+    let account = client
+        .get(format!("{}/{}", URL_ACCOUNT, loan_id))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap();
+    assert_eq!(account.account_type, "loan");
+    assert!(!account.on_budget);
+    let summary = client
+        .get(URL_SUMMARY)
+        .dispatch()
+        .into_json::<Summary>()
+        .unwrap();
+    assert_eq!(summary.net_worth, -12000.0);
+    assert_eq!(summary.to_assign, 0.0);
+    let loans = client
+        .get(URL_LOAN)
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(loans.len(), 1);
+}
+
+#[test]
+fn test_loan_create_invalid() {
+    // Setup test
+    let client = &Setup::new().client;
+    for (principal, interest_rate, term) in [(0.0, 6.0, 12), (1000.0, -1.0, 12), (1000.0, 6.0, 0)] {
+        let response = client
+            .post(URL_LOAN)
+            .json(&json!({
+                "name": "car",
+                "principal": principal,
+                "interest_rate": interest_rate,
+                "term": term,
+                "start_date": date("2022-01-15"),
+            }))
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+    // Loans are accounts, named uniquely
+    create_loan(client);
+    let response = client
+        .post(URL_LOAN)
+        .json(&json!({
+            "name": "car",
+            "principal": 12000.0,
+            "interest_rate": 6.0,
+            "term": 12,
+            "start_date": date("2022-01-15"),
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .get(format!("{}/{}/schedule", URL_LOAN, 0))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_loan_schedule() {
+    // Setup test
+    let client = &Setup::new().client;
+    let loan_id = create_loan(client);
+    let schedule = client
+        .get(format!("{}/{}/schedule", URL_LOAN, loan_id))
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(schedule.len(), 12);
+    assert_eq!(schedule[0]["date"], json!(date("2022-02-15")));
+    assert_eq!(schedule[0]["interest"], 60.0);
+    assert_eq!(schedule[0]["principal"], 972.8);
+    assert_eq!(schedule[11]["date"], json!(date("2023-01-15")));
+    assert_eq!(schedule[11]["balance"], 0.0);
+    // The principal is repaid in full
+    let repaid: f64 = schedule
+        .iter()
+        .map(|installment| installment["principal"].as_f64().unwrap())
+        .sum();
+    assert!((repaid - 12000.0).abs() < 0.01);
+}
+
+#[test]
+fn test_loan_payment() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let loan_id = create_loan(client);
+    let account_id = setup.create_account();
+    // The payment is split into a month of interest and principal
+    let payment = client
+        .post(format!("{}/{}/payment", URL_LOAN, loan_id))
+        .json(&json!({
+            "name": "car payment",
+            "amount": 1032.8,
+            "date": date("2022-02-15"),
+            "from_account_id": account_id,
+        }))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(payment["interest"], 60.0);
+    assert_eq!(payment["principal"], 972.8);
+    let transactions = payment["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 3);
+    assert_eq!(transactions[0]["account_id"], account_id);
+    assert_eq!(transactions[0]["amount"], -60.0);
+    assert_eq!(transactions[2]["account_id"], loan_id);
+    assert_eq!(transactions[2]["amount"], 972.8);
+    let loan = client
+        .get(format!("{}/{}", URL_LOAN, loan_id))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(loan["remaining"], 11027.2);
+    assert_eq!(loan["payoff_date"], json!(date("2023-01-15")));
+    // Paying more than is owed is refused
+    assert_eq!(
+        pay(client, loan_id, account_id, 20000.0, "2022-03-15"),
+        Status::UnprocessableEntity
+    );
+    // A larger payment brings the payoff date closer
+    assert_eq!(
+        pay(client, loan_id, account_id, 6000.0, "2022-03-15"),
+        Status::Created
+    );
+    let loan = client
+        .get(format!("{}/{}", URL_LOAN, loan_id))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(loan["payoff_date"], json!(date("2022-08-15")));
+    // Payments come from an account of the loan currency
+    let euros_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("euros")).with_currency("EUR"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    assert_eq!(
+        pay(client, loan_id, euros_id, 1032.8, "2022-04-15"),
+        Status::UnprocessableEntity
+    );
+}
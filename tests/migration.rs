@@ -0,0 +1,63 @@
+mod common;
+
+use std::env;
+use std::fs;
+
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+
+use common::{Account, Credentials, Token};
+use common::{URL_ACCOUNT, URL_LOGIN, URL_REGISTER};
+
+#[test]
+fn test_migration_budgets_without_users() {
+    // Setup an instance with data but no users yet
+    let path = env::temp_dir().join(format!("migration_{}.sqlite", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let database_url = path.to_str().unwrap();
+    let conn = SqliteConnection::establish(database_url).unwrap();
+    let mut migrations: Vec<String> = fs::read_dir("migrations")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    migrations.sort();
+    for migration in migrations {
+        if migration.ends_with("_budgets") {
+            conn.batch_execute("INSERT INTO accounts (name) VALUES ('checking')")
+                .unwrap();
+        }
+        let sql = fs::read_to_string(format!("migrations/{}/up.sql", migration)).unwrap();
+        conn.batch_execute(&sql).unwrap();
+    }
+    drop(conn);
+    // The first user to register finds the data in their budget
+    let client = Client::tracked(common::rocket(database_url)).unwrap();
+    let credentials = Credentials::new("first_user", "password");
+    assert_eq!(
+        client
+            .post(URL_REGISTER)
+            .json(&credentials)
+            .dispatch()
+            .status(),
+        Status::Created
+    );
+    let token = client
+        .post(URL_LOGIN)
+        .json(&credentials)
+        .dispatch()
+        .into_json::<Token>()
+        .unwrap()
+        .token;
+    let accounts = client
+        .get(URL_ACCOUNT)
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch()
+        .into_json::<Vec<Account>>()
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].name, "checking");
+    drop(client);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,303 @@
+mod common;
+
+use chrono::{Duration, NaiveDateTime};
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use common::{
+    if_match, Reconciliation, Setup, Statement, Transaction, Transfer, URL_ACCOUNT, URL_TRANSACTION,
+};
+
+fn create_transactions(setup: &Setup, account_id: i32) -> Vec<i32> {
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let transactions = [
+        Transaction::new(String::from("Income"), 1000.0, date, account_id, None)
+            .with_status("cleared"),
+        Transaction::new(String::from("Rent"), -600.0, date, account_id, None)
+            .with_status("cleared"),
+        Transaction::new(String::from("Groceries"), -50.0, date, account_id, None),
+        Transaction::new(
+            String::from("Later"),
+            -25.0,
+            date + Duration::days(40),
+            account_id,
+            None,
+        )
+        .with_status("cleared"),
+    ];
+    transactions
+        .iter()
+        .map(|transaction| {
+            setup
+                .client
+                .post(URL_TRANSACTION)
+                .json(transaction)
+                .dispatch()
+                .into_json::<Transaction>()
+                .unwrap()
+                .id
+                .unwrap()
+        })
+        .collect()
+}
+
+fn statement(ending_balance: f32, adjust: bool) -> Statement {
+    Statement {
+        date: NaiveDateTime::parse_from_str("2022-07-31 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        ending_balance,
+        adjust,
+    }
+}
+
+#[test]
+fn test_reconciliation_report() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    create_transactions(&setup, account_id);
+    // Compare the statement to the cleared balance
+    let response = client
+        .post(format!("{}/{}/reconcile", URL_ACCOUNT, account_id))
+        .json(&statement(390.0, false))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let reconciliation = response.into_json::<Reconciliation>().unwrap();
+    assert_eq!(reconciliation.cleared_balance, 400.0);
+    assert_eq!(reconciliation.difference, -10.0);
+}
+
+#[test]
+fn test_reconciliation_account_not_found() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Try reconciling a missing account
+    let response = client
+        .post(format!("{}/0/reconcile", URL_ACCOUNT))
+        .json(&statement(0.0, false))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_reconciliation_confirm() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let ids = create_transactions(&setup, account_id);
+    // Confirm a matching statement
+    let response = client
+        .post(format!("{}/{}/reconcile/confirm", URL_ACCOUNT, account_id))
+        .json(&statement(400.0, false))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // Only cleared transactions up to the statement date are reconciled
+    let statuses: Vec<String> = ids
+        .iter()
+        .map(|id| {
+            client
+                .get(format!("{}/{}", URL_TRANSACTION, id))
+                .dispatch()
+                .into_json::<Transaction>()
+                .unwrap()
+                .status
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        ["reconciled", "reconciled", "uncleared", "cleared"]
+    );
+}
+
+#[test]
+fn test_reconciliation_confirm_difference() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    create_transactions(&setup, account_id);
+    // Refuse to reconcile with a difference
+    let response = client
+        .post(format!("{}/{}/reconcile/confirm", URL_ACCOUNT, account_id))
+        .json(&statement(390.0, false))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(
+        client
+            .get(format!(
+                "{}/{}/transactions?status=reconciled",
+                URL_ACCOUNT, account_id
+            ))
+            .dispatch()
+            .into_json::<Vec<Transaction>>(),
+        Some(vec![])
+    );
+}
+
+#[test]
+fn test_reconciliation_confirm_adjust() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    create_transactions(&setup, account_id);
+    // Reconcile with an adjustment transaction
+    let response = client
+        .post(format!("{}/{}/reconcile/confirm", URL_ACCOUNT, account_id))
+        .json(&statement(390.0, true))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let reconciliation = response.into_json::<Reconciliation>().unwrap();
+    assert_eq!(reconciliation.cleared_balance, 390.0);
+    assert_eq!(reconciliation.difference, 0.0);
+    let reconciled = client
+        .get(format!(
+            "{}/{}/transactions?status=reconciled",
+            URL_ACCOUNT, account_id
+        ))
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap();
+    assert_eq!(reconciled.len(), 3);
+    assert_eq!(reconciled[2].amount, -10.0);
+}
+
+#[test]
+fn test_reconciliation_locks_transactions() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let ids = create_transactions(&setup, account_id);
+    client
+        .post(format!("{}/{}/reconcile/confirm", URL_ACCOUNT, account_id))
+        .json(&statement(400.0, false))
+        .dispatch();
+    // Try updating and deleting a reconciled transaction
+    let transaction = client
+        .get(format!("{}/{}", URL_TRANSACTION, ids[0]))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    let response = client
+        .put(format!("{}/{}", URL_TRANSACTION, ids[0]))
+        .header(if_match(2))
+        .json(&transaction.with_name(String::from("new_name")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .delete(format!("{}/{}", URL_TRANSACTION, ids[0]))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Uncleared transactions can still be edited
+    let response = client
+        .delete(format!("{}/{}", URL_TRANSACTION, ids[2]))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_reconciliation_status_refused() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let ids = create_transactions(&setup, account_id);
+    let transaction = client
+        .get(format!("{}/{}", URL_TRANSACTION, ids[2]))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    // Transactions are only marked as reconciled by a reconciliation
+    let response = client
+        .put(format!("{}/{}", URL_TRANSACTION, ids[2]))
+        .header(if_match(1))
+        .json(&transaction.with_status("reconciled"))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let response = client
+        .patch(format!("{}/{}", URL_TRANSACTION, ids[2]))
+        .header(if_match(1))
+        .json(&json!({ "status": "reconciled" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let response = client
+        .patch(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({ "ids": [ids[2]], "patch": { "status": "reconciled" } }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let transaction = client
+        .get(format!("{}/{}", URL_TRANSACTION, ids[2]))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    assert_eq!(transaction.status, "uncleared");
+    // Missing transactions aren't a conflict
+    let response = client
+        .patch(format!("{}/{}", URL_TRANSACTION, 0))
+        .header(if_match(1))
+        .json(&json!({ "name": "missing" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_reconciliation_locks_transfers() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let from_account_id = setup.create_account();
+    let to_account_id = setup.create_account();
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let legs = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("Savings"),
+            amount: 100.0,
+            date,
+            from_account_id,
+            to_account_id,
+        })
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap();
+    let (sent_id, received_id) = (legs[0].id.unwrap(), legs[1].id.unwrap());
+    // Reconcile the receiving leg only
+    client
+        .patch(format!("{}/{}", URL_TRANSACTION, received_id))
+        .header(if_match(1))
+        .json(&json!({ "status": "cleared" }))
+        .dispatch();
+    let response = client
+        .post(format!(
+            "{}/{}/reconcile/confirm",
+            URL_ACCOUNT, to_account_id
+        ))
+        .json(&statement(100.0, false))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // Try updating and deleting the other leg
+    let response = client
+        .patch(format!("{}/{}", URL_TRANSACTION, sent_id))
+        .header(if_match(1))
+        .json(&json!({ "name": "new_name" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .delete(format!("{}/{}", URL_TRANSACTION, sent_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .delete(format!("{}/bulk", URL_TRANSACTION))
+        .json(&json!({ "ids": [sent_id] }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let received = client
+        .get(format!("{}/{}", URL_TRANSACTION, received_id))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    assert_eq!(received.name, "Savings");
+    assert_eq!(received.status, "reconciled");
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use common::{Setup, URL_RECURRING};
+
+fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[test]
+fn test_recurring_crud() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    // Plan a monthly rent
+    let response = client
+        .post(URL_RECURRING)
+        .json(&json!({
+            "name": "rent",
+            "amount": -1000.0,
+            "next_date": date("2022-08-01"),
+            "frequency": "monthly",
+            "account_id": account_id,
+            "bucket_id": bucket_id,
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let id = response.into_json::<Value>().unwrap()["id"]
+        .as_i64()
+        .unwrap();
+    let recurring = client
+        .get(URL_RECURRING)
+        .dispatch()
+        .into_json::<Vec<Value>>()
+        .unwrap();
+    assert_eq!(recurring.len(), 1);
+    assert_eq!(recurring[0]["frequency"], "monthly");
+    // Updates replace every field
+    let response = client
+        .put(format!("{}/{}", URL_RECURRING, id))
+        .json(&json!({
+            "name": "rent",
+            "amount": -1100.0,
+            "next_date": date("2022-09-01"),
+            "frequency": "monthly",
+            "account_id": account_id,
+        }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let recurring = response.into_json::<Value>().unwrap();
+    assert_eq!(recurring["amount"], -1100.0);
+    assert_eq!(recurring["bucket_id"], Value::Null);
+    // Delete it
+    let response = client
+        .delete(format!("{}/{}", URL_RECURRING, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.get(format!("{}/{}", URL_RECURRING, id)).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_recurring_invalid() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Try planning on a missing account, or with an unknown frequency
+    for (account_id, frequency, status) in [
+        (0, "monthly", Status::Conflict),
+        (account_id, "daily", Status::UnprocessableEntity),
+    ] {
+        let response = client
+            .post(URL_RECURRING)
+            .json(&json!({
+                "name": "rent",
+                "amount": -1000.0,
+                "next_date": date("2022-08-01"),
+                "frequency": frequency,
+                "account_id": account_id,
+            }))
+            .dispatch();
+        assert_eq!(response.status(), status);
+    }
+}
@@ -0,0 +1,437 @@
+mod common;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime};
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use common::{Account, Fill, Setup, Transaction, Transfer, URL_ACCOUNT, URL_FILL};
+use common::{URL_RECURRING, URL_REPORTS, URL_TRANSACTION};
+
+fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+// Day of the month `offset` months away from the current one
+fn day_of_month(offset: i32, day: u32) -> NaiveDateTime {
+    let today = Local::now().naive_local().date();
+    let month = today.year() * 12 + today.month0() as i32 + offset;
+    NaiveDate::from_ymd(month / 12, month as u32 % 12 + 1, day).and_hms(0, 0, 0)
+}
+
+#[test]
+fn test_reports_spending() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    for (amount, day) in [
+        (-30.0, "2022-07-02"),
+        (-10.0, "2022-07-31"),
+        (-20.0, "2022-08-15"),
+    ] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("groceries"),
+                amount,
+                date(day),
+                account_id,
+                Some(bucket_id),
+            ))
+            .dispatch();
+    }
+    // Spending outside of the range is left out
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("groceries"),
+            -99.0,
+            date("2022-10-01"),
+            account_id,
+            Some(bucket_id),
+        ))
+        .dispatch();
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(50.0, date("2022-07-01"), bucket_id))
+        .dispatch();
+    // Read the report of three months
+    let response = client
+        .get(format!("{}/spending?from=2022-07&to=2022-09", URL_REPORTS))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let report = response.into_json::<Value>().unwrap();
+    assert_eq!(report["months"], json!(["2022-07", "2022-08", "2022-09"]));
+    let bucket = report["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|bucket| bucket["bucket_id"] == bucket_id)
+        .unwrap();
+    assert_eq!(bucket["spent"], json!([40.0, 20.0, 0.0]));
+    assert_eq!(bucket["filled"], json!([50.0, 0.0, 0.0]));
+    assert_eq!(bucket["average_spent"], 20.0);
+    assert_eq!(bucket["difference"], -10.0);
+}
+
+#[test]
+fn test_reports_spending_invalid_range() {
+    // Setup test
+    let client = &Setup::new().client;
+    // The range must not be reversed
+    let response = client
+        .get(format!("{}/spending?from=2022-09&to=2022-07", URL_REPORTS))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn test_reports_cash_flow() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let savings_id = setup.create_account();
+    let investment_id = client
+        .post(URL_ACCOUNT)
+        .json(
+            &Account::new(String::from("investment"))
+                .with_type("investment")
+                .off_budget(),
+        )
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    for (account_id, amount, day) in [
+        (checking_id, 1000.0, "2022-07-01"),
+        (checking_id, -200.0, "2022-07-10"),
+        (savings_id, -50.0, "2022-08-01"),
+        (investment_id, 500.0, "2022-07-01"),
+    ] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("cash"),
+                amount,
+                date(day),
+                account_id,
+                None,
+            ))
+            .dispatch();
+    }
+    // Transfers are neither income nor expenses
+    client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("saving"),
+            amount: 100.0,
+            date: date("2022-07-15"),
+            from_account_id: checking_id,
+            to_account_id: savings_id,
+        })
+        .dispatch();
+    // Read the monthly cash flow
+    let response = client
+        .get(format!(
+            "{}/cash-flow?from=2022-07-01&to=2022-08-31",
+            URL_REPORTS
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let report = response.into_json::<Value>().unwrap();
+    let periods = report["periods"].as_array().unwrap();
+    assert_eq!(periods.len(), 2);
+    assert_eq!(periods[0]["period"], "2022-07");
+    assert_eq!(periods[0]["income"], 1000.0);
+    assert_eq!(periods[0]["expenses"], 200.0);
+    assert_eq!(periods[0]["net"], 800.0);
+    assert_eq!(periods[1]["net"], -50.0);
+    // Only on-budget accounts are broken down
+    let accounts = periods[1]["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[1]["account_id"], savings_id);
+    assert_eq!(accounts[1]["expenses"], 50.0);
+    // Group by year instead
+    let report = client
+        .get(format!(
+            "{}/cash-flow?from=2022-07-01&to=2022-08-31&interval=year",
+            URL_REPORTS
+        ))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(report["periods"][0]["period"], "2022");
+    assert_eq!(report["periods"][0]["net"], 750.0);
+}
+
+#[test]
+fn test_reports_cash_flow_opening_balance() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("checking")).with_opening(1000.0, date("2022-07-01")))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("cash"),
+            -200.0,
+            date("2022-07-10"),
+            checking_id,
+            None,
+        ))
+        .dispatch();
+    // The opening balance isn't income
+    let report = client
+        .get(format!(
+            "{}/cash-flow?from=2022-07-01&to=2022-07-31",
+            URL_REPORTS
+        ))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(report["periods"][0]["income"], 0.0);
+    assert_eq!(report["periods"][0]["expenses"], 200.0);
+    // But it is part of the net worth
+    let report = client
+        .get(format!("{}/net-worth?from=2022-07&to=2022-07", URL_REPORTS))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    assert_eq!(report["months"][0]["net_worth"], 800.0);
+}
+
+#[test]
+fn test_reports_net_worth() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let card_id = client
+        .post(URL_ACCOUNT)
+        .json(&Account::new(String::from("card")).with_type("credit_card"))
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    let investment_id = client
+        .post(URL_ACCOUNT)
+        .json(
+            &Account::new(String::from("investment"))
+                .with_type("investment")
+                .off_budget(),
+        )
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    for (account_id, amount, day) in [
+        (checking_id, 1000.0, "2022-06-15"),
+        (checking_id, -200.0, "2022-07-10"),
+        (card_id, -300.0, "2022-07-20"),
+        (investment_id, 5000.0, "2022-08-01"),
+    ] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("cash"),
+                amount,
+                date(day),
+                account_id,
+                None,
+            ))
+            .dispatch();
+    }
+    // Balances carry over from before the range
+    let response = client
+        .get(format!("{}/net-worth?from=2022-07&to=2022-08", URL_REPORTS))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let report = response.into_json::<Value>().unwrap();
+    let months = report["months"].as_array().unwrap();
+    assert_eq!(months.len(), 2);
+    assert_eq!(months[0]["month"], "2022-07");
+    assert_eq!(months[0]["assets"], 800.0);
+    assert_eq!(months[0]["liabilities"], 300.0);
+    assert_eq!(months[0]["net_worth"], 500.0);
+    assert_eq!(months[1]["net_worth"], 5500.0);
+    let accounts = months[1]["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 3);
+    assert_eq!(accounts[2]["account_id"], investment_id);
+    assert_eq!(accounts[2]["balance"], 5000.0);
+}
+
+#[test]
+fn test_reports_forecast() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let card_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    // Income of last month is averaged over three months, a bill is scheduled next month
+    // and stands for all of that month, spending is scheduled the month after
+    for (account_id, amount, date, bucket_id) in [
+        (checking_id, 900.0, day_of_month(-1, 1), None),
+        (checking_id, -2000.0, day_of_month(1, 2), None),
+        (card_id, -60.0, day_of_month(-1, 5), Some(bucket_id)),
+        (card_id, -5.0, day_of_month(2, 3), Some(bucket_id)),
+    ] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("cash"),
+                amount,
+                date,
+                account_id,
+                bucket_id,
+            ))
+            .dispatch();
+    }
+    // A fill is planned for next month
+    for (amount, date) in [(100.0, day_of_month(-1, 1)), (50.0, day_of_month(1, 2))] {
+        client
+            .post(URL_FILL)
+            .json(&Fill::new(amount, date, bucket_id))
+            .dispatch();
+    }
+    // Forecast the next two months
+    let response = client
+        .get(format!("{}/forecast?months=2", URL_REPORTS))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let report = response.into_json::<Value>().unwrap();
+    let months = [
+        day_of_month(1, 1).format("%Y-%m").to_string(),
+        day_of_month(2, 1).format("%Y-%m").to_string(),
+    ];
+    assert_eq!(report["months"], json!(months));
+    let checking = &report["accounts"][0];
+    assert_eq!(checking["account_id"], checking_id);
+    assert_eq!(checking["balance"], 900.0);
+    assert_eq!(checking["average_change"], 300.0);
+    assert_eq!(checking["forecast"], json!([-1100.0, -800.0]));
+    assert_eq!(checking["negative_months"], json!(months));
+    let bucket = report["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|bucket| bucket["bucket_id"] == bucket_id)
+        .unwrap();
+    assert_eq!(bucket["available"], 40.0);
+    assert_eq!(bucket["average_spent"], 20.0);
+    assert_eq!(bucket["forecast"], json!([70.0, 65.0]));
+    // The number of months is bounded
+    let response = client
+        .get(format!("{}/forecast?months=0", URL_REPORTS))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn test_reports_forecast_recurring() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    // A rent entered once repeats every month of the forecast
+    client
+        .post(URL_RECURRING)
+        .json(&json!({
+            "name": "rent",
+            "amount": -1000.0,
+            "next_date": day_of_month(1, 5),
+            "frequency": "monthly",
+            "account_id": account_id,
+            "bucket_id": bucket_id,
+        }))
+        .dispatch();
+    let report = client
+        .get(format!("{}/forecast?months=3", URL_REPORTS))
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap();
+    let account = report["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|account| account["account_id"] == account_id)
+        .unwrap();
+    assert_eq!(account["forecast"], json!([-1000.0, -2000.0, -3000.0]));
+    let bucket = report["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|bucket| bucket["bucket_id"] == bucket_id)
+        .unwrap();
+    assert_eq!(bucket["forecast"], json!([-1000.0, -2000.0, -3000.0]));
+}
+
+#[test]
+fn test_reports_age_of_money() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let savings_id = setup.create_account();
+    for (amount, day) in [
+        (1000.0, "2022-01-01"),
+        (-300.0, "2022-01-11"),
+        (200.0, "2022-02-01"),
+        (-500.0, "2022-02-10"),
+        (-400.0, "2022-03-03"),
+    ] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("cash"),
+                amount,
+                date(day),
+                checking_id,
+                None,
+            ))
+            .dispatch();
+    }
+    // Transfers between on-budget accounts don't age money
+    client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("saving"),
+            amount: 100.0,
+            date: date("2022-01-05"),
+            from_account_id: checking_id,
+            to_account_id: savings_id,
+        })
+        .dispatch();
+    // Outflows consume the oldest income first
+    let response = client
+        .get(format!(
+            "{}/age-of-money?from=2022-01&to=2022-04",
+            URL_REPORTS
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let report = response.into_json::<Value>().unwrap();
+    let months = report["months"].as_array().unwrap();
+    assert_eq!(months.len(), 4);
+    assert_eq!(months[0]["month"], "2022-01");
+    assert_eq!(months[0]["age"], 10.0);
+    assert_eq!(months[0]["spent"], 300.0);
+    assert_eq!(months[1]["age"], 40.0);
+    // 200 received on January 1st and 200 on February 1st
+    assert_eq!(months[2]["age"], 45.5);
+    assert_eq!(months[3]["age"], Value::Null);
+    assert_eq!(months[3]["spent"], 0.0);
+}
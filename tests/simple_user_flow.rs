@@ -1,13 +1,12 @@
 mod common;
 
 use chrono::NaiveDateTime;
-use rocket::local::blocking::Client;
 
-use common::Setup;
+use common::{AuthClient, Setup};
 use common::{Account, Bucket, Fill, Transaction};
 use common::{URL_ACCOUNT, URL_BUCKET, URL_FILL, URL_TRANSACTION};
 
-fn create_bucket_from_name(client: &Client, name: String) -> i32 {
+fn create_bucket_from_name(client: &AuthClient, name: String) -> i32 {
     client
         .post(URL_BUCKET)
         .json(&Bucket::new(name))
@@ -26,10 +25,7 @@ fn test_simple_user_flow() {
     // Create an account
     let account_id = client
         .post(URL_ACCOUNT)
-        .json(&Account {
-            id: None,
-            name: String::from("banking"),
-        })
+        .json(&Account::new(String::from("banking")))
         .dispatch()
         .into_json::<Account>()
         .unwrap()
@@ -100,7 +96,7 @@ fn test_simple_user_flow() {
     );
 }
 
-fn get_bucket_fill(client: &Client, bucket_id: i32) -> f32 {
+fn get_bucket_fill(client: &AuthClient, bucket_id: i32) -> f32 {
     client
         .get(format!("{}/{}/fills/2022/07", URL_BUCKET, bucket_id,))
         .dispatch()
@@ -111,7 +107,7 @@ fn get_bucket_fill(client: &Client, bucket_id: i32) -> f32 {
         .sum::<f32>()
 }
 
-fn get_bucket_consumption(client: &Client, bucket_id: i32) -> f32 {
+fn get_bucket_consumption(client: &AuthClient, bucket_id: i32) -> f32 {
     client
         .get(format!("{}/{}/transactions/2022/07", URL_BUCKET, bucket_id,))
         .dispatch()
@@ -122,7 +118,7 @@ fn get_bucket_consumption(client: &Client, bucket_id: i32) -> f32 {
         .sum::<f32>()
 }
 
-fn check_bucket_sum_less_income(client: &Client, account_id: i32) {
+fn check_bucket_sum_less_income(client: &AuthClient, account_id: i32) {
     let buckets = client
         .get(URL_BUCKET)
         .dispatch()
@@ -0,0 +1,105 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+
+use common::{Account, Fill, Setup, Summary, Transaction};
+use common::{URL_ACCOUNT, URL_FILL, URL_SUMMARY, URL_TRANSACTION};
+
+#[test]
+fn test_summary_empty() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Read summary
+    let response = client.get(URL_SUMMARY).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let summary = response.into_json::<Summary>().unwrap();
+    assert_eq!(summary.net_worth, 0.0);
+    assert_eq!(summary.to_assign, 0.0);
+}
+
+#[test]
+fn test_summary_tracking_account() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let checking_id = setup.create_account();
+    let investment_id = client
+        .post(URL_ACCOUNT)
+        .json(
+            &Account::new(String::from("investment"))
+                .with_type("investment")
+                .off_budget(),
+        )
+        .dispatch()
+        .into_json::<Account>()
+        .unwrap()
+        .id
+        .unwrap();
+    let bucket_id = setup.create_bucket();
+    // Add money to both accounts and fill a bucket
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for (account_id, amount) in [(checking_id, 1000.0), (investment_id, 5000.0)] {
+        client
+            .post(URL_TRANSACTION)
+            .json(&Transaction::new(
+                String::from("Income"),
+                amount,
+                date,
+                account_id,
+                None,
+            ))
+            .dispatch();
+    }
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(400.0, date, bucket_id))
+        .dispatch();
+    // Tracking accounts count in net worth only
+    let summary = client
+        .get(URL_SUMMARY)
+        .dispatch()
+        .into_json::<Summary>()
+        .unwrap();
+    assert_eq!(summary.net_worth, 6000.0);
+    assert_eq!(summary.on_budget, 1000.0);
+    assert_eq!(summary.filled, 400.0);
+    assert_eq!(summary.to_assign, 600.0);
+}
+
+#[test]
+fn test_summary_spending() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    // Receive an income, fill a bucket and spend from it
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let transactions = [
+        Transaction::new(String::from("Income"), 1000.0, date, account_id, None),
+        Transaction::new(
+            String::from("Groceries"),
+            -100.0,
+            date,
+            account_id,
+            Some(bucket_id),
+        ),
+    ];
+    for transaction in &transactions {
+        client.post(URL_TRANSACTION).json(transaction).dispatch();
+    }
+    client
+        .post(URL_FILL)
+        .json(&Fill::new(400.0, date, bucket_id))
+        .dispatch();
+    // Spending from a bucket leaves the money to assign untouched
+    let summary = client
+        .get(URL_SUMMARY)
+        .dispatch()
+        .into_json::<Summary>()
+        .unwrap();
+    assert_eq!(summary.on_budget, 900.0);
+    assert_eq!(summary.spent, -100.0);
+    assert_eq!(summary.to_assign, 600.0);
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use chrono::{Duration, NaiveDateTime};
+use rocket::http::Status;
+
+use common::{Setup, Tag, TagSpending, Transaction, TAG_NUMBER, URL_TAG, URL_TRANSACTION};
+
+fn create_transaction(setup: &Setup, transaction: &Transaction) -> i32 {
+    setup
+        .client
+        .post(URL_TRANSACTION)
+        .json(transaction)
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap()
+        .id
+        .unwrap()
+}
+
+#[test]
+fn test_tag_create() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Create tags
+    for index in 1..=TAG_NUMBER {
+        let tag_form = Tag::new(format!("tag_name_{index}"));
+        let response = client.post(URL_TAG).json(&tag_form).dispatch();
+        assert_eq!(response.status(), Status::Created);
+        assert_eq!(response.into_json::<Tag>(), Some(tag_form));
+    }
+}
+
+#[test]
+fn test_tag_create_same_name() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Create tag twice
+    let tag_form = Tag::new(String::from("tag_name"));
+    client.post(URL_TAG).json(&tag_form).dispatch();
+    let response = client.post(URL_TAG).json(&tag_form).dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn test_tag_list() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Create tags
+    let mut tag_forms = Vec::with_capacity(TAG_NUMBER);
+    for index in 1..=TAG_NUMBER {
+        let tag = Tag::new(format!("tag_name_{index}"));
+        client.post(URL_TAG).json(&tag).dispatch();
+        tag_forms.push(tag);
+    }
+    // Read tags
+    let response = client.get(URL_TAG).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_json::<Vec<Tag>>().unwrap(), tag_forms);
+}
+
+#[test]
+fn test_tag_read_not_found() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Try reading
+    let response = client.get(format!("{}/0", URL_TAG)).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_tag_update() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let tag_id = setup.create_tag();
+    // Update tag
+    let new_tag = Tag::new(String::from("new_name"));
+    let response = client
+        .put(format!("{}/{}", URL_TAG, tag_id))
+        .json(&new_tag)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let returned_tag = response.into_json::<Tag>().unwrap();
+    assert_eq!(returned_tag, new_tag);
+    assert_eq!(returned_tag.id, Some(tag_id));
+}
+
+#[test]
+fn test_tag_update_same_name() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let tag_form = Tag::new(String::from("tag_name"));
+    client.post(URL_TAG).json(&tag_form).dispatch();
+    let tag_id = setup.create_tag();
+    // Rename a tag as another one
+    let response = client
+        .put(format!("{}/{}", URL_TAG, tag_id))
+        .json(&tag_form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    // Unknown tags are not found
+    let response = client
+        .put(format!("{}/{}", URL_TAG, 0))
+        .json(&Tag::new(String::from("other_name")))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_tag_transactions() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let tag_id = setup.create_tag();
+    let other_tag_id = setup.create_tag();
+    // Create transactions and tag some of them
+    let date = NaiveDateTime::parse_from_str("2022-06-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let transactions = [
+        Transaction::new(String::from("t1"), 10.1, date, account_id, None),
+        Transaction::new(String::from("t2"), 20.2, date, account_id, None),
+        Transaction::new(String::from("t3"), 30.3, date, account_id, None),
+    ];
+    let ids: Vec<i32> = transactions
+        .iter()
+        .map(|transaction| create_transaction(&setup, transaction))
+        .collect();
+    for id in &ids[..2] {
+        let response = client
+            .put(format!("{}/{}/tags/{}", URL_TRANSACTION, id, tag_id))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+    client
+        .put(format!(
+            "{}/{}/tags/{}",
+            URL_TRANSACTION, ids[1], other_tag_id
+        ))
+        .dispatch();
+    // List transactions for the tag
+    let response = client
+        .get(format!("{}/{}/transactions", URL_TAG, tag_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.into_json::<Vec<Transaction>>().unwrap(),
+        transactions[..2]
+    );
+    // List tags for a transaction
+    let tags = client
+        .get(format!("{}/{}/tags", URL_TRANSACTION, ids[1]))
+        .dispatch()
+        .into_json::<Vec<Tag>>()
+        .unwrap();
+    assert_eq!(tags.len(), 2);
+    // Remove a tag
+    client
+        .delete(format!("{}/{}/tags/{}", URL_TRANSACTION, ids[0], tag_id))
+        .dispatch();
+    assert_eq!(
+        client
+            .get(format!("{}/{}/transactions", URL_TAG, tag_id))
+            .dispatch()
+            .into_json::<Vec<Transaction>>()
+            .unwrap(),
+        transactions[1..2]
+    );
+}
+
+#[test]
+fn test_tag_transaction_not_found() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let tag_id = setup.create_tag();
+    // Try tagging a missing transaction
+    let response = client
+        .put(format!("{}/0/tags/{}", URL_TRANSACTION, tag_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn test_tag_spending() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let tag_id = setup.create_tag();
+    // Create transactions over three months and tag them
+    let date = NaiveDateTime::parse_from_str("2022-06-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let transactions = [
+        Transaction::new(String::from("t1_june"), -10.0, date, account_id, None),
+        Transaction::new(
+            String::from("t2_july"),
+            -20.0,
+            date + Duration::days(31),
+            account_id,
+            None,
+        ),
+        Transaction::new(
+            String::from("t3_july"),
+            -30.0,
+            date + Duration::days(59),
+            account_id,
+            None,
+        ),
+        Transaction::new(
+            String::from("t4_august"),
+            -40.0,
+            date + Duration::days(65),
+            account_id,
+            None,
+        ),
+    ];
+    for transaction in &transactions {
+        let id = create_transaction(&setup, transaction);
+        client
+            .put(format!("{}/{}/tags/{}", URL_TRANSACTION, id, tag_id))
+            .dispatch();
+    }
+    // Spending from July 1st to July 30th, both days included
+    let response = client
+        .get(format!(
+            "{}/spending?from=2022-07-01&to=2022-07-30",
+            URL_TAG
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let spending = response.into_json::<Vec<TagSpending>>().unwrap();
+    assert_eq!(spending.len(), 1);
+    assert_eq!(spending[0].tag_id, tag_id);
+    assert_eq!(spending[0].total, -50.0);
+    // Transactions for the same period
+    let response = client
+        .get(format!(
+            "{}/{}/transactions?from=2022-07-01&to=2022-07-30",
+            URL_TAG, tag_id
+        ))
+        .dispatch();
+    assert_eq!(
+        response.into_json::<Vec<Transaction>>().unwrap(),
+        transactions[1..=2]
+    );
+}
+
+#[test]
+fn test_tag_spending_invalid_date() {
+    // Setup test
+    let client = &Setup::new().client;
+    // Try an invalid date
+    let response = client
+        .get(format!("{}/spending?from=2022-07-01&to=july", URL_TAG))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
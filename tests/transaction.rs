@@ -1,10 +1,14 @@
 mod common;
 
 use chrono::{Duration, NaiveDateTime};
-use rocket::http::Status;
+use rocket::http::{Header, Status};
+use rocket::serde::json::json;
 use std::iter::zip;
 
-use common::{Setup, Transaction, TRANSACTION_NUMBER, URL_ACCOUNT, URL_BUCKET, URL_TRANSACTION};
+use common::{
+    if_match, Setup, Transaction, Transfer, TRANSACTION_NUMBER, URL_ACCOUNT, URL_BUCKET,
+    URL_TRANSACTION,
+};
 
 fn default_transaction(account_id: i32) -> Transaction {
     let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
@@ -188,6 +192,7 @@ fn test_transaction_update() {
     let new_transaction = transaction_form.with_name(String::from("new_transaction_name"));
     let response_update = client
         .put(format!("{}/{}", URL_TRANSACTION, transaction_id))
+        .header(if_match(1))
         .json(&new_transaction)
         .dispatch();
     assert_eq!(response_update.status(), Status::Ok);
@@ -205,6 +210,115 @@ fn test_transaction_update() {
     );
 }
 
+#[test]
+fn test_transaction_update_concurrent() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let transaction_form = default_transaction(account_id);
+    let transaction_id = client
+        .post(URL_TRANSACTION)
+        .json(&transaction_form)
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap()
+        .id
+        .unwrap();
+    let url = format!("{}/{}", URL_TRANSACTION, transaction_id);
+    // Reads carry the version as ETag
+    let response = client.get(url.clone()).dispatch();
+    let etag = response.headers().get_one("ETag").unwrap().to_string();
+    assert_eq!(etag, "\"1\"");
+    // Updates require the version
+    let response = client.put(url.clone()).json(&transaction_form).dispatch();
+    assert_eq!(response.status(), Status::PreconditionRequired);
+    // The first update wins, the second one read an outdated version
+    let response = client
+        .put(url.clone())
+        .header(Header::new("If-Match", etag.clone()))
+        .json(&transaction_form.with_name(String::from("phone")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("ETag"), Some("\"2\""));
+    let response = client
+        .put(url.clone())
+        .header(Header::new("If-Match", etag))
+        .json(&default_transaction(account_id).with_name(String::from("laptop")))
+        .dispatch();
+    assert_eq!(response.status(), Status::PreconditionFailed);
+    let transaction = client
+        .get(url.clone())
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    assert_eq!(transaction.name, "phone");
+    // Malformed versions are refused, `*` matches any version
+    let response = client
+        .put(url.clone())
+        .header(Header::new("If-Match", "\"one\""))
+        .json(&default_transaction(account_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    let response = client
+        .put(url)
+        .header(Header::new("If-Match", "*"))
+        .json(&default_transaction(account_id).with_name(String::from("tablet")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("ETag"), Some("\"3\""));
+}
+
+#[test]
+fn test_transaction_patch() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let bucket_id = setup.create_bucket();
+    let mut transaction_form = default_transaction(account_id);
+    transaction_form.bucket_id = Some(bucket_id);
+    let transaction_id = client
+        .post(URL_TRANSACTION)
+        .json(&transaction_form)
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap()
+        .id
+        .unwrap();
+    let url = format!("{}/{}", URL_TRANSACTION, transaction_id);
+    // Clear the bucket, other fields are kept
+    let response = client
+        .patch(url.clone())
+        .header(if_match(1))
+        .json(&json!({ "bucket_id": null }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    transaction_form.bucket_id = None;
+    assert_eq!(response.into_json::<Transaction>(), Some(transaction_form));
+    // The merged transaction is validated
+    for (patch, status) in [
+        (json!({ "amount": "a lot" }), Status::UnprocessableEntity),
+        (json!({ "name": null }), Status::UnprocessableEntity),
+        (json!({ "id": 1 }), Status::UnprocessableEntity),
+        (json!({ "bucket_id": bucket_id + 1 }), Status::Conflict),
+    ] {
+        let response = client
+            .patch(url.clone())
+            .header(if_match(2))
+            .json(&patch)
+            .dispatch();
+        assert_eq!(response.status(), status);
+    }
+    // Patches need the latest version too
+    let response = client
+        .patch(url)
+        .header(if_match(1))
+        .json(&json!({ "name": "stale" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::PreconditionFailed);
+}
+
 #[test]
 fn test_transaction_destroy() {
     // Setup test
@@ -364,3 +478,241 @@ fn test_transaction_per_bucket() {
         );
     }
 }
+
+#[test]
+fn test_transaction_memo_and_status() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Create a transaction with a memo and a status
+    let transaction_form = default_transaction(account_id)
+        .with_memo(String::from("Split with Alice"))
+        .with_status("cleared");
+    let response = client
+        .post(URL_TRANSACTION)
+        .json(&transaction_form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(response.into_json::<Transaction>(), Some(transaction_form));
+}
+
+#[test]
+fn test_transaction_invalid_status() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Try creating a transaction with an unknown status
+    let transaction_form = default_transaction(account_id).with_status("pending");
+    let response = client
+        .post(URL_TRANSACTION)
+        .json(&transaction_form)
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[test]
+fn test_transaction_per_status() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let other_account_id = setup.create_account();
+    // Create transactions with different statuses
+    let transactions = [
+        default_transaction(account_id).with_status("cleared"),
+        default_transaction(account_id),
+        default_transaction(account_id).with_status("reconciled"),
+        default_transaction(other_account_id).with_status("cleared"),
+    ];
+    for transaction in &transactions {
+        client.post(URL_TRANSACTION).json(transaction).dispatch();
+    }
+    // List cleared transactions
+    let response = client
+        .get(format!("{}?status=cleared", URL_TRANSACTION))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let cleared = response.into_json::<Vec<Transaction>>().unwrap();
+    assert_eq!(cleared.len(), 2);
+    assert_eq!(cleared[0], transactions[0]);
+    assert_eq!(cleared[1], transactions[3]);
+    // List cleared transactions for an account
+    let response = client
+        .get(format!(
+            "{}/{}/transactions?status=cleared",
+            URL_ACCOUNT, account_id
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.into_json::<Vec<Transaction>>().unwrap(),
+        transactions[..1]
+    );
+    // List cleared transactions for a bucket, in a month
+    let bucket_id = setup.create_bucket();
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let bucket_transactions = [
+        Transaction::new(
+            String::from("cleared"),
+            -5.0,
+            date,
+            account_id,
+            Some(bucket_id),
+        )
+        .with_status("cleared"),
+        Transaction::new(
+            String::from("uncleared"),
+            -5.0,
+            date,
+            account_id,
+            Some(bucket_id),
+        ),
+    ];
+    for transaction in &bucket_transactions {
+        client.post(URL_TRANSACTION).json(transaction).dispatch();
+    }
+    for url in [
+        format!("{}/{}/transactions?status=cleared", URL_BUCKET, bucket_id),
+        format!(
+            "{}/{}/transactions/2022/7?status=cleared",
+            URL_BUCKET, bucket_id
+        ),
+    ] {
+        let response = client.get(url).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_json::<Vec<Transaction>>().unwrap(),
+            bucket_transactions[..1]
+        );
+    }
+}
+
+#[test]
+fn test_transaction_transfer() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let from_account_id = setup.create_account();
+    let to_account_id = setup.create_account();
+    // Transfer between accounts
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let response = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("Savings"),
+            amount: 100.0,
+            date,
+            from_account_id,
+            to_account_id,
+        })
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let legs = response.into_json::<Vec<Transaction>>().unwrap();
+    assert_eq!(legs.len(), 2);
+    assert_eq!(legs[0].account_id, from_account_id);
+    assert_eq!(legs[0].amount, -100.0);
+    assert_eq!(legs[0].transfer_account_id, Some(to_account_id));
+    assert_eq!(legs[1].account_id, to_account_id);
+    assert_eq!(legs[1].amount, 100.0);
+    assert_eq!(legs[1].transfer_account_id, Some(from_account_id));
+}
+
+#[test]
+fn test_transaction_transfer_same_account() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    // Try transferring to the same account
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let response = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("Savings"),
+            amount: 100.0,
+            date,
+            from_account_id: account_id,
+            to_account_id: account_id,
+        })
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn test_transaction_transfer_legs() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let from_account_id = setup.create_account();
+    let to_account_id = setup.create_account();
+    let date = NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut legs = client
+        .post(format!("{}/transfer", URL_TRANSACTION))
+        .json(&Transfer {
+            name: String::from("Savings"),
+            amount: 100.0,
+            date,
+            from_account_id,
+            to_account_id,
+        })
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap();
+    let (sent_id, received_id) = (legs[0].id.unwrap(), legs[1].id.unwrap());
+    // Updating a leg updates the other one
+    let mut sent = legs.remove(0).with_name(String::from("Rainy day"));
+    sent.amount = -150.0;
+    let response = client
+        .put(format!("{}/{}", URL_TRANSACTION, sent_id))
+        .header(if_match(1))
+        .json(&sent)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let received = client
+        .get(format!("{}/{}", URL_TRANSACTION, received_id))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap();
+    assert_eq!(received.name, "Rainy day");
+    assert_eq!(received.amount, 150.0);
+    // A leg can't stop being a transfer
+    sent.transfer_account_id = None;
+    let response = client
+        .put(format!("{}/{}", URL_TRANSACTION, sent_id))
+        .header(if_match(2))
+        .json(&sent)
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    // Deleting a leg trashes both, restoring one brings both back
+    client
+        .delete(format!("{}/{}", URL_TRANSACTION, received_id))
+        .dispatch();
+    let response = client
+        .get(format!("{}/{}", URL_TRANSACTION, sent_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client
+        .post(format!("{}/{}/restore", URL_TRANSACTION, sent_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .get(format!("{}/{}", URL_TRANSACTION, received_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_transaction_create_transfer() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let other_account_id = setup.create_account();
+    // Transfers are only made with both legs
+    let mut transaction = default_transaction(account_id);
+    transaction.transfer_account_id = Some(other_account_id);
+    let response = client.post(URL_TRANSACTION).json(&transaction).dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+
+use common::{AuditEntry, Balance, Fill, Setup, Transaction, Trash};
+use common::{URL_ACCOUNT, URL_FILL, URL_TRANSACTION, URL_TRASH};
+
+fn default_date() -> NaiveDateTime {
+    NaiveDateTime::parse_from_str("2022-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[test]
+fn test_trash_transaction() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let account_id = setup.create_account();
+    let id = client
+        .post(URL_TRANSACTION)
+        .json(&Transaction::new(
+            String::from("trashed"),
+            -42.0,
+            default_date(),
+            account_id,
+            None,
+        ))
+        .dispatch()
+        .into_json::<Transaction>()
+        .unwrap()
+        .id
+        .unwrap();
+    // Deleted transactions leave lists and balances for the trash
+    client
+        .delete(format!("{}/{}", URL_TRANSACTION, id))
+        .dispatch();
+    let transactions = client
+        .get(URL_TRANSACTION)
+        .dispatch()
+        .into_json::<Vec<Transaction>>()
+        .unwrap();
+    assert!(transactions.is_empty());
+    let response = client.get(format!("{}/{}", URL_TRANSACTION, id)).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    let balance = client
+        .get(format!("{}/{}/balance", URL_ACCOUNT, account_id))
+        .dispatch()
+        .into_json::<Balance>()
+        .unwrap();
+    assert_eq!(balance.balance, 0.0);
+    let trash = client
+        .get(URL_TRASH)
+        .dispatch()
+        .into_json::<Trash>()
+        .unwrap();
+    assert_eq!(trash.transactions.len(), 1);
+    assert_eq!(trash.transactions[0].id, Some(id));
+    // Restore the transaction
+    let response = client
+        .post(format!("{}/{}/restore", URL_TRANSACTION, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let balance = client
+        .get(format!("{}/{}/balance", URL_ACCOUNT, account_id))
+        .dispatch()
+        .into_json::<Balance>()
+        .unwrap();
+    assert_eq!(balance.balance, -42.0);
+    // Only trashed transactions are restored
+    let response = client
+        .post(format!("{}/{}/restore", URL_TRANSACTION, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_trash_fill() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let bucket_id = setup.create_bucket();
+    let id = client
+        .post(URL_FILL)
+        .json(&Fill::new(10.0, default_date(), bucket_id))
+        .dispatch()
+        .into_json::<Fill>()
+        .unwrap()
+        .id
+        .unwrap();
+    client.delete(format!("{}/{}", URL_FILL, id)).dispatch();
+    let fills = client
+        .get(URL_FILL)
+        .dispatch()
+        .into_json::<Vec<Fill>>()
+        .unwrap();
+    assert!(fills.is_empty());
+    // Restore the fill
+    let response = client
+        .post(format!("{}/{}/restore", URL_FILL, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let fills = client
+        .get(URL_FILL)
+        .dispatch()
+        .into_json::<Vec<Fill>>()
+        .unwrap();
+    assert_eq!(fills.len(), 1);
+}
+
+#[test]
+fn test_trash_empty() {
+    // Setup test
+    let setup = Setup::new();
+    let client = &setup.client;
+    let bucket_id = setup.create_bucket();
+    let id = client
+        .post(URL_FILL)
+        .json(&Fill::new(10.0, default_date(), bucket_id))
+        .dispatch()
+        .into_json::<Fill>()
+        .unwrap()
+        .id
+        .unwrap();
+    client.delete(format!("{}/{}", URL_FILL, id)).dispatch();
+    // Emptying the trash deletes for good
+    let response = client.delete(URL_TRASH).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let trash = client
+        .get(URL_TRASH)
+        .dispatch()
+        .into_json::<Trash>()
+        .unwrap();
+    assert!(trash.fills.is_empty());
+    let response = client
+        .post(format!("{}/{}/restore", URL_FILL, id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    // Both the deletion and the permanent one are recorded
+    let history = client
+        .get(format!("{}/{}/history", URL_FILL, id))
+        .dispatch()
+        .into_json::<Vec<AuditEntry>>()
+        .unwrap();
+    let actions: Vec<&str> = history.iter().map(|entry| entry.action.as_str()).collect();
+    assert_eq!(actions, ["create", "delete", "delete"]);
+}